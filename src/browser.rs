@@ -1,9 +1,12 @@
+use crate::config;
+
 /// Search type kanji
 const KANJI_SEARCH_TYPE: u8 = 1;
 
 /// Returns a jotoba-search url with the given query and search type
 pub fn get_jotoba_url(query: &str, s_type: u8) -> String {
-    format!("https://jotoba.de/search/{query}?t={s_type}")
+    let base = &config::get().dictionary_url;
+    format!("{base}/search/{query}?t={s_type}")
 }
 
 /// Opens jotoba kanji page with given kanji as query
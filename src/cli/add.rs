@@ -1,3 +1,4 @@
+use super::output::{self, Format};
 use crate::{japanese::JapaneseExt, storage::Storage, utils};
 use clap::ArgMatches;
 
@@ -12,22 +13,18 @@ pub fn run(mut storage: Storage, app: &ArgMatches) {
 
     for kanji in inp.chars().filter(|i| i.is_kanji()) {
         if storage.add(kanji) {
-            added.push(kanji.to_string());
+            added.push(kanji);
         }
     }
 
-    if !added.is_empty() {
-        println!("Added {}", added.join(","));
-    } else {
-        println!("Nothing to add");
-    }
+    output::print_changed(Format::of(app), &added, "Added", "Nothing to add");
 }
 
 pub(crate) fn parse_kanji_arg(app: &ArgMatches) -> Option<String> {
     let val = match app.value_of("kanji") {
         Some(v) => v,
         None => {
-            println!("Missing kanji!");
+            output::print_error(Format::of(app), "Missing kanji!");
             return None;
         }
     };
@@ -36,7 +33,7 @@ pub(crate) fn parse_kanji_arg(app: &ArgMatches) -> Option<String> {
         Some(utils::read_stdin())
     } else {
         if !val.has_kanji() {
-            println!("Missing kanji!");
+            output::print_error(Format::of(app), "Missing kanji!");
             return None;
         }
         Some(val.to_string())
@@ -1,8 +1,17 @@
+use super::output::{self, Format, Kanji};
 use crate::storage::Storage;
 use clap::ArgMatches;
 
 /// Adds kanji to the storage
 pub fn run(storage: Storage, app: &ArgMatches) {
+    if Format::of(app).is_json() {
+        let items = storage
+            .iter()
+            .filter_map(|i| storage.get_srs_storage().find(i.get_id()));
+        output::print_json(&Kanji::all(&storage, items));
+        return;
+    }
+
     let separated = !app.is_present("raw");
 
     for (pos, kanji) in storage.iter().map(|i| i.get_literal()).enumerate() {
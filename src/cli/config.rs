@@ -0,0 +1,87 @@
+use super::output::{self, Format};
+use crate::config::{self, Config};
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+
+/// Show or change values of the config file
+pub fn run(app: &ArgMatches) {
+    let format = Format::of(app);
+    let path = match Config::path(app.value_of("config")) {
+        Some(path) => path,
+        None => {
+            output::print_error(format, "Couldn't determine the location of the config file");
+            return;
+        }
+    };
+
+    let key = match app.value_of("key") {
+        Some(key) => key,
+        None => {
+            let config = config::get();
+            if format.is_json() {
+                let values = config::KEYS
+                    .iter()
+                    .map(|key| (*key, config.get(key).unwrap_or_default()))
+                    .collect::<BTreeMap<_, _>>();
+                output::print_json(&values);
+                return;
+            }
+
+            println!("Config file: {}", path.display());
+            for key in config::KEYS {
+                println!("{key} = {}", config.get(key).unwrap_or_default());
+            }
+            return;
+        }
+    };
+
+    let value = match app.value_of("value") {
+        Some(value) => value,
+        None => {
+            match config::get().get(key) {
+                Some(value) => print_value(format, key, &value),
+                None => output::print_error(format, &format!("Unknown key '{key}'")),
+            }
+            return;
+        }
+    };
+
+    // Changes are made to the file so values overridden by flags don't get written
+    let mut config = match Config::load(&path) {
+        Ok(config) => config,
+        Err(err) => {
+            let message = format!("Invalid config file {}: {err}", path.display());
+            output::print_error(format, &message);
+            return;
+        }
+    };
+
+    if let Err(err) = config.set(key, value) {
+        output::print_error(format, &err.to_string());
+        return;
+    }
+
+    match config.save(&path) {
+        Ok(()) => {
+            let value = config.get(key).unwrap_or_default();
+            if format.is_json() {
+                print_value(format, key, &value);
+            } else {
+                println!("{key} = {value}");
+            }
+        }
+        Err(err) => {
+            let message = format!("Failed to write {}: {err}", path.display());
+            output::print_error(format, &message);
+        }
+    }
+}
+
+/// Prints the value of a single key. JSON gets an object with the key
+fn print_value(format: Format, key: &str, value: &str) {
+    if format.is_json() {
+        output::print_json(&serde_json::json!({ key: value }));
+    } else {
+        println!("{value}");
+    }
+}
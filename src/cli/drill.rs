@@ -0,0 +1,197 @@
+//! Drill mode which repeatedly prints small shuffled groups of the kanji of a session until each
+//! one has been written a few times. Kanji which have been failed are picked more often and the
+//! amount of failures determines their grade
+
+use crate::{japanese::JapaneseExt, sm2::RepQuality, utils};
+use clap::ArgMatches;
+use rand::{seq::SliceRandom, Rng};
+use std::collections::HashMap;
+
+/// Default amount of kanji written at once
+const DEFAULT_GROUP_SIZE: usize = 3;
+
+/// Default amount of times each kanji has to be written correctly
+const DEFAULT_REPETITIONS: usize = 3;
+
+/// Additional weight a kanji gets for each time it has been failed
+const FAILURE_WEIGHT: f64 = 2.0;
+
+pub struct DrillConfig {
+    group_size: usize,
+    repetitions: usize,
+}
+
+/// Runs a drill for `kanji` and returns the grades resulting from it
+pub fn run(kanji: &[char], config: &DrillConfig) -> HashMap<char, RepQuality> {
+    let mut drill = Drill::new(kanji, config);
+    let mut rng = rand::thread_rng();
+
+    println!("Write the kanji of each group and enter the ones you got wrong");
+    while let Some(group) = drill.next_group(&mut rng) {
+        let literals = group
+            .iter()
+            .map(|i| kanji[*i].to_string())
+            .collect::<Vec<_>>();
+        println!("\n{}", literals.join("  "));
+        utils::print_stdout("Failed > ");
+
+        let failed = utils::read_std_line()
+            .chars()
+            .filter(|i| i.is_kanji())
+            .collect::<Vec<_>>();
+        drill.record(&group, &failed);
+    }
+
+    drill.grades()
+}
+
+/// Parses the drill options of a session
+pub fn parse_config(app: &ArgMatches) -> DrillConfig {
+    DrillConfig {
+        group_size: utils::parse_nr(app.value_of("group-size"), DEFAULT_GROUP_SIZE).max(1),
+        repetitions: utils::parse_nr(app.value_of("repetitions"), DEFAULT_REPETITIONS).max(1),
+    }
+}
+
+struct Drill {
+    kanji: Vec<char>,
+    /// Times each kanji has been written correctly
+    correct: Vec<usize>,
+    /// Times each kanji has been failed
+    failures: Vec<usize>,
+    last_group: Vec<usize>,
+    group_size: usize,
+    repetitions: usize,
+}
+
+impl Drill {
+    fn new(kanji: &[char], config: &DrillConfig) -> Self {
+        Self {
+            kanji: kanji.to_vec(),
+            correct: vec![0; kanji.len()],
+            failures: vec![0; kanji.len()],
+            last_group: vec![],
+            group_size: config.group_size,
+            repetitions: config.repetitions,
+        }
+    }
+
+    /// Returns `true` if the kanji at `index` doesn't have to be written anymore. Kanji which
+    /// keep being failed are done after having been written twice as often as required
+    fn is_done(&self, index: usize) -> bool {
+        let written = self.correct[index] + self.failures[index];
+        self.correct[index] >= self.repetitions || written >= self.repetitions * 2
+    }
+
+    /// Picks the next group of kanji to write. Kanji of the previous group are only picked if
+    /// there are no others left. `None` if all kanji are done
+    fn next_group<R: Rng>(&mut self, rng: &mut R) -> Option<Vec<usize>> {
+        let remaining = (0..self.kanji.len())
+            .filter(|i| !self.is_done(*i))
+            .collect::<Vec<_>>();
+        if remaining.is_empty() {
+            return None;
+        }
+
+        let fresh = remaining
+            .iter()
+            .copied()
+            .filter(|i| !self.last_group.contains(i))
+            .collect::<Vec<_>>();
+        let candidates = if fresh.is_empty() { remaining } else { fresh };
+
+        let weight = |i: &usize| 1.0 + self.failures[*i] as f64 * FAILURE_WEIGHT;
+        let group = candidates
+            .choose_multiple_weighted(rng, self.group_size, weight)
+            .map(|i| i.copied().collect::<Vec<_>>())
+            .unwrap_or(candidates);
+
+        self.last_group = group.clone();
+        Some(group)
+    }
+
+    /// Records that the kanji of `group` have been written and the ones in `failed` were wrong
+    fn record(&mut self, group: &[usize], failed: &[char]) {
+        for i in group {
+            if failed.contains(&self.kanji[*i]) {
+                self.failures[*i] += 1;
+            } else {
+                self.correct[*i] += 1;
+            }
+        }
+    }
+
+    /// Returns the grade of each kanji based on the amount of times it has been failed
+    fn grades(&self) -> HashMap<char, RepQuality> {
+        self.kanji
+            .iter()
+            .zip(self.failures.iter())
+            .map(|(k, f)| {
+                let grade = match f {
+                    0 => RepQuality::Grade4,
+                    1 => RepQuality::Grade3,
+                    _ => RepQuality::Grade2,
+                };
+                (*k, grade)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn drill(kanji: &[char], group_size: usize) -> Drill {
+        let config = DrillConfig {
+            group_size,
+            repetitions: 2,
+        };
+        Drill::new(kanji, &config)
+    }
+
+    #[test]
+    fn no_kanji_twice_in_a_row() {
+        let mut drill = drill(&['日', '月', '火', '水', '木'], 2);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut last = vec![];
+        let mut groups = 0;
+        while let Some(group) = drill.next_group(&mut rng) {
+            assert!(group.len() <= 2);
+            // Repeating is only allowed if nothing else is left
+            if group.iter().any(|i| last.contains(i)) {
+                assert!((0..5).all(|i| drill.is_done(i) || last.contains(&i)));
+            }
+            drill.record(&group, &[]);
+            last = group;
+            groups += 1;
+        }
+
+        assert!(groups >= 5);
+        assert!(drill.correct.iter().all(|i| *i == 2));
+    }
+
+    #[test]
+    fn failures_determine_grades() {
+        let mut drill = drill(&['日', '月', '火'], 3);
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let group = drill.next_group(&mut rng).unwrap();
+        assert_eq!(3, group.len());
+        drill.record(&group, &['月', '火']);
+
+        while let Some(group) = drill.next_group(&mut rng) {
+            drill.record(&group, &['火']);
+        }
+
+        // Kanji which keep being failed are given up on after twice the repetitions
+        assert_eq!(4, drill.failures[2]);
+
+        let grades = drill.grades();
+        assert_eq!(RepQuality::Grade4, grades[&'日']);
+        assert_eq!(RepQuality::Grade3, grades[&'月']);
+        assert_eq!(RepQuality::Grade2, grades[&'火']);
+    }
+}
@@ -0,0 +1,199 @@
+use super::output::{self, Format};
+use crate::{
+    storage::{
+        anki::{AnkiCollection, Deck, Field},
+        export::{self, FileFormat, ImportPolicy, ImportSummary},
+        Storage,
+    },
+    utils,
+};
+use clap::ArgMatches;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+/// Writes all kanji with their SRS data and reviews to a file or stdout
+pub fn export(storage: Storage, app: &ArgMatches) {
+    let format = Format::of(app);
+    let path = app.value_of("file").unwrap_or("-");
+    let file_format = match file_format(app, path) {
+        Ok(file_format) => file_format,
+        Err(err) => return output::print_error(format, &err),
+    };
+
+    let export = storage.export();
+    let res = if path == "-" {
+        export::write(&export, file_format, io::stdout().lock())
+    } else {
+        File::create(path)
+            .map_err(Into::into)
+            .and_then(|file| export::write(&export, file_format, BufWriter::new(file)))
+    };
+    if let Err(err) = res {
+        return output::print_error(format, &format!("Failed to export: {err}"));
+    }
+
+    if path == "-" {
+        return;
+    }
+
+    // Reviews don't fit into CSV files
+    let reviews = match file_format {
+        FileFormat::Json => export.reviews.len(),
+        FileFormat::Csv => 0,
+    };
+    if format.is_json() {
+        output::print_json(&serde_json::json!({
+            "kanji": export.kanji.len(),
+            "reviews": reviews,
+        }));
+    } else {
+        println!(
+            "Exported {} kanji and {reviews} reviews to {path}",
+            export.kanji.len()
+        );
+    }
+}
+
+/// Imports kanji from a file or stdin written by `export`
+pub fn import(mut storage: Storage, app: &ArgMatches) {
+    if let Some(("anki", sub_matches)) = app.subcommand() {
+        return anki(storage, sub_matches);
+    }
+
+    let format = Format::of(app);
+    let path = app.value_of("file").unwrap_or("-");
+    let file_format = match file_format(app, path) {
+        Ok(file_format) => file_format,
+        Err(err) => return output::print_error(format, &err),
+    };
+    let policy = match policy(app) {
+        Ok(policy) => policy,
+        Err(err) => return output::print_error(format, &err),
+    };
+
+    let export = if path == "-" {
+        export::read(file_format, io::stdin().lock())
+    } else {
+        File::open(path)
+            .map_err(Into::into)
+            .and_then(|file| export::read(file_format, BufReader::new(file)))
+    };
+    let summary = match export.and_then(|export| storage.import(&export, policy)) {
+        Ok(summary) => summary,
+        Err(err) => return output::print_error(format, &format!("Failed to import: {err}")),
+    };
+    print_summary(format, &summary);
+}
+
+/// Imports the kanji of a deck in an Anki collection along with their progress
+fn anki(mut storage: Storage, app: &ArgMatches) {
+    let format = Format::of(app);
+    let path = app.value_of("file").unwrap();
+    let policy = match policy(app) {
+        Ok(policy) => policy,
+        Err(err) => return output::print_error(format, &err),
+    };
+
+    let collection = match AnkiCollection::open(path) {
+        Ok(collection) => collection,
+        Err(err) => return output::print_error(format, &format!("Failed to open {path}: {err}")),
+    };
+
+    let decks = collection.decks();
+    let deck = match app.value_of("deck") {
+        Some(name) => match decks.iter().find(|i| i.name == name) {
+            Some(deck) => deck,
+            None => return output::print_error(format, &format!("No deck named '{name}'")),
+        },
+        None => match pick(format, "deck", &decks, |i: &Deck| {
+            format!("{} ({} notes)", i.name, i.notes)
+        }) {
+            Ok(deck) => deck,
+            Err(err) => return output::print_error(format, &err),
+        },
+    };
+
+    // Fields without any kanji aren't worth asking for
+    let fields = collection
+        .fields(deck)
+        .into_iter()
+        .filter(|i| i.with_kanji > 0)
+        .collect::<Vec<_>>();
+    let field = match app.value_of("field") {
+        Some(name) => name,
+        None => match pick(format, "field", &fields, |i: &Field| {
+            format!("{} ({} notes with kanji)", i.name, i.with_kanji)
+        }) {
+            Ok(field) => field.name.as_str(),
+            Err(err) => return output::print_error(format, &err),
+        },
+    };
+
+    let export = collection.to_export(deck, field, &storage.day_boundary(), storage.now());
+    let summary = match export.and_then(|export| storage.import(&export, policy)) {
+        Ok(summary) => summary,
+        Err(err) => return output::print_error(format, &format!("Failed to import: {err}")),
+    };
+    print_summary(format, &summary);
+}
+
+fn print_summary(format: Format, summary: &ImportSummary) {
+    if format.is_json() {
+        output::print_json(summary);
+    } else {
+        println!(
+            "Added {} kanji, updated {}, skipped {} and imported {} reviews",
+            summary.added, summary.updated, summary.skipped, summary.reviews
+        );
+    }
+}
+
+/// Returns the format passed with `--file-format` or the one of the file's extension. Files
+/// without a known extension are JSON
+fn file_format(app: &ArgMatches, path: &str) -> Result<FileFormat, String> {
+    match app.value_of("file-format") {
+        Some(file_format) => file_format.parse(),
+        None => Ok(FileFormat::of(Path::new(path)).unwrap_or(FileFormat::Json)),
+    }
+}
+
+fn policy(app: &ArgMatches) -> Result<ImportPolicy, String> {
+    match app.value_of("policy") {
+        Some(policy) => policy.parse(),
+        None => Ok(ImportPolicy::default()),
+    }
+}
+
+/// Returns the only one of `options` or lets the user pick one. Scripts using JSON can't be
+/// asked and have to pass the option
+fn pick<'a, T, F>(format: Format, what: &str, options: &'a [T], label: F) -> Result<&'a T, String>
+where
+    F: Fn(&T) -> String,
+{
+    match options {
+        [] => return Err(format!("No {what} to import from")),
+        [option] => return Ok(option),
+        _ if format.is_json() => {
+            return Err(format!("Multiple {what}s found, pass one with --{what}"))
+        }
+        _ => (),
+    }
+
+    for (i, option) in options.iter().enumerate() {
+        println!("{}) {}", i + 1, label(option));
+    }
+    loop {
+        utils::print_stdout(&format!("Select the {what}> "));
+        let input = utils::read_std_line();
+        if input.is_empty() {
+            return Err("Nothing selected".to_string());
+        }
+        match input.trim().parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return Ok(&options[n - 1]),
+            _ => println!("Enter a number from 1 to {}", options.len()),
+        }
+    }
+}
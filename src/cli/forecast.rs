@@ -0,0 +1,98 @@
+use super::output::{self, Format};
+use crate::{
+    config,
+    storage::{forecast::ForecastDay, Storage},
+    utils,
+};
+use clap::ArgMatches;
+
+/// Width of the longest bar in characters
+const BAR_WIDTH: usize = 40;
+
+/// Shows how many reviews and new kanji are due on each of the next days or weeks
+pub fn run(storage: Storage, app: &ArgMatches) {
+    let weeks = app.value_of("weeks").map(|i| utils::parse_nr(Some(i), 0));
+    let days = match weeks {
+        Some(weeks) => weeks * 7,
+        None => utils::parse_nr(app.value_of("days"), 14),
+    };
+    let format = Format::of(app);
+    if days == 0 {
+        output::print_error(format, "Nothing to forecast");
+        return;
+    }
+
+    let new_per_day = if app.is_present("no-new") {
+        0
+    } else {
+        utils::parse_nr(app.value_of("new-count"), config::get().new_per_day)
+    };
+
+    let forecast = storage.forecast(days as u32, new_per_day);
+    let boundary = storage.day_boundary();
+    let rows = match weeks {
+        Some(_) => forecast
+            .chunks(7)
+            .map(|week| {
+                let row = ForecastDay {
+                    start: week[0].start,
+                    reviews: week.iter().map(|i| i.reviews).sum(),
+                    new: week.iter().map(|i| i.new).sum(),
+                };
+                (format!("from {}", boundary.format_day(row.start)), row)
+            })
+            .collect::<Vec<_>>(),
+        None => forecast
+            .iter()
+            .enumerate()
+            .map(|(i, day)| {
+                let label = match i {
+                    0 => "Today".to_string(),
+                    1 => "Tomorrow".to_string(),
+                    _ => boundary.format_day(day.start),
+                };
+                (label, *day)
+            })
+            .collect(),
+    };
+
+    if format.is_json() {
+        output::print_json(&rows.iter().map(|(_, i)| i).collect::<Vec<_>>());
+        return;
+    }
+
+    let max = rows.iter().map(|(_, i)| i.total()).max().unwrap_or(0);
+    let label_width = rows.iter().map(|(i, _)| i.len()).max().unwrap_or(0);
+    for (label, row) in rows.iter() {
+        println!(
+            "{label:<label_width$} {:<BAR_WIDTH$} {}",
+            bar(row, max),
+            counts(row)
+        );
+    }
+
+    let reviews = forecast.iter().map(|i| i.reviews).sum::<usize>();
+    let new = forecast.iter().map(|i| i.new).sum::<usize>();
+    println!();
+    println!("█ reviews ░ new kanji. {reviews} reviews and {new} new kanji in {days} days");
+}
+
+/// Returns the bar of `row` scaled so the one of a row with `max` kanji has the full width
+fn bar(row: &ForecastDay, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+
+    let scale = |n: usize| (n * BAR_WIDTH).div_ceil(max);
+    let reviews = scale(row.reviews);
+    let new = scale(row.total()) - reviews;
+    format!("{}{}", "█".repeat(reviews), "░".repeat(new))
+}
+
+fn counts(row: &ForecastDay) -> String {
+    if row.new > 0 {
+        format!("{} + {} new", row.reviews, row.new)
+    } else {
+        row.reviews.to_string()
+    }
+}
@@ -1,27 +1,110 @@
+use super::output::{self, Format, Kanji};
 use crate::{
+    config::{self, NewOrder},
     storage::{srs, Storage},
-    utils,
 };
 use clap::ArgMatches;
+use serde::Serialize;
+
+/// Kanji shown by `info`, grouped by when they are due
+#[derive(Serialize)]
+struct Info {
+    /// Order in which the new kanji are introduced
+    order: String,
+    next: Vec<Kanji>,
+    today: Vec<Kanji>,
+    learning: Vec<Kanji>,
+    buried: Vec<Kanji>,
+    suspended: Vec<Kanji>,
+    tomorrow: Vec<Kanji>,
+    future: Vec<Kanji>,
+}
 
 /// Show info about reviews
-pub fn run(storage: Storage, _app: &ArgMatches) {
-    let mut next = storage.get_srs_storage().get_new().collect::<Vec<_>>();
+pub fn run(storage: Storage, app: &ArgMatches) {
+    // Only cached information is used, kanji without any come last. Looking up the others is
+    // left to sessions
+    let order = config::get().new_order;
+    let format = Format::of(app);
+
+    let next = storage
+        .get_new_ordered(order)
+        .into_iter()
+        .map(|i| i.id)
+        .collect::<Vec<_>>();
+
+    let today = storage
+        .get_due_ordered(config::get().review_order)
+        .into_iter()
+        .map(|i| i.id)
+        .collect::<Vec<_>>();
+
+    let stepping = storage
+        .get_srs_storage()
+        .get_stepping(storage.now())
+        .map(|i| i.id)
+        .collect::<Vec<_>>();
+
+    let now = storage.now();
+    let buried = storage
+        .get_srs_storage()
+        .iter()
+        .filter(|i| i.is_buried(now) && !i.suspended)
+        .map(|i| i.id)
+        .collect::<Vec<_>>();
+
+    let suspended = storage
+        .get_srs_storage()
+        .iter()
+        .filter(|i| i.suspended)
+        .map(|i| i.id)
+        .collect::<Vec<_>>();
+
+    let tomorrow = reviews_tomorrow(&storage);
+
+    let tomorrow_time = storage.day_offset(1);
+    let mut future = all_due_ordered(&storage)
+        .into_iter()
+        .filter(|i| i.due_on > tomorrow_time && !i.suspended && !i.drill)
+        .collect::<Vec<_>>();
+    future.sort_by_key(|a| a.id);
+    future.sort_by_key(|a| a.due_on);
+    let future = future.into_iter().map(|i| i.id).collect::<Vec<_>>();
+
+    if format.is_json() {
+        let kanji = |ids: &[u32]| {
+            let items = ids
+                .iter()
+                .filter_map(|i| storage.get_srs_storage().find(*i));
+            Kanji::all(&storage, items)
+        };
+        output::print_json(&Info {
+            order: order.to_string(),
+            next: kanji(&next),
+            today: kanji(&today),
+            learning: kanji(&stepping),
+            buried: kanji(&buried),
+            suspended: kanji(&suspended),
+            tomorrow: kanji(&tomorrow),
+            future: kanji(&future),
+        });
+        return;
+    }
+
     if !next.is_empty() {
-        next.sort_by(|a, b| a.id.cmp(&b.id));
-        println!("Next: ");
-        print_review_day(
-            &storage,
-            &next.into_iter().map(|i| i.id).collect::<Vec<_>>(),
-        );
+        match order {
+            NewOrder::Added => println!("Next: "),
+            NewOrder::Random => println!("Next (random): "),
+            _ => println!("Next (by {order}): "),
+        }
+        print_kanji(&storage, &next);
         println!();
     }
 
-    let today = storage.get_srs_storage().get_due().collect::<Vec<_>>();
     if !today.is_empty() {
         let s = today
             .into_iter()
-            .filter_map(|i| storage.get_by_id(i.id))
+            .filter_map(|i| storage.get_by_id(i))
             .map(|i| i.get_literal().to_string())
             .collect::<Vec<_>>()
             .join(",");
@@ -29,28 +112,33 @@ pub fn run(storage: Storage, _app: &ArgMatches) {
         println!();
     }
 
-    let tomorrow = reviews_tomorrow(&storage);
+    if !stepping.is_empty() {
+        println!("Learning: ");
+        print_review_day(&storage, &stepping);
+        println!();
+    }
+
+    if !buried.is_empty() {
+        println!("Buried: ");
+        print_review_day(&storage, &buried);
+        println!();
+    }
+
+    if !suspended.is_empty() {
+        println!("Suspended: ");
+        print_review_day(&storage, &suspended);
+        println!();
+    }
+
     if !tomorrow.is_empty() {
         println!("Tomorrow: ");
         print_review_day(&storage, &tomorrow);
         println!();
     }
 
-    let tomorrow_time = utils::unix_n_days_offset(1);
-    let mut future = all_due_ordered(&storage)
-        .into_iter()
-        .filter(|i| i.due_on as u64 > tomorrow_time)
-        .collect::<Vec<_>>();
-    future.sort_by(|a, b| a.id.cmp(&b.id));
-    future.sort_by(|a, b| a.due_on.cmp(&b.due_on));
-    future.truncate(20);
-
     if !future.is_empty() {
         println!("Future: ");
-        print_review_day(
-            &storage,
-            &future.into_iter().map(|i| i.id).collect::<Vec<_>>(),
-        );
+        print_review_day(&storage, &future[..future.len().min(20)]);
     }
 
     if storage.is_empty() {
@@ -65,7 +153,11 @@ pub fn print_review_day(storage: &Storage, data: &[u32]) {
 
     let mut data = data.to_vec();
     data.sort();
+    print_kanji(storage, &data);
+}
 
+/// Prints the kanji with the ids `data` in the given order
+pub fn print_kanji(storage: &Storage, data: &[u32]) {
     let items = data
         .iter()
         .filter_map(|i| storage.get_by_id(*i))
@@ -86,18 +178,19 @@ pub fn print_review_day(storage: &Storage, data: &[u32]) {
 }
 
 pub fn reviews_tomorrow(storage: &Storage) -> Vec<u32> {
-    let 明日 = utils::unix_n_days_offset(1);
-    let 明後日 = utils::unix_n_days_offset(2);
+    let 明日 = storage.day_offset(1);
+    let 明後日 = storage.day_offset(2);
     storage
         .get_srs_storage()
         .iter()
-        .filter(|i| i.due_on >= 明日 && i.in_learning && i.due_on < 明後日)
+        .filter(|i| i.due_on >= 明日 && i.in_learning && i.due_on < 明後日 && i.step.is_none())
+        .filter(|i| !i.suspended && !i.drill)
         .map(|i| i.id)
         .collect()
 }
 
 pub fn all_due_ordered(storage: &Storage) -> Vec<&srs::Item> {
     let mut srs_items = storage.get_srs_storage().iter().collect::<Vec<_>>();
-    srs_items.sort_by(|a, b| a.due_on.cmp(&b.due_on));
+    srs_items.sort_by_key(|a| a.due_on);
     srs_items
 }
@@ -0,0 +1,98 @@
+use super::{
+    drill,
+    output::{self, Format, Kanji},
+};
+use crate::{browser, sm2::RepQuality, storage::Storage};
+use clap::ArgMatches;
+use term_table::{
+    row::Row,
+    table_cell::{Alignment, TableCell},
+    Table, TableStyle,
+};
+
+/// Lists leeches along with their lapses or drills the ones in the leech drill queue
+pub fn run(storage: Storage, app: &ArgMatches) {
+    if app.is_present("drill") {
+        run_drill(storage, app);
+        return;
+    }
+
+    let leeches = storage.get_srs_storage().get_leeches().collect::<Vec<_>>();
+    if Format::of(app).is_json() {
+        output::print_json(&Kanji::all(&storage, leeches));
+        return;
+    }
+
+    if leeches.is_empty() {
+        println!("No leeches");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.style = TableStyle::rounded();
+    table.add_row(Row::new(vec![
+        TableCell::new("Kanji"),
+        TableCell::new("Lapses"),
+        TableCell::new("Status"),
+    ]));
+
+    for leech in leeches {
+        let literal = match storage.get_by_id(leech.id) {
+            Some(item) => item.get_literal(),
+            None => continue,
+        };
+        let status = if leech.drill {
+            "drill queue"
+        } else if leech.suspended {
+            "suspended"
+        } else {
+            ""
+        };
+
+        table.add_row(Row::new(vec![
+            TableCell::new(literal),
+            TableCell::new_with_alignment(leech.lapses, 1, Alignment::Right),
+            TableCell::new(status),
+        ]));
+    }
+
+    println!("{}", table.render());
+}
+
+/// Drills the kanji in the leech drill queue. Kanji written without a single mistake go back
+/// into the regular sessions
+fn run_drill(mut storage: Storage, app: &ArgMatches) {
+    let queue = storage
+        .get_srs_storage()
+        .get_leeches()
+        .filter(|i| i.drill)
+        .filter_map(|i| storage.get_by_id(i.id))
+        .map(|i| (i.get_id(), i.get_literal()))
+        .collect::<Vec<_>>();
+    if queue.is_empty() {
+        println!("The leech drill queue is empty");
+        return;
+    }
+
+    let kanji = queue.iter().map(|i| i.1).collect::<Vec<_>>();
+    browser::open_kanji(&kanji.iter().collect::<String>());
+    let grades = drill::run(&kanji, &drill::parse_config(app));
+
+    let mut released = vec![];
+    for (id, literal) in queue {
+        if grades.get(&literal) != Some(&RepQuality::Grade4) {
+            continue;
+        }
+
+        if let Some(item) = storage.get_srs_mut(id) {
+            item.drill = false;
+            released.push(literal.to_string());
+        }
+    }
+
+    if released.is_empty() {
+        println!("All kanji stay in the leech drill queue");
+    } else {
+        println!("Back in the sessions: {}", released.join(","));
+    }
+}
@@ -1,12 +1,22 @@
 pub mod add;
 pub mod all;
+pub mod config;
+pub mod drill;
+pub mod export;
 pub mod fix_db;
+pub mod forecast;
 pub mod info;
+pub mod leeches;
+pub mod output;
 pub mod remove;
 pub mod reset;
 pub mod review;
 pub mod run;
+pub mod scheduler;
 pub mod stats;
+pub mod suspend;
+pub mod tui;
+pub mod undo;
 
 pub use run::run;
 
@@ -16,6 +26,39 @@ pub fn build() -> App<'static> {
     let app = App::new("ksrs")
         .about("Tool to help learning kanji")
         .setting(AppSettings::TrailingVarArg)
+        .arg(
+            Arg::new("db")
+                .help("Directory of the database. Can also be set with KANJI_SRS_DB")
+                .takes_value(true)
+                .global(true)
+                .long("db"),
+        )
+        .arg(
+            Arg::new("config")
+                .help("Config file to use. Can also be set with KANJI_SRS_CONFIG")
+                .takes_value(true)
+                .global(true)
+                .long("config"),
+        )
+        .arg(
+            Arg::new("format")
+                .help("Output format of subcommands. json is meant for scripts")
+                .takes_value(true)
+                .possible_values(["text", "json"])
+                .default_value("text")
+                .global(true)
+                .long("format"),
+        )
+        .arg(
+            Arg::new("now")
+                .help(
+                    "Pretend the current time is the given one, eg. 2022-03-10 or 2022-03-10 23:30",
+                )
+                .takes_value(true)
+                .global(true)
+                .hide(true)
+                .long("now"),
+        )
         .arg(
             Arg::new("no-new")
                 .help("Don't add new kanji, just review old")
@@ -31,8 +74,60 @@ pub fn build() -> App<'static> {
         .arg(
             Arg::new("new-count")
                 .help("Specify how many new cards you want to learn")
+                .takes_value(true)
                 .long("new-count"),
         )
+        .arg(
+            Arg::new("order")
+                .help("Order in which new kanji are introduced")
+                .takes_value(true)
+                .possible_values([
+                    "added",
+                    "random",
+                    "strokes",
+                    "frequency",
+                    "grade",
+                    "jlpt",
+                    "components",
+                ])
+                .long("order"),
+        )
+        .arg(
+            Arg::new("review-order")
+                .help("Order in which due kanji are reviewed if not all fit into the session")
+                .takes_value(true)
+                .possible_values(["added", "overdue", "retrievability", "ease", "random"])
+                .long("review-order"),
+        )
+        .arg(
+            Arg::new("catch-up")
+                .help("Spread due kanji which don't fit into the session over this many days")
+                .takes_value(true)
+                .long("catch-up"),
+        )
+        .arg(
+            Arg::new("tui")
+                .help("Review in the terminal instead of opening jotoba in a browser")
+                .long("tui"),
+        )
+        .arg(
+            Arg::new("drill")
+                .help("Write the kanji in small shuffled groups and grade them by your mistakes")
+                .conflicts_with("tui")
+                .long("drill"),
+        )
+        .arg(
+            Arg::new("group-size")
+                .help("Amount of kanji written at once in a drill")
+                .takes_value(true)
+                .long("group-size"),
+        )
+        .arg(
+            Arg::new("repetitions")
+                .help("How often each kanji has to be written correctly in a drill")
+                .takes_value(true)
+                .long("repetitions"),
+        )
         .subcommand(
             App::new("add")
                 .about("Adds kanji to learn")
@@ -44,16 +139,140 @@ pub fn build() -> App<'static> {
                 .arg(Arg::new("kanji")),
         )
         .subcommand(App::new("info").about("Show info about reviews"))
+        .subcommand(
+            App::new("forecast")
+                .about("Show how many kanji are due on each of the next days")
+                .arg(
+                    Arg::new("days")
+                        .help("Amount of days to show (Default 14)")
+                        .takes_value(true)
+                        .long("days"),
+                )
+                .arg(
+                    Arg::new("weeks")
+                        .help("Show this many weeks instead of days")
+                        .takes_value(true)
+                        .conflicts_with("days")
+                        .long("weeks"),
+                )
+                .arg(
+                    Arg::new("new-count")
+                        .help("New kanji introduced per day (Default from config)")
+                        .takes_value(true)
+                        .long("new-count"),
+                )
+                .arg(
+                    Arg::new("no-new")
+                        .help("Don't project new kanji")
+                        .conflicts_with("new-count")
+                        .long("no-new"),
+                ),
+        )
         .subcommand(
             App::new("reset")
                 .about("Reset learn process of a kanji and treat it as a new item")
                 .arg(Arg::new("kanji")),
         )
+        .subcommand(
+            App::new("suspend")
+                .about("Stop showing kanji in sessions until they get unsuspended")
+                .arg(Arg::new("kanji")),
+        )
+        .subcommand(
+            App::new("unsuspend")
+                .about("Show suspended kanji in sessions again")
+                .arg(Arg::new("kanji")),
+        )
+        .subcommand(
+            App::new("bury")
+                .about("Skip kanji until tomorrow")
+                .arg(Arg::new("kanji")),
+        )
         .subcommand(
             App::new("review")
                 .about("Manually tag kanji as reviewed")
                 .arg(Arg::new("kanji")),
         )
+        .subcommand(
+            App::new("undo")
+                .about("Undo the last session or a single review")
+                .arg(
+                    Arg::new("review")
+                        .help("Number of the review to undo instead of the last session"),
+                )
+                .arg(
+                    Arg::new("list")
+                        .help("List the latest reviews which can be undone")
+                        .conflicts_with("review")
+                        .long("list"),
+                ),
+        )
+        .subcommand(
+            App::new("export")
+                .about("Export all kanji with their progress to JSON or CSV")
+                .arg(Arg::new("file").help("File to write to. Writes to stdout if missing or -"))
+                .arg(
+                    Arg::new("file-format")
+                        .help("Format of the file. Taken from the extension by default")
+                        .takes_value(true)
+                        .possible_values(["json", "csv"])
+                        .long("file-format"),
+                ),
+        )
+        .subcommand(
+            App::new("import")
+                .about("Import kanji from a file written by export")
+                .arg(
+                    Arg::new("file")
+                        .help("File to read. Reads from stdin if -")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("file-format")
+                        .help("Format of the file. Taken from the extension by default")
+                        .takes_value(true)
+                        .possible_values(["json", "csv"])
+                        .long("file-format"),
+                )
+                .arg(
+                    Arg::new("policy")
+                        .help("What happens to kanji which are in the database already")
+                        .takes_value(true)
+                        .possible_values(["skip", "overwrite", "newer"])
+                        .default_value("skip")
+                        .long("policy"),
+                )
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .subcommand(
+                    App::new("anki")
+                        .about("Import kanji and their progress from Anki")
+                        .arg(
+                            Arg::new("file")
+                                .help("Deck package (.apkg) or collection file to read")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("deck")
+                                .help("Deck to import along with its subdecks")
+                                .takes_value(true)
+                                .long("deck"),
+                        )
+                        .arg(
+                            Arg::new("field")
+                                .help("Field of the notes with the kanji")
+                                .takes_value(true)
+                                .long("field"),
+                        )
+                        .arg(
+                            Arg::new("policy")
+                                .help("What happens to kanji which are in the database already")
+                                .takes_value(true)
+                                .possible_values(["skip", "overwrite", "newer"])
+                                .default_value("skip")
+                                .long("policy"),
+                        ),
+                ),
+        )
         .subcommand(App::new("fix-db").about("Fix database"))
         .subcommand(
             App::new("all").about("Show all kanji").arg(
@@ -62,7 +281,45 @@ pub fn build() -> App<'static> {
                     .long("raw"),
             ),
         )
-        .subcommand(App::new("stats").about("Show stats"));
+        .subcommand(App::new("stats").about("Show stats"))
+        .subcommand(
+            App::new("leeches")
+                .about("List kanji which keep being forgotten")
+                .arg(
+                    Arg::new("drill")
+                        .help("Drill the kanji in the leech drill queue")
+                        .long("drill"),
+                )
+                .arg(
+                    Arg::new("group-size")
+                        .help("Amount of kanji written at once")
+                        .takes_value(true)
+                        .long("group-size"),
+                )
+                .arg(
+                    Arg::new("repetitions")
+                        .help("How often each kanji has to be written correctly")
+                        .takes_value(true)
+                        .long("repetitions"),
+                ),
+        )
+        .subcommand(
+            App::new("config")
+                .about("Show or change the configuration")
+                .arg(Arg::new("key"))
+                .arg(Arg::new("value")),
+        )
+        .subcommand(
+            App::new("scheduler")
+                .about("Show or change the scheduling algorithm (sm2, fsrs)")
+                .arg(Arg::new("name"))
+                .arg(
+                    Arg::new("retention")
+                        .help("Desired retention for FSRS (0.7 - 0.99)")
+                        .takes_value(true)
+                        .long("retention"),
+                ),
+        );
 
     app
 }
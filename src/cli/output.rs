@@ -0,0 +1,148 @@
+//! Output of the subcommands, either as text for humans or as JSON for scripts
+
+use crate::storage::{srs, Storage};
+use clap::ArgMatches;
+use serde::Serialize;
+
+/// Format of the output selected with `--format`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+impl Format {
+    /// Returns the format passed to the command
+    pub fn of(app: &ArgMatches) -> Self {
+        match app.value_of("format") {
+            Some("json") => Format::Json,
+            _ => Format::Text,
+        }
+    }
+
+    #[inline]
+    pub fn is_json(self) -> bool {
+        self == Format::Json
+    }
+}
+
+/// A kanji along with its review state
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Kanji {
+    pub literal: char,
+    pub id: u32,
+    pub in_learning: bool,
+    /// Unix timestamp at which the kanji is due. `None` for new kanji
+    pub due_on: Option<u64>,
+    /// Local time at which the kanji is due
+    pub due: Option<String>,
+    pub interval: u32,
+    pub ease: f64,
+    pub lapses: u32,
+    pub leech: bool,
+    pub suspended: bool,
+    /// `true` if the kanji waits in the leech drill queue
+    pub drill: bool,
+}
+
+impl Kanji {
+    /// Returns the kanji `item` belongs to. `None` if there is no such kanji
+    pub fn of(storage: &Storage, item: &srs::Item) -> Option<Self> {
+        let literal = storage.get_by_id(item.id)?.get_literal();
+        let due_on = (item.in_learning && item.due_on > 0).then_some(item.due_on);
+        Some(Self {
+            literal,
+            id: item.id,
+            in_learning: item.in_learning,
+            due_on,
+            due: due_on.map(|i| storage.day_boundary().format_time(i)),
+            interval: item.interval,
+            ease: item.state.ease_factor(),
+            lapses: item.lapses,
+            leech: item.leech,
+            suspended: item.suspended,
+            drill: item.drill,
+        })
+    }
+
+    /// Returns the kanji of all `items` in the same order
+    pub fn all<'a, I>(storage: &Storage, items: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = &'a srs::Item>,
+    {
+        items
+            .into_iter()
+            .filter_map(|i| Self::of(storage, i))
+            .collect()
+    }
+}
+
+/// Prints `value` as JSON
+pub fn print_json<T: Serialize + ?Sized>(value: &T) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("Failed to serialize output")
+    );
+}
+
+/// Prints why a command couldn't be run. JSON gets an object with the message as `error`
+pub fn print_error(format: Format, message: &str) {
+    if format.is_json() {
+        print_json(&serde_json::json!({ "error": message }));
+    } else {
+        println!("{message}");
+    }
+}
+
+/// Prints a progress or status message which isn't part of the result. JSON output gets it on
+/// stderr so stdout stays parseable
+pub fn print_status(format: Format, message: &str) {
+    if format.is_json() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// Prints the kanji a command changed. Text gets `done` followed by the kanji or `nothing` if no
+/// kanji has been changed. JSON gets an object with the kanji as `kanji`
+pub fn print_changed(format: Format, changed: &[char], done: &str, nothing: &str) {
+    if format.is_json() {
+        print_json(&serde_json::json!({ "kanji": changed }));
+    } else if changed.is_empty() {
+        println!("{nothing}");
+    } else {
+        let changed = changed.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        println!("{done} {}", changed.join(","));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_of_subcommands() {
+        let app = crate::cli::build().get_matches_from(["ksrs", "info", "--format", "json"]);
+        assert_eq!(Format::Json, Format::of(app.subcommand().unwrap().1));
+
+        let app = crate::cli::build().get_matches_from(["ksrs", "stats"]);
+        assert_eq!(Format::Text, Format::of(app.subcommand().unwrap().1));
+    }
+
+    #[test]
+    fn kanji() {
+        let mut storage = Storage::in_memory();
+        storage.add('日');
+        let item = *storage.get_srs_storage().find(1).unwrap();
+
+        let kanji = Kanji::of(&storage, &item).unwrap();
+        assert_eq!('日', kanji.literal);
+        assert_eq!(None, kanji.due_on);
+        assert_eq!(
+            r#"{"literal":"日","id":1,"in_learning":false,"due_on":null,"due":null,"interval":0,"ease":2.5,"lapses":0,"leech":false,"suspended":false,"drill":false}"#,
+            serde_json::to_string(&kanji).unwrap()
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use super::add::parse_kanji_arg;
+use super::{
+    add::parse_kanji_arg,
+    output::{self, Format},
+};
 use crate::{japanese::JapaneseExt, storage::Storage};
 use clap::ArgMatches;
 
@@ -13,13 +16,9 @@ pub fn run(mut storage: Storage, app: &ArgMatches) {
 
     for kanji in inp.chars().filter(|i| i.is_kanji()) {
         if storage.remove(kanji) {
-            removed.push(kanji.to_string());
+            removed.push(kanji);
         }
     }
 
-    if !removed.is_empty() {
-        println!("Removed {}", removed.join(","));
-    } else {
-        println!("Nothing to remove");
-    }
+    output::print_changed(Format::of(app), &removed, "Removed", "Nothing to remove");
 }
@@ -1,4 +1,7 @@
-use super::add::parse_kanji_arg;
+use super::{
+    add::parse_kanji_arg,
+    output::{self, Format},
+};
 use crate::{japanese::JapaneseExt, storage::Storage};
 use clap::ArgMatches;
 
@@ -13,13 +16,9 @@ pub fn run(mut storage: Storage, app: &ArgMatches) {
 
     for kanji in inp.chars().filter(|i| i.is_kanji()) {
         if storage.reset(kanji) {
-            reset.push(kanji.to_string());
+            reset.push(kanji);
         }
     }
 
-    if !reset.is_empty() {
-        println!("Resetted {}", reset.join(","));
-    } else {
-        println!("Nothing to reset");
-    }
+    output::print_changed(Format::of(app), &reset, "Resetted", "Nothing to reset");
 }
@@ -1,7 +1,11 @@
-use crate::{japanese::JapaneseExt, sm2::RepQuality, storage::Storage};
+use crate::{japanese::JapaneseExt, storage::Storage};
 use clap::ArgMatches;
 
-use super::{add::parse_kanji_arg, run::read_failed_input};
+use super::{
+    add::parse_kanji_arg,
+    output::{self, Format},
+    run::{read_grades, DEFAULT_GRADE},
+};
 
 /// Manually mark kanji as reviewed
 pub fn run(mut storage: Storage, app: &ArgMatches) {
@@ -10,12 +14,13 @@ pub fn run(mut storage: Storage, app: &ArgMatches) {
         None => return,
     };
 
+    let now = storage.now();
     let inp_kanji = inp
         .chars()
         .filter(|i| i.is_kanji())
         .filter_map(|i| {
             let item = storage.get_by_lit(i)?;
-            if !item.can_be_reviewed() {
+            if !item.can_be_reviewed(now) {
                 return None;
             }
 
@@ -23,36 +28,26 @@ pub fn run(mut storage: Storage, app: &ArgMatches) {
         })
         .collect::<Vec<_>>();
 
+    let format = Format::of(app);
     if inp_kanji.is_empty() {
-        println!("Nothing to do");
+        output::print_changed(format, &[], "", "Nothing to do");
         return;
     }
 
-    let to_relearn = read_failed_input()
-        .into_iter()
-        .filter(|i| inp_kanji.iter().any(|j| j.get_literal() == *i))
-        .collect::<Vec<_>>();
+    let grades = read_grades(!format.is_json());
 
     let mut reviewd = vec![];
+    let session = storage.start_session();
 
     for (id, lit) in inp_kanji
         .into_iter()
         .map(|i| (i.get_id(), i.get_literal()))
         .collect::<Vec<_>>()
     {
-        let item_mut = storage.get_srs_mut(id).unwrap();
-
-        if to_relearn.contains(&lit) {
-            item_mut.review(RepQuality::Grade2)
-        } else {
-            item_mut.review(RepQuality::Grade4)
-        }
-        reviewd.push(lit.to_string());
+        let grade = grades.get(&lit).copied().unwrap_or(DEFAULT_GRADE);
+        storage.review(id, grade, session);
+        reviewd.push(lit);
     }
 
-    if !reviewd.is_empty() {
-        println!("Reviewed {}", reviewd.join(","));
-    } else {
-        println!("Nothing to reset");
-    }
+    output::print_changed(format, &reviewd, "Reviewed", "Nothing to review");
 }
@@ -1,22 +1,42 @@
+use super::{
+    drill,
+    output::{self, Format},
+    tui,
+};
 use crate::{
     browser,
+    config::{self, NewOrder, ReviewOrder},
     japanese::JapaneseExt,
+    jotoba::{self, KanjiInfo},
     sm2::RepQuality,
     storage::{Item, Storage},
     utils,
 };
 use clap::ArgMatches;
+use std::collections::HashMap;
+
+/// Grade of kanji which haven't been entered
+pub(crate) const DEFAULT_GRADE: RepQuality = RepQuality::Grade4;
+
+/// Grade of kanji which have been entered without a grade
+const AGAIN_GRADE: RepQuality = RepQuality::Grade2;
 
-pub(crate) const DEFAULT_NEW: usize = 8;
-pub(crate) const MAX_REVIEWS: usize = 20;
+/// Amount of kanji looked up on jotoba with a single request
+const INFO_CHUNK_SIZE: usize = 20;
 
 pub struct RunConfigs {
     new_count: usize,
     max_reviews: usize,
+    new_order: NewOrder,
+    review_order: ReviewOrder,
+    catch_up_days: u32,
 }
 
 pub fn run(mut storage: Storage, app: ArgMatches) {
     let run_config = parse_runconfig(&app);
+    if run_config.new_count > 0 {
+        load_kanji_info(&mut storage, run_config.new_order, Format::of(&app));
+    }
 
     let to_learn = pick_to_learn(&storage, &run_config);
     if to_learn.is_empty() {
@@ -33,48 +53,101 @@ pub fn run(mut storage: Storage, app: ArgMatches) {
         return;
     }
 
-    let learn_string = to_learn.iter().map(|i| i.get_literal()).collect::<String>();
-    browser::open_kanji(&learn_string);
-
-    let to_relearn = determine_relearn(&storage, &to_learn);
-
-    for i in to_learn.into_iter().map(|i| i.get_id()).collect::<Vec<_>>() {
-        let item_mut = storage.get_srs_mut(i).unwrap();
+    // Started before writing so the session's duration can be told from its reviews
+    let session = storage.start_session();
+    let grades = if app.is_present("tui") {
+        let kanji = to_learn.iter().map(|i| i.get_literal()).collect::<Vec<_>>();
+        match tui::run(&kanji) {
+            Ok(Some(grades)) => grades,
+            Ok(None) => {
+                println!("Session aborted");
+                return;
+            }
+            Err(err) => {
+                println!("Failed to run terminal session: {err}");
+                return;
+            }
+        }
+    } else {
+        let learn_string = to_learn.iter().map(|i| i.get_literal()).collect::<String>();
+        browser::open_kanji(&learn_string);
 
-        if to_relearn.contains(&i) {
-            item_mut.review(RepQuality::Grade2)
+        if app.is_present("drill") {
+            let kanji = to_learn.iter().map(|i| i.get_literal()).collect::<Vec<_>>();
+            drill::run(&kanji, &drill::parse_config(&app))
         } else {
-            item_mut.review(RepQuality::Grade4)
+            read_grades(true)
         }
+    };
+
+    for (id, lit) in to_learn
+        .into_iter()
+        .map(|i| (i.get_id(), i.get_literal()))
+        .collect::<Vec<_>>()
+    {
+        let grade = grades.get(&lit).copied().unwrap_or(DEFAULT_GRADE);
+        storage.review(id, grade, session);
     }
 
-    println!("Learning done");
+    let days = run_config.catch_up_days;
+    let spread = storage.spread_due(days, run_config.review_order);
+    if spread > 0 {
+        println!(
+            "Spread {spread} kanji which didn't fit into the session over the next {days} days"
+        );
+    }
+
+    println!("Learning done. Run `kanji_srs undo` if you graded something wrong");
 }
 
-pub fn read_failed_input() -> Vec<char> {
-    utils::print_stdout("Enter kanji you want to learn again > ");
-    utils::read_std_line()
-        .chars()
-        .filter(|i| i.is_kanji())
-        .collect::<Vec<char>>()
+/// Asks the user to grade the kanji of a session. Kanji which haven't been entered are graded
+/// with `DEFAULT_GRADE`. Without `prompt` the grades are read silently, eg. from a script
+pub fn read_grades(prompt: bool) -> HashMap<char, RepQuality> {
+    if prompt {
+        println!("Grades: 0-2 forgotten, 3 hard, 4 good (default), 5 easy");
+        utils::print_stdout("Enter kanji to grade, eg. 日0 月3 火5 (no grade = learn again) > ");
+    }
+    parse_grades(&utils::read_std_line())
 }
 
-fn determine_relearn(storage: &Storage, learned: &[Item<'_>]) -> Vec<u32> {
-    read_failed_input()
-        .into_iter()
-        .filter_map(|r| {
-            learned
-                .iter()
-                .any(|i| i.get_literal() == r)
-                .then(|| storage.get_by_lit(r))
-                .flatten()
-        })
-        .map(|i| i.get_id())
-        .collect::<Vec<_>>()
+/// Parses kanji, each optionally followed by a grade from 0 to 5. Kanji without a grade get
+/// `AGAIN_GRADE`
+fn parse_grades(inp: &str) -> HashMap<char, RepQuality> {
+    let mut grades = HashMap::new();
+    let mut chars = inp.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !c.is_kanji() {
+            continue;
+        }
+
+        let grade = match chars.peek().and_then(|i| parse_grade(*i)) {
+            Some(grade) => {
+                chars.next();
+                grade
+            }
+            None => AGAIN_GRADE,
+        };
+        grades.insert(c, grade);
+    }
+
+    grades
+}
+
+/// Parses a single digit from 0 to 5. Full-width digits are accepted as well
+fn parse_grade(c: char) -> Option<RepQuality> {
+    let digit = match c {
+        '０'..='９' => c as u32 - '０' as u32,
+        _ => c.to_digit(10)?,
+    };
+    RepQuality::from_grade(digit as u8)
 }
 
 fn pick_to_learn<'a>(storage: &'a Storage, run_config: &RunConfigs) -> Vec<Item<'a>> {
-    let due = storage.get_srs_storage().get_due().map(|i| i.id);
+    let due = storage
+        .get_due_ordered(run_config.review_order)
+        .into_iter()
+        .map(|i| i.id);
     let reviews = if run_config.max_reviews == 0 {
         // take all
         due.collect::<Vec<_>>()
@@ -88,8 +161,8 @@ fn pick_to_learn<'a>(storage: &'a Storage, run_config: &RunConfigs) -> Vec<Item<
         .chain(
             // add new reviews
             storage
-                .get_srs_storage()
-                .get_new()
+                .get_new_ordered(run_config.new_order)
+                .into_iter()
                 .map(|i| i.id)
                 .take(run_config.new_count),
         )
@@ -97,17 +170,96 @@ fn pick_to_learn<'a>(storage: &'a Storage, run_config: &RunConfigs) -> Vec<Item<
         .collect::<Vec<_>>()
 }
 
+/// Reads the session options from the config, overridden by the ones passed in `app`
 fn parse_runconfig(app: &ArgMatches) -> RunConfigs {
+    let config = config::get();
     let new_count = if !app.is_present("no-new") {
-        utils::parse_nr(app.value_of("new-count"), DEFAULT_NEW)
+        utils::parse_nr(app.value_of("new-count"), config.new_per_day)
     } else {
         0
     };
 
-    let max_reviews = utils::parse_nr(app.value_of("max-reviews"), MAX_REVIEWS);
+    let max_reviews = utils::parse_nr(app.value_of("max-reviews"), config.max_reviews);
+
+    let new_order = app
+        .value_of("order")
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(config.new_order);
+
+    let review_order = app
+        .value_of("review-order")
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(config.review_order);
+
+    let catch_up_days =
+        utils::parse_nr(app.value_of("catch-up"), config.catch_up_days as usize) as u32;
 
     RunConfigs {
         new_count,
         max_reviews,
+        new_order,
+        review_order,
+        catch_up_days,
+    }
+}
+
+/// Looks up the new kanji `order` needs information about on jotoba. Kanji jotoba doesn't know
+/// get cached without information and are introduced last
+pub fn load_kanji_info(storage: &mut Storage, order: NewOrder, format: Format) {
+    if !order.needs_info() {
+        return;
+    }
+
+    let missing = storage.missing_info();
+    if missing.is_empty() {
+        return;
+    }
+
+    let message = format!("Looking up {} kanji on jotoba", missing.len());
+    output::print_status(format, &message);
+    for chunk in missing.chunks(INFO_CHUNK_SIZE) {
+        let mut infos = match jotoba::kanji_infos(chunk) {
+            Some(infos) => infos,
+            None => {
+                let message =
+                    "Couldn't reach jotoba. Kanji without information are introduced last";
+                output::print_status(format, message);
+                return;
+            }
+        };
+
+        for literal in chunk {
+            let info = match infos.iter().position(|i| i.literal == *literal) {
+                Some(pos) => infos.swap_remove(pos),
+                None => KanjiInfo {
+                    literal: *literal,
+                    ..KanjiInfo::default()
+                },
+            };
+            storage.add_info(info);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grades() {
+        let grades = parse_grades("日0 月3火５ 水\n");
+        assert_eq!(4, grades.len());
+        assert_eq!(Some(&RepQuality::Grade0), grades.get(&'日'));
+        assert_eq!(Some(&RepQuality::Grade3), grades.get(&'月'));
+        assert_eq!(Some(&RepQuality::Grade5), grades.get(&'火'));
+        assert_eq!(Some(&AGAIN_GRADE), grades.get(&'水'));
+    }
+
+    #[test]
+    fn invalid_grades() {
+        let grades = parse_grades("日9 月a 4");
+        assert_eq!(2, grades.len());
+        assert_eq!(Some(&AGAIN_GRADE), grades.get(&'日'));
+        assert_eq!(Some(&AGAIN_GRADE), grades.get(&'月'));
     }
 }
@@ -0,0 +1,59 @@
+use super::output::{self, Format};
+use crate::storage::{
+    settings::{SchedulerKind, Settings},
+    Storage,
+};
+use clap::ArgMatches;
+
+/// Show or change the scheduling algorithm of the database
+pub fn run(mut storage: Storage, app: &ArgMatches) {
+    let mut settings = storage.get_settings();
+    let format = Format::of(app);
+
+    if !app.is_present("name") && !app.is_present("retention") {
+        print_settings(&settings, format);
+        return;
+    }
+
+    if let Some(name) = app.value_of("name") {
+        settings.scheduler = match name.parse::<SchedulerKind>() {
+            Ok(s) => s,
+            Err(_) => {
+                let message = format!("Unknown scheduler '{name}'. Available: sm2, fsrs");
+                output::print_error(format, &message);
+                return;
+            }
+        };
+    }
+
+    if let Some(retention) = app.value_of("retention") {
+        settings.desired_retention = match retention.parse::<f64>() {
+            Ok(r) if (0.7..=0.99).contains(&r) => r,
+            _ => {
+                output::print_error(format, "Desired retention has to be between 0.7 and 0.99");
+                return;
+            }
+        };
+    }
+
+    storage.set_settings(settings);
+    print_settings(&settings, format);
+}
+
+fn print_settings(settings: &Settings, format: Format) {
+    if format.is_json() {
+        output::print_json(&serde_json::json!({
+            "scheduler": settings.scheduler.to_string(),
+            "desired_retention": settings.desired_retention,
+        }));
+        return;
+    }
+
+    match settings.scheduler {
+        SchedulerKind::SM2 => println!("Scheduler: SM-2"),
+        SchedulerKind::FSRS => println!(
+            "Scheduler: FSRS (desired retention {:.0}%)",
+            settings.desired_retention * 100.0
+        ),
+    }
+}
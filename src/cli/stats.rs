@@ -1,50 +1,234 @@
-use crate::{cli::run::DEFAULT_NEW, storage::Storage};
+use super::output::{self, Format};
+use crate::{
+    config,
+    storage::{
+        history::{History, Retention, MATURE_INTERVAL},
+        Storage,
+    },
+};
+use chrono::{Datelike, Duration};
 use clap::ArgMatches;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use term_table::{
     row::Row,
     table_cell::{Alignment, TableCell},
     Table, TableStyle,
 };
 
+/// Amount of weeks shown in the heatmap
+const HEATMAP_WEEKS: i64 = 16;
+
+/// Characters of the heatmap for days with more and more reviews
+const HEATMAP_LEVELS: [char; 4] = ['░', '▒', '▓', '█'];
+
+/// Numbers shown by `stats`
+#[derive(Serialize)]
+struct Stats {
+    total: usize,
+    in_learning: usize,
+    young: usize,
+    mature: usize,
+    suspended: usize,
+    /// Days until all kanji have been introduced with the configured new kanji per day
+    days_left: usize,
+    /// Average ease of the kanji in learning. `None` if there are none
+    average_ease: Option<f64>,
+    lapses: u32,
+    reviews: usize,
+    streak: u32,
+    /// Average duration of the sessions in seconds. `None` if no session has been timed
+    average_session_secs: Option<u64>,
+    retention: Vec<Retention>,
+    /// Reviews on each day which has any, by date (`YYYY-MM-DD`)
+    reviews_per_day: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    fn of(storage: &Storage, history: &History) -> Self {
+        let learning = storage
+            .get_srs_storage()
+            .iter()
+            .filter(|i| i.in_learning)
+            .collect::<Vec<_>>();
+        let mature = learning
+            .iter()
+            .filter(|i| i.interval >= MATURE_INTERVAL)
+            .count();
+
+        let left = storage
+            .get_srs_storage()
+            .iter()
+            .filter(|i| !i.in_learning && !i.suspended)
+            .count();
+        let new_per_day = config::get().new_per_day.max(1);
+
+        let average_ease = (!learning.is_empty()).then(|| {
+            learning.iter().map(|i| i.state.ease_factor()).sum::<f64>() / learning.len() as f64
+        });
+
+        // Sessions graded right away (eg. by older versions) don't tell how long they took
+        let durations = history
+            .session_durations()
+            .into_iter()
+            .filter(|i| *i > 0)
+            .collect::<Vec<_>>();
+        let average_session_secs =
+            (!durations.is_empty()).then(|| durations.iter().sum::<u64>() / durations.len() as u64);
+
+        Self {
+            total: storage.len(),
+            in_learning: learning.len(),
+            young: learning.len() - mature,
+            mature,
+            suspended: storage.suspended_kanji(),
+            days_left: left.div_ceil(new_per_day),
+            average_ease,
+            lapses: learning.iter().map(|i| i.lapses).sum(),
+            reviews: history.len(),
+            streak: history.streak(storage.now()),
+            average_session_secs,
+            retention: history.retention(),
+            reviews_per_day: history
+                .reviews_per_day()
+                .into_iter()
+                .map(|(day, count)| (day.format("%Y-%m-%d").to_string(), count))
+                .collect(),
+        }
+    }
+}
+
 /// Show ovarall stats
-pub fn run(storage: Storage, _app: &ArgMatches) {
+pub fn run(storage: Storage, app: &ArgMatches) {
+    let history = storage.history();
+    let stats = Stats::of(&storage, &history);
+    if Format::of(app).is_json() {
+        output::print_json(&stats);
+        return;
+    }
+
     let mut table = Table::new();
 
     table.max_column_width = 30;
     table.style = TableStyle::rounded();
 
+    add_title(&mut table, "Kanji stats");
+    add_row(&mut table, "Total Kanji", format!("{}字", stats.total));
+    add_row(
+        &mut table,
+        "In learning",
+        format!("{}字", stats.in_learning),
+    );
+
+    let percent = (stats.in_learning as f32) * 100.0 / stats.total as f32;
+    add_row(&mut table, "Percentage", format!("{percent:.1}%"));
+    add_row(&mut table, "Young", format!("{}字", stats.young));
+    add_row(&mut table, "Mature", format!("{}字", stats.mature));
+    add_row(&mut table, "Suspended", format!("{}字", stats.suspended));
+    add_row(&mut table, "Days left", format!("{}日", stats.days_left));
+
+    if let Some(ease) = stats.average_ease {
+        add_row(&mut table, "Average ease", format!("{:.0}%", ease * 100.0));
+
+        let per_kanji = stats.lapses as f64 / stats.in_learning as f64;
+        add_row(
+            &mut table,
+            "Lapses",
+            format!("{} ({per_kanji:.2} per kanji)", stats.lapses),
+        );
+    }
+
+    if !history.is_empty() {
+        add_history(&mut table, &stats, &history, storage.now());
+    }
+
+    println!("{}", table.render());
+}
+
+/// Adds the review counts, retention and heatmap to `table`
+fn add_history(table: &mut Table, stats: &Stats, history: &History, now: u64) {
+    add_title(table, "Reviews");
+    add_row(table, "Total reviews", stats.reviews.to_string());
+    add_row(table, "Streak", format!("{}日", stats.streak));
+
+    if let Some(average) = stats.average_session_secs {
+        add_row(
+            table,
+            "Session time",
+            format!("{}m {}s on average", average / 60, average % 60),
+        );
+    }
+
+    add_title(table, "Retention");
+    for bucket in stats.retention.iter() {
+        let interval = if bucket.max_interval == u32::MAX {
+            format!("{}日+", bucket.min_interval)
+        } else if bucket.min_interval == bucket.max_interval {
+            format!("{}日", bucket.min_interval)
+        } else {
+            format!("{}-{}日", bucket.min_interval, bucket.max_interval)
+        };
+        let rate = match bucket.rate() {
+            Some(rate) => format!("{:.1}% of {}", rate * 100.0, bucket.reviews),
+            None => "-".to_string(),
+        };
+        add_row(table, &interval, rate);
+    }
+
+    add_title(table, &format!("Last {HEATMAP_WEEKS} weeks"));
     table.add_row(Row::new(vec![TableCell::new_with_alignment(
-        "Kanji stats",
+        heatmap(history, now),
         2,
-        Alignment::Center,
+        Alignment::Left,
     )]));
+}
 
-    table.add_row(Row::new(vec![
-        TableCell::new("Total Kanji"),
-        TableCell::new_with_alignment(format!("{}字", storage.len()), 1, Alignment::Left),
-    ]));
+/// Renders the reviews per day of the last weeks as calendar with a row for each weekday
+fn heatmap(history: &History, now: u64) -> String {
+    let per_day = history.reviews_per_day();
+    let today = history.boundary().date_of(now);
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let start = monday - Duration::weeks(HEATMAP_WEEKS - 1);
+    let max = per_day
+        .range(start..=today)
+        .map(|(_, i)| *i)
+        .max()
+        .unwrap_or(0);
 
-    table.add_row(Row::new(vec![
-        TableCell::new("In learning"),
-        TableCell::new_with_alignment(
-            format!("{}字", storage.learning_kanji()),
-            1,
-            Alignment::Left,
-        ),
-    ]));
+    let mut rows = vec![];
+    for weekday in 0..7 {
+        let mut row = (start + Duration::days(weekday)).format("%a ").to_string();
+        for week in 0..HEATMAP_WEEKS {
+            let day = start + Duration::weeks(week) + Duration::days(weekday);
+            let c = match per_day.get(&day) {
+                _ if day > today => ' ',
+                Some(n) if *n > 0 => {
+                    let level = (n * HEATMAP_LEVELS.len()).div_ceil(max);
+                    HEATMAP_LEVELS[level.clamp(1, HEATMAP_LEVELS.len()) - 1]
+                }
+                _ => '·',
+            };
+            row.push(c);
+        }
+        rows.push(row);
+    }
 
-    let percent = (storage.learning_kanji() as f32) * 100.0 / storage.len() as f32;
-    table.add_row(Row::new(vec![
-        TableCell::new("Percentage"),
-        TableCell::new_with_alignment(format!("{percent:.1}%"), 1, Alignment::Left),
-    ]));
+    let legend = HEATMAP_LEVELS.iter().collect::<String>();
+    rows.push(format!("\nLess ·{legend} More (max {max} a day)"));
+    rows.join("\n")
+}
+
+fn add_title(table: &mut Table, title: &str) {
+    table.add_row(Row::new(vec![TableCell::new_with_alignment(
+        title,
+        2,
+        Alignment::Center,
+    )]));
+}
 
-    let left = storage.len() - storage.learning_kanji();
-    let days_left = (left as f32 / DEFAULT_NEW as f32).ceil() as usize;
+fn add_row(table: &mut Table, name: &str, value: String) {
     table.add_row(Row::new(vec![
-        TableCell::new("Days left"),
-        TableCell::new_with_alignment(format!("{}日", days_left), 1, Alignment::Left),
+        TableCell::new(name),
+        TableCell::new_with_alignment(value, 1, Alignment::Left),
     ]));
-
-    println!("{}", table.render());
 }
@@ -0,0 +1,58 @@
+use super::{
+    add::parse_kanji_arg,
+    output::{self, Format},
+};
+use crate::{japanese::JapaneseExt, storage::Storage};
+use clap::ArgMatches;
+
+/// Stop showing kanji in sessions
+pub fn suspend(mut storage: Storage, app: &ArgMatches) {
+    apply(
+        &mut storage,
+        app,
+        Storage::suspend,
+        "Suspended",
+        "Nothing to suspend",
+    );
+}
+
+/// Show suspended kanji in sessions again
+pub fn unsuspend(mut storage: Storage, app: &ArgMatches) {
+    apply(
+        &mut storage,
+        app,
+        Storage::unsuspend,
+        "Unsuspended",
+        "Nothing to unsuspend",
+    );
+}
+
+/// Skip kanji until tomorrow
+pub fn bury(mut storage: Storage, app: &ArgMatches) {
+    apply(
+        &mut storage,
+        app,
+        Storage::bury,
+        "Buried until tomorrow",
+        "Nothing to bury",
+    );
+}
+
+/// Runs `action` for every kanji passed to the subcommand and prints the ones it succeeded for
+fn apply<F>(storage: &mut Storage, app: &ArgMatches, action: F, done: &str, nothing: &str)
+where
+    F: Fn(&mut Storage, char) -> bool,
+{
+    let inp = match parse_kanji_arg(app) {
+        Some(inp) => inp,
+        None => return,
+    };
+
+    let changed = inp
+        .chars()
+        .filter(|i| i.is_kanji())
+        .filter(|i| action(storage, *i))
+        .collect::<Vec<_>>();
+
+    output::print_changed(Format::of(app), &changed, done, nothing);
+}
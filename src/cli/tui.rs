@@ -0,0 +1,341 @@
+//! Full-screen terminal session showing all kanji to write as a grid. Kanji can be graded with
+//! the keyboard and a few of them can be picked at random to be written next
+
+use super::run::DEFAULT_GRADE;
+use crate::{
+    jotoba::{self, KanjiInfo},
+    sm2::RepQuality,
+};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+/// Width of a single kanji in the grid in terminal columns
+const CELL_WIDTH: u16 = 6;
+
+/// Amount of kanji picked by default when shuffling
+const DEFAULT_PICK: usize = 4;
+
+const HELP: &str = "←↑↓→/hjkl move  0-5 grade  ⌫ clear  i info  s shuffle  +/- pick size  \
+                    c commit  q quit";
+
+/// Runs a session for `kanji` and returns the grades given. Kanji which haven't been graded get
+/// the default grade. `None` if the session has been aborted
+pub fn run(kanji: &[char]) -> io::Result<Option<HashMap<char, RepQuality>>> {
+    let mut session = Session::new(kanji, StdRng::from_entropy());
+    let mut out = io::stdout();
+
+    let _terminal = RawTerminal::enter(&mut out)?;
+    loop {
+        session.columns = (terminal::size()?.0 / CELL_WIDTH).max(1) as usize;
+        session.draw(&mut out)?;
+
+        if session.reveal && !session.info.contains_key(&session.current()) {
+            let literal = session.current();
+            session.info.insert(literal, jotoba::kanji_info(literal));
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+
+        match session.handle_key(key) {
+            Action::Continue => (),
+            Action::Commit => return Ok(Some(session.grades())),
+            Action::Abort => return Ok(None),
+        }
+    }
+}
+
+/// Puts the terminal into raw mode on an alternate screen and restores it when dropped
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter(out: &mut impl Write) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Commit,
+    Abort,
+}
+
+struct Session {
+    kanji: Vec<char>,
+    grades: Vec<Option<RepQuality>>,
+    cursor: usize,
+    /// Indices of the kanji picked to be written next
+    picked: Vec<usize>,
+    pick_count: usize,
+    /// Show information about the kanji under the cursor
+    reveal: bool,
+    /// Information loaded from jotoba. `None` if loading failed
+    info: HashMap<char, Option<KanjiInfo>>,
+    columns: usize,
+    rng: StdRng,
+}
+
+impl Session {
+    fn new(kanji: &[char], rng: StdRng) -> Self {
+        Self {
+            kanji: kanji.to_vec(),
+            grades: vec![None; kanji.len()],
+            cursor: 0,
+            picked: vec![],
+            pick_count: DEFAULT_PICK,
+            reveal: false,
+            info: HashMap::new(),
+            columns: 1,
+            rng,
+        }
+    }
+
+    #[inline]
+    fn current(&self) -> char {
+        self.kanji[self.cursor]
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        // Raw mode doesn't turn Ctrl+C into a signal, so it arrives here as a key
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('c') | KeyCode::Char('d') => Action::Abort,
+                _ => Action::Continue,
+            };
+        }
+
+        let last = self.kanji.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Left | KeyCode::Char('h') => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.cursor = self.cursor.saturating_sub(self.columns)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.cursor = (self.cursor + self.columns).min(last)
+            }
+            KeyCode::Char(c @ '0'..='5') => {
+                self.grades[self.cursor] = RepQuality::from_grade(c as u8 - b'0');
+                self.cursor = (self.cursor + 1).min(last);
+            }
+            KeyCode::Backspace | KeyCode::Delete => self.grades[self.cursor] = None,
+            KeyCode::Char('i') | KeyCode::Enter => self.reveal = !self.reveal,
+            KeyCode::Char('s') => self.shuffle(),
+            KeyCode::Char('+') => self.pick_count = (self.pick_count + 1).min(self.kanji.len()),
+            KeyCode::Char('-') => self.pick_count = self.pick_count.saturating_sub(1).max(1),
+            KeyCode::Char('c') => return Action::Commit,
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Abort,
+            _ => (),
+        }
+        Action::Continue
+    }
+
+    /// Picks `pick_count` random kanji to be written next
+    fn shuffle(&mut self) {
+        let mut indices = (0..self.kanji.len()).collect::<Vec<_>>();
+        indices.shuffle(&mut self.rng);
+        indices.truncate(self.pick_count);
+        self.picked = indices;
+    }
+
+    /// Returns the grades of all kanji
+    fn grades(&self) -> HashMap<char, RepQuality> {
+        self.kanji
+            .iter()
+            .zip(self.grades.iter())
+            .map(|(k, g)| (*k, g.unwrap_or(DEFAULT_GRADE)))
+            .collect()
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        let graded = self.grades.iter().filter(|i| i.is_some()).count();
+        queue!(out, Print(format!("Graded {graded}/{}", self.kanji.len())))?;
+
+        let mut row = 2;
+        for (line, chunk) in self.kanji.chunks(self.columns).enumerate() {
+            row = 2 + line as u16;
+            for (col, literal) in chunk.iter().enumerate() {
+                let index = line * self.columns + col;
+                queue!(out, cursor::MoveTo(col as u16 * CELL_WIDTH, row))?;
+                self.draw_cell(out, index, *literal)?;
+            }
+        }
+        row += 2;
+
+        if !self.picked.is_empty() {
+            let picked = self
+                .picked
+                .iter()
+                .map(|i| self.kanji[*i].to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                Print(format!("Write next: {picked}"))
+            )?;
+            row += 2;
+        }
+
+        if self.reveal {
+            for line in self.info_lines() {
+                queue!(out, cursor::MoveTo(0, row), Print(line))?;
+                row += 1;
+            }
+            row += 1;
+        }
+
+        queue!(
+            out,
+            cursor::MoveTo(0, row),
+            SetForegroundColor(Color::DarkGrey),
+            Print(HELP),
+            ResetColor
+        )?;
+        out.flush()
+    }
+
+    fn draw_cell(&self, out: &mut impl Write, index: usize, literal: char) -> io::Result<()> {
+        let grade = self.grades[index];
+        let color = match grade.map(|i| i.grade()) {
+            Some(0..=2) => Color::Red,
+            Some(3) => Color::Yellow,
+            Some(4) => Color::Green,
+            Some(_) => Color::Blue,
+            None => Color::Reset,
+        };
+
+        if index == self.cursor {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        if self.picked.contains(&index) {
+            queue!(out, SetAttribute(Attribute::Underlined))?;
+        }
+
+        let grade = grade.map(|i| i.grade().to_string()).unwrap_or_default();
+        queue!(
+            out,
+            SetForegroundColor(color),
+            Print(format!(" {literal}{grade:<2}")),
+            SetAttribute(Attribute::Reset),
+            ResetColor
+        )
+    }
+
+    fn info_lines(&self) -> Vec<String> {
+        let literal = self.current();
+        let info = match self.info.get(&literal) {
+            Some(Some(info)) => info,
+            Some(None) => return vec![format!("{literal}: Couldn't load info from jotoba")],
+            None => return vec![format!("{literal}: Loading...")],
+        };
+
+        vec![
+            format!("{literal}: {}", info.meanings.join(", ")),
+            format!("On:  {}", info.onyomi.join("、")),
+            format!("Kun: {}", info.kunyomi.join("、")),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let mut session = Session::new(&['日', '月', '火', '水', '木'], StdRng::seed_from_u64(0));
+        session.columns = 3;
+        session
+    }
+
+    fn press(session: &mut Session, code: KeyCode) -> Action {
+        session.handle_key(KeyEvent::from(code))
+    }
+
+    #[test]
+    fn grade_and_commit() {
+        let mut session = session();
+        press(&mut session, KeyCode::Char('1'));
+        press(&mut session, KeyCode::Char('5'));
+        assert_eq!(2, session.cursor);
+
+        press(&mut session, KeyCode::Down);
+        press(&mut session, KeyCode::Char('3'));
+        assert_eq!(4, session.cursor);
+        press(&mut session, KeyCode::Up);
+        press(&mut session, KeyCode::Backspace);
+
+        assert_eq!(Action::Commit, press(&mut session, KeyCode::Char('c')));
+        let grades = session.grades();
+        assert_eq!(RepQuality::Grade1, grades[&'日']);
+        assert_eq!(DEFAULT_GRADE, grades[&'月']);
+        assert_eq!(RepQuality::Grade3, grades[&'木']);
+        assert_eq!(DEFAULT_GRADE, grades[&'火']);
+    }
+
+    #[test]
+    fn ctrl_c_aborts() {
+        let mut session = session();
+        press(&mut session, KeyCode::Char('1'));
+        for c in ['c', 'd'] {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+            assert_eq!(Action::Abort, session.handle_key(key));
+        }
+        assert_eq!(1, session.cursor);
+    }
+
+    #[test]
+    fn movement_stays_in_grid() {
+        let mut session = session();
+        press(&mut session, KeyCode::Up);
+        assert_eq!(0, session.cursor);
+        press(&mut session, KeyCode::Down);
+        press(&mut session, KeyCode::Down);
+        assert_eq!(4, session.cursor);
+        press(&mut session, KeyCode::Right);
+        assert_eq!(4, session.cursor);
+    }
+
+    #[test]
+    fn shuffle() {
+        let mut session = session();
+        press(&mut session, KeyCode::Char('-'));
+        press(&mut session, KeyCode::Char('s'));
+        assert_eq!(3, session.picked.len());
+
+        let mut picked = session.picked.clone();
+        picked.sort_unstable();
+        picked.dedup();
+        assert_eq!(3, picked.len());
+
+        for _ in 0..10 {
+            press(&mut session, KeyCode::Char('+'));
+        }
+        press(&mut session, KeyCode::Char('s'));
+        assert_eq!(5, session.picked.len());
+    }
+}
@@ -0,0 +1,118 @@
+use super::output::{self, Format};
+use crate::storage::Storage;
+use clap::ArgMatches;
+use serde::Serialize;
+use term_table::{
+    row::Row,
+    table_cell::{Alignment, TableCell},
+    Table, TableStyle,
+};
+
+/// Amount of reviews shown by `--list`
+const LIST_LEN: usize = 20;
+
+/// A review which can be undone
+#[derive(Serialize)]
+struct Review {
+    /// Number to undo the review by
+    nr: usize,
+    literal: char,
+    grade: u8,
+    /// Unix timestamp of the review
+    timestamp: u64,
+    /// Local time of the review
+    time: String,
+}
+
+/// Undoes the last session, a single review or lists the reviews which can be undone
+pub fn run(mut storage: Storage, app: &ArgMatches) {
+    let format = Format::of(app);
+    if app.is_present("list") {
+        list(&storage, format);
+        return;
+    }
+
+    let restored = match app.value_of("review") {
+        Some(nr) => match nr.parse::<usize>() {
+            Ok(nr) if nr > 0 => storage.undo_review(nr - 1),
+            _ => {
+                let message = format!("Invalid review '{nr}'. Run with --list to see the reviews");
+                output::print_error(format, &message);
+                return;
+            }
+        },
+        None => match storage.undo_last_session() {
+            Some((session, restored)) => {
+                if !format.is_json() {
+                    let time = storage.day_boundary().format_time(session);
+                    println!("Undoing session from {time}");
+                }
+                restored
+            }
+            None => vec![],
+        },
+    };
+
+    let restored = restored
+        .into_iter()
+        .filter_map(|i| storage.get_by_id(i))
+        .map(|i| i.get_literal())
+        .collect::<Vec<_>>();
+
+    output::print_changed(format, &restored, "Restored", "Nothing to undo");
+}
+
+/// Prints the latest reviews which can be undone along with the numbers to undo them by
+fn list(storage: &Storage, format: Format) {
+    let log = storage.get_review_log();
+    let mut reviews = log
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| !i.undone && i.before.is_some())
+        .collect::<Vec<_>>();
+    reviews.drain(..reviews.len().saturating_sub(LIST_LEN));
+
+    let boundary = storage.day_boundary();
+    let reviews = reviews
+        .into_iter()
+        .filter_map(|(index, review)| {
+            Some(Review {
+                nr: index + 1,
+                literal: storage.get_by_id(review.item_id)?.get_literal(),
+                grade: review.quality.grade(),
+                timestamp: review.timestamp,
+                time: boundary.format_time(review.timestamp),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if format.is_json() {
+        output::print_json(&reviews);
+        return;
+    }
+
+    if reviews.is_empty() {
+        println!("Nothing to undo");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.style = TableStyle::rounded();
+    table.add_row(Row::new(vec![
+        TableCell::new("Nr"),
+        TableCell::new("Kanji"),
+        TableCell::new("Grade"),
+        TableCell::new("Reviewed"),
+    ]));
+
+    for review in reviews {
+        table.add_row(Row::new(vec![
+            TableCell::new_with_alignment(review.nr, 1, Alignment::Right),
+            TableCell::new(review.literal),
+            TableCell::new_with_alignment(review.grade, 1, Alignment::Right),
+            TableCell::new(review.time),
+        ]));
+    }
+
+    println!("{}", table.render());
+}
@@ -0,0 +1,607 @@
+//! User configuration loaded from a TOML file at startup
+//!
+//! ```toml
+//! new_per_day = 8
+//! max_reviews = 20
+//! new_order = "added"
+//! review_order = "overdue"
+//! catch_up_days = 0
+//! day_start_hour = 4
+//! timezone = "Europe/Berlin"
+//! dictionary_url = "https://jotoba.de"
+//!
+//! [scheduler]
+//! maximum_interval = 36500
+//! fuzz = true
+//! load_balance = false
+//! learning_steps = ["10m", "1h"]
+//! relearning_steps = ["10m"]
+//! leech_threshold = 8
+//! leech_action = "tag"
+//! fsrs_weights = [0.4872, 1.4003, ...]
+//! ```
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+/// Environment variable overriding the path of the config file
+pub const CONFIG_ENV: &str = "KANJI_SRS_CONFIG";
+
+/// Name of the config file within the user's config directory
+const CONFIG_FILE: &str = "kanji_srs/config.toml";
+
+/// Amount of weights FSRS uses
+const FSRS_WEIGHT_COUNT: usize = 17;
+
+/// Keys which can be shown and set with the `config` subcommand
+pub const KEYS: [&str; 16] = [
+    "new_per_day",
+    "max_reviews",
+    "new_order",
+    "review_order",
+    "catch_up_days",
+    "day_start_hour",
+    "timezone",
+    "dictionary_url",
+    "scheduler.maximum_interval",
+    "scheduler.fuzz",
+    "scheduler.load_balance",
+    "scheduler.fsrs_weights",
+    "scheduler.learning_steps",
+    "scheduler.relearning_steps",
+    "scheduler.leech_threshold",
+    "scheduler.leech_action",
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// User configuration
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// New kanji to learn in a session
+    pub new_per_day: usize,
+    /// Max amount of reviews in a session. 0 for no limit
+    pub max_reviews: usize,
+    /// Order in which new kanji are introduced
+    pub new_order: NewOrder,
+    /// Order in which due kanji are reviewed if not all of them fit into a session
+    pub review_order: ReviewOrder,
+    /// Days over which due kanji which didn't fit into a session are spread. 0 to review them
+    /// in the next session
+    pub catch_up_days: u32,
+    /// Hour at which a new day starts in local time
+    pub day_start_hour: u32,
+    /// Timezone used for day boundaries, eg. `Asia/Tokyo`. The timezone of the system if not set
+    pub timezone: Option<String>,
+    /// Base URL of the jotoba instance used to look up kanji
+    pub dictionary_url: String,
+    pub scheduler: SchedulerConfig,
+}
+
+/// Parameters of the scheduling algorithms. The algorithm itself is chosen per database
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Longest interval in days an item can be scheduled with
+    pub maximum_interval: u32,
+    /// Randomly move due dates by a few days so kanji learned together don't stay together
+    pub fuzz: bool,
+    /// Move due dates within the fuzz range to the day with the fewest reviews
+    pub load_balance: bool,
+    /// Custom FSRS weights, eg. from the FSRS optimizer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fsrs_weights: Option<Vec<f64>>,
+    /// Delays before new kanji are shown again on the day they have been learned, eg. `10m`
+    pub learning_steps: Vec<String>,
+    /// Delays before failed kanji are shown again on the day they have been failed
+    pub relearning_steps: Vec<String>,
+    /// Lapses after which a kanji is considered a leech. 0 to disable leech detection
+    pub leech_threshold: u32,
+    /// What happens to kanji once they become leeches
+    pub leech_action: LeechAction,
+}
+
+/// Order in which new kanji are introduced
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NewOrder {
+    /// In the order they have been added
+    Added,
+    /// Shuffled, but the same throughout a day
+    Random,
+    /// Fewest strokes first
+    Strokes,
+    /// Most frequently used first
+    Frequency,
+    /// Lowest school grade first
+    Grade,
+    /// Lowest JLPT level (N5) first
+    Jlpt,
+    /// Kanji after the kanji they are built from
+    Components,
+}
+
+/// Order in which due kanji are reviewed. Kanji in learning steps always come first
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewOrder {
+    /// In the order they have been added
+    Added,
+    /// The ones which have been due the longest first
+    Overdue,
+    /// The ones which are most likely forgotten first
+    Retrievability,
+    /// The hardest ones first
+    Ease,
+    /// Shuffled, but the same throughout a day
+    Random,
+}
+
+/// Action applied to leeches
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LeechAction {
+    /// Only mark the kanji as leech
+    Tag,
+    /// Mark the kanji and stop showing it until it gets unsuspended
+    Suspend,
+    /// Mark the kanji and move it from the sessions into the leech drill queue
+    Drill,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            new_per_day: 8,
+            max_reviews: 20,
+            new_order: NewOrder::Added,
+            review_order: ReviewOrder::Overdue,
+            catch_up_days: 0,
+            day_start_hour: 4,
+            timezone: None,
+            dictionary_url: "https://jotoba.de".to_string(),
+            scheduler: SchedulerConfig::default(),
+        }
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            maximum_interval: 36500,
+            fuzz: true,
+            load_balance: false,
+            fsrs_weights: None,
+            learning_steps: vec!["10m".to_string(), "1h".to_string()],
+            relearning_steps: vec!["10m".to_string()],
+            leech_threshold: 8,
+            leech_action: LeechAction::Tag,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Returns the learning steps in seconds
+    pub fn learning_steps(&self) -> Vec<u64> {
+        self.learning_steps
+            .iter()
+            .filter_map(|i| parse_step(i))
+            .collect()
+    }
+
+    /// Returns the relearning steps in seconds
+    pub fn relearning_steps(&self) -> Vec<u64> {
+        self.relearning_steps
+            .iter()
+            .filter_map(|i| parse_step(i))
+            .collect()
+    }
+
+    /// Returns the FSRS weights if custom ones have been set
+    pub fn fsrs_weights(&self) -> Option<[f64; FSRS_WEIGHT_COUNT]> {
+        self.fsrs_weights
+            .as_ref()
+            .and_then(|i| i.as_slice().try_into().ok())
+    }
+}
+
+impl Config {
+    /// Returns the path of the config file. `arg` and the environment variable [`CONFIG_ENV`]
+    /// override the default path within the user's config directory
+    pub fn path(arg: Option<&str>) -> Option<PathBuf> {
+        arg.map(PathBuf::from)
+            .or_else(|| std::env::var(CONFIG_ENV).ok().map(PathBuf::from))
+            .or_else(|| dirs::config_dir().map(|i| i.join(CONFIG_FILE)))
+    }
+
+    /// Loads the config from `file`. A missing file results in the default config
+    pub fn load<P: AsRef<Path>>(file: P) -> Result<Self, String> {
+        let file = file.as_ref();
+        if !file.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let config: Config = toml::from_str(&content).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Writes the config to `file`
+    pub fn save<P: AsRef<Path>>(&self, file: P) -> Result<(), String> {
+        let file = file.as_ref();
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(file, content).map_err(|e| e.to_string())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.day_start_hour > 23 {
+            return Err("day_start_hour has to be between 0 and 23".to_string());
+        }
+
+        if let Some(tz) = &self.timezone {
+            tz.parse::<Tz>()
+                .map_err(|_| format!("Unknown timezone '{tz}'"))?;
+        }
+
+        if self.scheduler.maximum_interval == 0 {
+            return Err("scheduler.maximum_interval has to be at least 1".to_string());
+        }
+
+        if let Some(weights) = &self.scheduler.fsrs_weights {
+            if weights.len() != FSRS_WEIGHT_COUNT {
+                return Err(format!(
+                    "scheduler.fsrs_weights needs {FSRS_WEIGHT_COUNT} values"
+                ));
+            }
+        }
+
+        for step in self
+            .scheduler
+            .learning_steps
+            .iter()
+            .chain(self.scheduler.relearning_steps.iter())
+        {
+            if parse_step(step).is_none() {
+                return Err(format!(
+                    "Invalid learning step '{step}'. Use eg. 30s, 10m or 1h"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the timezone used for day boundaries. `None` for the timezone of the system
+    pub fn tz(&self) -> Option<Tz> {
+        self.timezone.as_ref().and_then(|i| i.parse().ok())
+    }
+
+    /// Returns the value of `key` formatted for displaying
+    pub fn get(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "new_per_day" => self.new_per_day.to_string(),
+            "max_reviews" => self.max_reviews.to_string(),
+            "new_order" => self.new_order.to_string(),
+            "review_order" => self.review_order.to_string(),
+            "catch_up_days" => self.catch_up_days.to_string(),
+            "day_start_hour" => self.day_start_hour.to_string(),
+            "timezone" => self
+                .timezone
+                .clone()
+                .unwrap_or_else(|| "system".to_string()),
+            "dictionary_url" => self.dictionary_url.clone(),
+            "scheduler.maximum_interval" => self.scheduler.maximum_interval.to_string(),
+            "scheduler.fuzz" => self.scheduler.fuzz.to_string(),
+            "scheduler.load_balance" => self.scheduler.load_balance.to_string(),
+            "scheduler.fsrs_weights" => match &self.scheduler.fsrs_weights {
+                Some(weights) => join(weights),
+                None => "default".to_string(),
+            },
+            "scheduler.learning_steps" => self.scheduler.learning_steps.join(","),
+            "scheduler.relearning_steps" => self.scheduler.relearning_steps.join(","),
+            "scheduler.leech_threshold" => self.scheduler.leech_threshold.to_string(),
+            "scheduler.leech_action" => self.scheduler.leech_action.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Sets `key` to `value`. Values which can be unset are reset by passing an empty value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let mut new = self.clone();
+        match key {
+            "new_per_day" => new.new_per_day = parse(key, value)?,
+            "max_reviews" => new.max_reviews = parse(key, value)?,
+            "new_order" => new.new_order = parse(key, value)?,
+            "review_order" => new.review_order = parse(key, value)?,
+            "catch_up_days" => new.catch_up_days = parse(key, value)?,
+            "day_start_hour" => new.day_start_hour = parse(key, value)?,
+            "timezone" => new.timezone = (!value.is_empty()).then(|| value.to_string()),
+            "dictionary_url" => new.dictionary_url = value.trim_end_matches('/').to_string(),
+            "scheduler.maximum_interval" => new.scheduler.maximum_interval = parse(key, value)?,
+            "scheduler.fuzz" => new.scheduler.fuzz = parse(key, value)?,
+            "scheduler.load_balance" => new.scheduler.load_balance = parse(key, value)?,
+            "scheduler.fsrs_weights" => {
+                new.scheduler.fsrs_weights = if value.is_empty() {
+                    None
+                } else {
+                    let weights = value
+                        .split(',')
+                        .map(|i| parse(key, i.trim()))
+                        .collect::<Result<Vec<f64>, _>>()?;
+                    Some(weights)
+                }
+            }
+            "scheduler.learning_steps" => new.scheduler.learning_steps = split_steps(value),
+            "scheduler.relearning_steps" => new.scheduler.relearning_steps = split_steps(value),
+            "scheduler.leech_threshold" => new.scheduler.leech_threshold = parse(key, value)?,
+            "scheduler.leech_action" => new.scheduler.leech_action = parse(key, value)?,
+            _ => return Err(format!("Unknown key '{key}'")),
+        }
+
+        new.validate()?;
+        *self = new;
+        Ok(())
+    }
+}
+
+impl NewOrder {
+    /// All orders, as listed in help texts
+    pub const ALL: [NewOrder; 7] = [
+        NewOrder::Added,
+        NewOrder::Random,
+        NewOrder::Strokes,
+        NewOrder::Frequency,
+        NewOrder::Grade,
+        NewOrder::Jlpt,
+        NewOrder::Components,
+    ];
+
+    /// Returns `true` if the order needs information about the kanji from jotoba
+    #[inline]
+    pub fn needs_info(&self) -> bool {
+        !matches!(self, NewOrder::Added | NewOrder::Random)
+    }
+}
+
+impl Display for NewOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NewOrder::Added => write!(f, "added"),
+            NewOrder::Random => write!(f, "random"),
+            NewOrder::Strokes => write!(f, "strokes"),
+            NewOrder::Frequency => write!(f, "frequency"),
+            NewOrder::Grade => write!(f, "grade"),
+            NewOrder::Jlpt => write!(f, "jlpt"),
+            NewOrder::Components => write!(f, "components"),
+        }
+    }
+}
+
+impl FromStr for NewOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Self::ALL.into_iter().find(|i| i.to_string() == s).ok_or(())
+    }
+}
+
+impl ReviewOrder {
+    /// All orders, as listed in help texts
+    pub const ALL: [ReviewOrder; 5] = [
+        ReviewOrder::Added,
+        ReviewOrder::Overdue,
+        ReviewOrder::Retrievability,
+        ReviewOrder::Ease,
+        ReviewOrder::Random,
+    ];
+}
+
+impl Display for ReviewOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReviewOrder::Added => write!(f, "added"),
+            ReviewOrder::Overdue => write!(f, "overdue"),
+            ReviewOrder::Retrievability => write!(f, "retrievability"),
+            ReviewOrder::Ease => write!(f, "ease"),
+            ReviewOrder::Random => write!(f, "random"),
+        }
+    }
+}
+
+impl FromStr for ReviewOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Self::ALL.into_iter().find(|i| i.to_string() == s).ok_or(())
+    }
+}
+
+impl Display for LeechAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LeechAction::Tag => write!(f, "tag"),
+            LeechAction::Suspend => write!(f, "suspend"),
+            LeechAction::Drill => write!(f, "drill"),
+        }
+    }
+}
+
+impl FromStr for LeechAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tag" => Ok(LeechAction::Tag),
+            "suspend" => Ok(LeechAction::Suspend),
+            "drill" => Ok(LeechAction::Drill),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Sets the config used by the whole program. Can only be set once. Returns `false` without
+/// setting `config` if a config has been set or read already
+#[must_use]
+pub fn init(config: Config) -> bool {
+    CONFIG.set(config).is_ok()
+}
+
+/// Returns the config of the program. The default config if none has been set
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{value}' for {key}"))
+}
+
+/// Parses a learning step like `30s`, `10m`, `1h` or `1d` into seconds
+fn parse_step(step: &str) -> Option<u64> {
+    let step = step.trim();
+    let unit = match step.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    let amount: u64 = step[..step.len() - 1].parse().ok()?;
+    (amount > 0).then_some(amount * unit)
+}
+
+/// Splits a list of learning steps separated by commas or spaces. Empty for no steps
+fn split_steps(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|i| !i.is_empty())
+        .map(|i| i.to_string())
+        .collect()
+}
+
+fn join<T: Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_file() {
+        let config: Config = toml::from_str(
+            "new_per_day = 5
+            timezone = 'Asia/Tokyo'
+            [scheduler]
+            maximum_interval = 365",
+        )
+        .unwrap();
+
+        assert_eq!(5, config.new_per_day);
+        assert_eq!(Config::default().max_reviews, config.max_reviews);
+        assert_eq!(Some(chrono_tz::Asia::Tokyo), config.tz());
+        assert_eq!(365, config.scheduler.maximum_interval);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn set_values() {
+        let mut config = Config::default();
+        config.set("new_per_day", "12").unwrap();
+        config.set("timezone", "Europe/Berlin").unwrap();
+        assert_eq!(Some("12".to_string()), config.get("new_per_day"));
+        assert_eq!(Some(chrono_tz::Europe::Berlin), config.tz());
+
+        assert!(config.set("day_start_hour", "24").is_err());
+        assert!(config.set("timezone", "Mars/Olympus").is_err());
+        assert!(config.set("scheduler.fsrs_weights", "1,2,3").is_err());
+        assert!(config.set("unknown", "1").is_err());
+        assert_eq!(4, config.day_start_hour);
+
+        let weights = vec!["1.5"; FSRS_WEIGHT_COUNT].join(", ");
+        config.set("scheduler.fsrs_weights", &weights).unwrap();
+        assert_eq!(
+            Some([1.5; FSRS_WEIGHT_COUNT]),
+            config.scheduler.fsrs_weights()
+        );
+
+        config.set("timezone", "").unwrap();
+        assert_eq!(None, config.tz());
+    }
+
+    #[test]
+    fn learning_steps() {
+        let mut config = Config::default();
+        assert_eq!(vec![600, 3600], config.scheduler.learning_steps());
+
+        config
+            .set("scheduler.learning_steps", "30s, 2h 1d")
+            .unwrap();
+        assert_eq!(vec![30, 7200, 86400], config.scheduler.learning_steps());
+
+        config.set("scheduler.relearning_steps", "").unwrap();
+        assert!(config.scheduler.relearning_steps().is_empty());
+
+        assert!(config.set("scheduler.learning_steps", "10").is_err());
+        assert!(config.set("scheduler.learning_steps", "0m").is_err());
+        assert!(config.set("scheduler.learning_steps", "5x").is_err());
+
+        config.set("scheduler.leech_action", "Suspend").unwrap();
+        assert_eq!(LeechAction::Suspend, config.scheduler.leech_action);
+        assert!(config.set("scheduler.leech_action", "delete").is_err());
+    }
+
+    #[test]
+    fn new_order() {
+        let mut config = Config::default();
+        assert_eq!(NewOrder::Added, config.new_order);
+        config.set("new_order", "Components").unwrap();
+        assert_eq!(NewOrder::Components, config.new_order);
+        assert!(config.set("new_order", "alphabetical").is_err());
+
+        for order in NewOrder::ALL {
+            assert_eq!(Ok(order), order.to_string().parse());
+        }
+
+        config.set("review_order", "Retrievability").unwrap();
+        assert_eq!(ReviewOrder::Retrievability, config.review_order);
+        for order in ReviewOrder::ALL {
+            assert_eq!(Ok(order), order.to_string().parse());
+        }
+    }
+
+    #[test]
+    fn every_key_readable() {
+        let config = Config::default();
+        for key in KEYS {
+            assert!(config.get(key).is_some(), "{key}");
+        }
+    }
+
+    #[test]
+    fn init_once() {
+        // Reading the config sets the default one
+        get();
+        assert!(!init(Config::default()));
+    }
+}
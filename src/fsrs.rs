@@ -0,0 +1,268 @@
+//! The FSRS algorithm
+//!
+//! FSRS (Free Spaced Repetition Scheduler) models the memory of an item with two values and
+//! schedules the next review at the point where the probability of recalling the item drops to
+//! the desired retention.
+//!
+//! # Memory state
+//!
+//! * Stability: The number of days after which the probability of
+//!   recalling the item drops to 90%
+//!
+//! * Difficulty: How hard it is to increase the stability of the item,
+//!   ranging from 1 to 10
+//!
+//! This implements version 4.5 of the algorithm using its default parameters.
+
+use crate::sm2::{RepQuality, SM2};
+use serde::{Deserialize, Serialize};
+
+/// Default parameters of FSRS-4.5
+const DEFAULT_WEIGHTS: [f64; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 0.1367, 1.0461, 2.1072,
+    0.0793, 0.3246, 1.587, 0.2272, 2.8755,
+];
+
+const DECAY: f64 = -0.5;
+
+/// Chosen so that the retrievability after `stability` days is exactly 90%
+const FACTOR: f64 = 19.0 / 81.0;
+
+/// Longest interval in days an item can be scheduled with
+const MAX_INTERVAL: u32 = 36500;
+
+/// Default probability of recalling an item when it is due
+pub const DEFAULT_RETENTION: f64 = 0.9;
+
+/// The four ratings FSRS distinguishes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rating {
+    Again = 1,
+    Hard = 2,
+    Good = 3,
+    Easy = 4,
+}
+
+impl From<RepQuality> for Rating {
+    #[inline]
+    fn from(quality: RepQuality) -> Self {
+        match quality {
+            RepQuality::Grade5 => Rating::Easy,
+            RepQuality::Grade4 => Rating::Good,
+            RepQuality::Grade3 => Rating::Hard,
+            RepQuality::Grade2 | RepQuality::Grade1 | RepQuality::Grade0 => Rating::Again,
+        }
+    }
+}
+
+/// Memory state of an item
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FSRSState {
+    /// Days after which the retrievability drops to 90%
+    pub stability: f64,
+    /// Difficulty of the item from 1 to 10
+    pub difficulty: f64,
+}
+
+/// An implementation of the FSRS algorithm
+#[derive(Debug, Copy, Clone)]
+pub struct FSRS {
+    weights: [f64; 17],
+    desired_retention: f64,
+}
+
+impl FSRS {
+    /// Creates a new instance of FSRS scheduling for the given retention
+    #[inline]
+    pub fn new(desired_retention: f64) -> Self {
+        Self::with_weights(desired_retention, DEFAULT_WEIGHTS)
+    }
+
+    /// Creates a new instance of FSRS using custom weights, eg. from the FSRS optimizer
+    #[inline]
+    pub fn with_weights(desired_retention: f64, weights: [f64; 17]) -> Self {
+        Self {
+            weights,
+            desired_retention: desired_retention.clamp(0.7, 0.99),
+        }
+    }
+
+    /// Returns the memory state after reviewing an item with the given rating. `state` is `None`
+    /// for items which have never been reviewed and `elapsed_days` the days since the last review
+    pub fn review(&self, state: Option<FSRSState>, rating: Rating, elapsed_days: u32) -> FSRSState {
+        let state = match state {
+            Some(state) => state,
+            None => {
+                return FSRSState {
+                    stability: self.init_stability(rating),
+                    difficulty: self.init_difficulty(rating),
+                }
+            }
+        };
+
+        let retrievability = retrievability(elapsed_days, state.stability);
+        let stability = if rating == Rating::Again {
+            self.stability_after_failure(state, retrievability)
+        } else {
+            self.stability_after_success(state, retrievability, rating)
+        };
+
+        FSRSState {
+            stability,
+            difficulty: self.next_difficulty(state.difficulty, rating),
+        }
+    }
+
+    /// Returns the interval in days after which the retrievability of an item with the given
+    /// state drops to the desired retention
+    pub fn interval(&self, state: FSRSState) -> u32 {
+        let interval = state.stability / FACTOR * (self.desired_retention.powf(1.0 / DECAY) - 1.0);
+        (interval.round() as u32).clamp(1, MAX_INTERVAL)
+    }
+
+    /// Converts the state of an item which has been scheduled with SM-2 using `interval` days
+    pub fn state_from_sm2(&self, sm2: &SM2, interval: u32) -> FSRSState {
+        // Invert `interval` so the item keeps being due at the same time
+        let stability =
+            interval.max(1) as f64 * FACTOR / (self.desired_retention.powf(1.0 / DECAY) - 1.0);
+
+        // Default ease of 2.5 matches the difficulty of a new item rated 'Good'
+        let difficulty = (self.weights[4] + (2.5 - sm2.ease_factor()) * 4.0).clamp(1.0, 10.0);
+
+        FSRSState {
+            stability,
+            difficulty,
+        }
+    }
+
+    /// Returns the SM-2 ease factor matching the difficulty of `state`. Inverse of the
+    /// conversion done by [`FSRS::state_from_sm2`]
+    pub fn ease_factor(&self, state: FSRSState) -> f64 {
+        (2.5 - (state.difficulty - self.weights[4]) / 4.0).max(1.3)
+    }
+
+    fn init_stability(&self, rating: Rating) -> f64 {
+        self.weights[rating as usize - 1].max(0.1)
+    }
+
+    fn init_difficulty(&self, rating: Rating) -> f64 {
+        let w = &self.weights;
+        (w[4] - (rating as i32 - 3) as f64 * w[5]).clamp(1.0, 10.0)
+    }
+
+    fn next_difficulty(&self, difficulty: f64, rating: Rating) -> f64 {
+        let w = &self.weights;
+        let next = difficulty - w[6] * (rating as i32 - 3) as f64;
+        // Mean reversion towards the difficulty of a new item rated 'Good'
+        let next = w[7] * self.init_difficulty(Rating::Good) + (1.0 - w[7]) * next;
+        next.clamp(1.0, 10.0)
+    }
+
+    fn stability_after_success(&self, state: FSRSState, r: f64, rating: Rating) -> f64 {
+        let w = &self.weights;
+        let hard_penalty = if rating == Rating::Hard { w[15] } else { 1.0 };
+        let easy_bonus = if rating == Rating::Easy { w[16] } else { 1.0 };
+
+        state.stability
+            * (w[8].exp()
+                * (11.0 - state.difficulty)
+                * state.stability.powf(-w[9])
+                * ((w[10] * (1.0 - r)).exp() - 1.0)
+                * hard_penalty
+                * easy_bonus
+                + 1.0)
+    }
+
+    fn stability_after_failure(&self, state: FSRSState, r: f64) -> f64 {
+        let w = &self.weights;
+        let stability = w[11]
+            * state.difficulty.powf(-w[12])
+            * ((state.stability + 1.0).powf(w[13]) - 1.0)
+            * (w[14] * (1.0 - r)).exp();
+        stability.min(state.stability).max(0.1)
+    }
+}
+
+impl Default for FSRS {
+    #[inline]
+    fn default() -> Self {
+        FSRS::new(DEFAULT_RETENTION)
+    }
+}
+
+/// Returns the probability of recalling an item with `stability` after `elapsed_days`
+#[inline]
+pub fn retrievability(elapsed_days: u32, stability: f64) -> f64 {
+    (1.0 + FACTOR * elapsed_days as f64 / stability).powf(DECAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(n: f64) -> f64 {
+        (n * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn first_review() {
+        let fsrs = FSRS::default();
+
+        let state = fsrs.review(None, Rating::Good, 0);
+        assert_eq!(3.7145, state.stability);
+        assert_eq!(5.1618, state.difficulty);
+        assert_eq!(4, fsrs.interval(state));
+
+        let state = fsrs.review(None, Rating::Again, 0);
+        assert_eq!(0.4872, state.stability);
+        assert_eq!(1, fsrs.interval(state));
+    }
+
+    #[test]
+    fn retrievability_at_stability() {
+        assert_eq!(1.0, retrievability(0, 10.0));
+        assert_eq!(0.9, round(retrievability(10, 10.0)));
+    }
+
+    #[test]
+    fn subsequent_reviews() {
+        let fsrs = FSRS::default();
+        let state = fsrs.review(None, Rating::Good, 0);
+
+        let good = fsrs.review(Some(state), Rating::Good, 4);
+        let easy = fsrs.review(Some(state), Rating::Easy, 4);
+        let hard = fsrs.review(Some(state), Rating::Hard, 4);
+        let again = fsrs.review(Some(state), Rating::Again, 4);
+
+        assert!(good.stability > state.stability);
+        assert!(easy.stability > good.stability);
+        assert!(hard.stability < good.stability);
+        assert!(again.stability < state.stability);
+        assert!(again.difficulty > state.difficulty);
+        assert!(easy.difficulty < state.difficulty);
+    }
+
+    #[test]
+    fn desired_retention() {
+        let state = FSRSState {
+            stability: 10.0,
+            difficulty: 5.0,
+        };
+        assert_eq!(10, FSRS::new(0.9).interval(state));
+        assert!(FSRS::new(0.8).interval(state) > 10);
+        assert!(FSRS::new(0.95).interval(state) < 10);
+    }
+
+    #[test]
+    fn from_sm2() {
+        let fsrs = FSRS::default();
+        let state = fsrs.state_from_sm2(&SM2::new().set_repetitions(3), 15);
+        assert_eq!(15, fsrs.interval(state));
+        assert_eq!(5.1618, state.difficulty);
+
+        let hard = fsrs.state_from_sm2(&SM2::new().set_ease_factor(1.3), 15);
+        assert!(hard.difficulty > state.difficulty);
+        assert_eq!(1.3, round(fsrs.ease_factor(hard)));
+        assert_eq!(2.5, round(fsrs.ease_factor(state)));
+    }
+}
@@ -160,53 +160,53 @@ impl JapaneseExt for char {
 impl JapaneseExt for str {
     #[inline]
     fn is_hiragana(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_hiragana())
+        !self.chars().any(|s| !s.is_hiragana())
     }
 
     #[inline]
     fn is_katakana(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_katakana())
+        !self.chars().any(|s| !s.is_katakana())
     }
 
     #[inline]
     fn is_roman_letter(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_roman_letter())
+        !self.chars().any(|s| !s.is_roman_letter())
     }
 
     #[inline]
     fn has_roman_letter(&self) -> bool {
-        self.chars().into_iter().any(|s| s.is_roman_letter())
+        self.chars().any(|s| s.is_roman_letter())
     }
 
     #[inline]
     fn has_kana(&self) -> bool {
-        self.chars().into_iter().any(|s| s.is_kana())
+        self.chars().any(|s| s.is_kana())
     }
 
     #[inline]
     fn has_symbol(&self) -> bool {
-        self.chars().into_iter().any(|s| s.is_symbol())
+        self.chars().any(|s| s.is_symbol())
     }
 
     #[inline]
     fn is_kana(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_kana())
+        !self.chars().any(|s| !s.is_kana())
     }
 
     #[inline]
     fn is_kanji(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_kanji())
+        !self.chars().any(|s| !s.is_kanji())
     }
 
     #[inline]
     fn has_kanji(&self) -> bool {
-        self.chars().into_iter().any(|s| s.is_kanji())
+        self.chars().any(|s| s.is_kanji())
     }
 
     #[inline]
     fn is_japanese(&self) -> bool {
         let mut buf = [0; 16];
-        !self.chars().into_iter().any(|c| {
+        !self.chars().any(|c| {
             let s = c.encode_utf8(&mut buf);
             !s.is_kana() && !s.is_kanji() && !s.is_symbol() && !s.is_roman_letter()
         })
@@ -215,7 +215,7 @@ impl JapaneseExt for str {
     #[inline]
     fn has_japanese(&self) -> bool {
         let mut buf = [0; 16];
-        self.chars().into_iter().any(|c| {
+        self.chars().any(|c| {
             let s = c.encode_utf8(&mut buf);
             s.is_kana() || s.is_kanji() || s.is_symbol() || s.is_roman_letter()
         })
@@ -223,23 +223,22 @@ impl JapaneseExt for str {
 
     #[inline]
     fn kanji_count(&self) -> usize {
-        self.chars().into_iter().filter(|i| i.is_kanji()).count()
+        self.chars().filter(|i| i.is_kanji()).count()
     }
 
     #[inline]
     fn is_symbol(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_symbol())
+        !self.chars().any(|s| !s.is_symbol())
     }
 
     #[inline]
-
     fn is_small_katakana(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_small_katakana())
+        !self.chars().any(|s| !s.is_small_katakana())
     }
 
     #[inline]
     fn is_small_hiragana(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_small_hiragana())
+        !self.chars().any(|s| !s.is_small_hiragana())
     }
 
     #[inline]
@@ -249,6 +248,6 @@ impl JapaneseExt for str {
 
     #[inline]
     fn is_particle(&self) -> bool {
-        !self.chars().into_iter().any(|s| !s.is_particle())
+        !self.chars().any(|s| !s.is_particle())
     }
 }
@@ -0,0 +1,84 @@
+//! Access to kanji information through the jotoba API
+
+use crate::config;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Endpoint for kanji searches, relative to the dictionary URL
+const KANJI_API_PATH: &str = "/api/search/kanji";
+
+/// Time after which a request gets aborted
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Information about a single kanji
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct KanjiInfo {
+    pub literal: char,
+    pub meanings: Vec<String>,
+    pub onyomi: Vec<String>,
+    pub kunyomi: Vec<String>,
+    pub stroke_count: Option<u8>,
+    pub grade: Option<u8>,
+    pub frequency: Option<u16>,
+    pub jlpt: Option<u8>,
+    /// Kanji and radicals the kanji is built from
+    pub parts: Vec<String>,
+}
+
+impl KanjiInfo {
+    /// Returns the kanji and radicals the kanji is built from, without itself
+    pub fn components(&self) -> impl Iterator<Item = char> + '_ {
+        self.parts
+            .iter()
+            .filter_map(|i| i.chars().next())
+            .filter(move |i| *i != self.literal)
+    }
+}
+
+#[derive(Serialize)]
+struct KanjiRequest<'a> {
+    query: &'a str,
+    language: &'a str,
+    no_english: bool,
+}
+
+#[derive(Deserialize)]
+struct KanjiResponse {
+    #[serde(default)]
+    kanji: Vec<KanjiInfo>,
+}
+
+/// Looks up `literal` on jotoba. `None` if jotoba couldn't be reached or doesn't know the kanji
+pub fn kanji_info(literal: char) -> Option<KanjiInfo> {
+    kanji_infos(&[literal])?
+        .into_iter()
+        .find(|i| i.literal == literal)
+}
+
+/// Looks up all kanji in `literals` on jotoba at once. Kanji jotoba doesn't know are missing in
+/// the result. `None` if jotoba couldn't be reached
+pub fn kanji_infos(literals: &[char]) -> Option<Vec<KanjiInfo>> {
+    let query = literals.iter().collect::<String>();
+    let request = KanjiRequest {
+        query: &query,
+        language: "English",
+        no_english: false,
+    };
+
+    let url = format!("{}{KANJI_API_PATH}", config::get().dictionary_url);
+    let response: KanjiResponse = ureq::post(&url)
+        .timeout(TIMEOUT)
+        .send_json(request)
+        .ok()?
+        .into_json()
+        .ok()?;
+
+    Some(
+        response
+            .kanji
+            .into_iter()
+            .filter(|i| literals.contains(&i.literal))
+            .collect(),
+    )
+}
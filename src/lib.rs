@@ -0,0 +1,11 @@
+pub mod browser;
+pub mod cli;
+pub mod config;
+pub mod fsrs;
+pub mod japanese;
+pub mod jotoba;
+pub mod scheduler;
+pub mod sm2;
+pub mod storage;
+pub mod time;
+pub mod utils;
@@ -1,65 +1,116 @@
-pub mod browser;
-pub mod cli;
-pub mod japanese;
-pub mod sm2;
-pub mod storage;
-pub mod utils;
+use std::path::PathBuf;
 
-use std::{path::PathBuf, str::FromStr};
+use kanji_srs::{
+    cli::{
+        self,
+        output::{self, Format},
+    },
+    config::{self, Config},
+    storage::{
+        location::{self, Location},
+        sqlite, Storage,
+    },
+    time::{DayBoundary, FixedClock},
+    utils,
+};
 
-use proc_lock::{lock, LockPath};
+/// Returns the directory of the storage. Offers to move an old storage from the working
+/// directory to the default location
+fn get_storage_path(arg: Option<&str>) -> PathBuf {
+    let location = Location::from_env(arg);
 
-use crate::storage::Storage;
+    if let Some(legacy) = location.legacy_storage() {
+        let question = format!(
+            "Found a database in {}. Move it to {}?",
+            legacy.display(),
+            location.dir.display()
+        );
+        if utils::confirmation(&question) {
+            location::move_storage(&legacy, &location.dir).expect("Failed to move database");
+        }
+    }
 
-fn get_storage_path() -> PathBuf {
-    let path = PathBuf::from_str("./storage/").unwrap();
-    if !path.exists() {
-        std::fs::create_dir(&path).unwrap();
+    if !location.dir.exists() {
+        std::fs::create_dir_all(&location.dir).unwrap();
     }
 
-    path
+    location.dir
 }
 
 fn main() {
     let app = cli::build().get_matches();
-    let path = get_storage_path();
-    let guard = lock(&LockPath::Tmp("kanji_srs.lock")).unwrap();
-    let item_storage = path.join("item_storage").to_str().unwrap().to_string();
-    let srs_storage = path.join("srs_storage").to_str().unwrap().to_string();
-    let item_storage = std::thread::spawn(move || storage::ItemStorage::new(item_storage));
-    let srs_storage = std::thread::spawn(move || storage::SRSStorage::new(srs_storage));
-    let srs_storage = srs_storage.join().unwrap();
-    let item_storage = item_storage.join().unwrap();
-    let storage = Storage::new(item_storage, srs_storage);
+    let format = Format::of(&app);
+
+    let config_file = Config::path(app.value_of("config"));
+    match config_file
+        .map(Config::load)
+        .unwrap_or_else(|| Ok(Config::default()))
+    {
+        Ok(config) => {
+            if !config::init(config) {
+                panic!("Config has been set already");
+            }
+        }
+        Err(err) => {
+            output::print_error(format, &format!("Invalid config file: {err}"));
+            return;
+        }
+    }
+
+    if let Some(("config", sub_matches)) = app.subcommand() {
+        cli::config::run(sub_matches);
+        return;
+    }
+
+    let path = get_storage_path(app.value_of("db"));
+    let mut storage = Storage::open(&path).expect("Failed to open storage");
+    if storage.migrated() {
+        // Keeps the output of scripts clean
+        eprintln!("Migrated database to {}", sqlite::DB_FILE);
+    }
 
     let sub_command = app.subcommand();
 
     if !storage.check() {
         if let Some(("fix-db", _)) = sub_command.as_ref() {
         } else {
-            println!("Database broken. Run with --fix-db to fix it");
+            output::print_error(format, "Database broken. Run with --fix-db to fix it");
             return;
         }
     }
 
-    // println!("{:#?}", storage);
+    storage.set_day_boundary(DayBoundary::from_config(config::get()));
+    storage.set_scheduler_config(config::get().scheduler.clone());
 
-    /*
-    for i in storage.get_srs_storage_mut().iter_mut() {
-        i.直す();
+    if let Some(now) = app.value_of("now") {
+        match storage.day_boundary().parse_time(now) {
+            Some(now) => storage.set_clock(FixedClock::new(now)),
+            None => {
+                let message = format!("Invalid time '{now}'. Use YYYY-MM-DD or YYYY-MM-DD HH:MM");
+                output::print_error(format, &message);
+                return;
+            }
+        }
     }
-    */
 
     match sub_command {
         Some(("add", sub_matches)) => cli::add::run(storage, sub_matches),
         Some(("remove", sub_matches)) => cli::remove::run(storage, sub_matches),
         Some(("reset", sub_matches)) => cli::reset::run(storage, sub_matches),
+        Some(("suspend", sub_matches)) => cli::suspend::suspend(storage, sub_matches),
+        Some(("unsuspend", sub_matches)) => cli::suspend::unsuspend(storage, sub_matches),
+        Some(("bury", sub_matches)) => cli::suspend::bury(storage, sub_matches),
         Some(("info", sub_matches)) => cli::info::run(storage, sub_matches),
+        Some(("forecast", sub_matches)) => cli::forecast::run(storage, sub_matches),
         Some(("review", sub_matches)) => cli::review::run(storage, sub_matches),
+        Some(("undo", sub_matches)) => cli::undo::run(storage, sub_matches),
+        Some(("export", sub_matches)) => cli::export::export(storage, sub_matches),
+        Some(("import", sub_matches)) => cli::export::import(storage, sub_matches),
         Some(("fix-db", sub_matches)) => cli::fix_db::run(storage, sub_matches),
         Some(("stats", sub_matches)) => cli::stats::run(storage, sub_matches),
+        Some(("leeches", sub_matches)) => cli::leeches::run(storage, sub_matches),
         Some(("all", sub_matches)) => cli::all::run(storage, sub_matches),
+        Some(("scheduler", sub_matches)) => cli::scheduler::run(storage, sub_matches),
         _ => cli::run(storage, app),
     }
-    drop(guard);
 }
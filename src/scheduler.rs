@@ -0,0 +1,422 @@
+//! Scheduling of reviews
+//!
+//! A [`Scheduler`] decides when an item has to be reviewed next, based on the grade given in a
+//! review and the state it keeps for each item. Items store the state of the scheduler they have
+//! been reviewed with last as [`SchedulerState`], which gets converted when the scheduler of a
+//! database is changed.
+
+use crate::{
+    config::SchedulerConfig,
+    fsrs::{FSRSState, FSRS},
+    sm2::{RepQuality, SM2},
+    storage::settings::{SchedulerKind, Settings},
+    utils,
+};
+use serde::{Deserialize, Serialize};
+
+/// A spaced repetition algorithm
+pub trait Scheduler {
+    /// State the algorithm keeps for each item
+    type State: Copy;
+
+    /// Returns the state of an item which has never been reviewed
+    fn initial_state(&self) -> Self::State;
+
+    /// Reviews an item with `grade` at the unix timestamp `now`
+    fn review(&self, state: &Self::State, grade: RepQuality, now: u64) -> Scheduled<Self::State>;
+
+    /// Serializes `state` into a string which can be stored
+    fn serialize_state(&self, state: &Self::State) -> String;
+
+    /// Parses a state serialized by [`Scheduler::serialize_state`]. `None` if `s` is invalid
+    fn deserialize_state(&self, s: &str) -> Option<Self::State>;
+}
+
+/// Intervals from which on due dates get fuzzed and the fraction of the interval added to the
+/// fuzz range within them
+const FUZZ_RANGES: [(f64, f64, f64); 3] =
+    [(2.5, 7.0, 0.15), (7.0, 20.0, 0.1), (20.0, f64::MAX, 0.05)];
+
+/// Returns the shortest and longest interval in days an item scheduled with `interval` days may
+/// be due after. Short intervals aren't fuzzed
+pub fn fuzz_range(interval: u32, maximum_interval: u32) -> (u32, u32) {
+    let ivl = interval as f64;
+    if ivl < FUZZ_RANGES[0].0 {
+        return (interval, interval);
+    }
+
+    let delta = 1.0
+        + FUZZ_RANGES
+            .iter()
+            .map(|(start, end, factor)| factor * (ivl.min(*end) - start).max(0.0))
+            .sum::<f64>();
+    let max = ((ivl + delta).round() as u32).min(maximum_interval);
+    let min = ((ivl - delta).round() as u32).max(2).min(max);
+    (min, max)
+}
+
+/// Result of a review
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scheduled<S> {
+    /// State of the item after the review
+    pub state: S,
+    /// Interval in days until the next review
+    pub interval: u32,
+}
+
+/// The SM-2 algorithm
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SM2Scheduler;
+
+impl Scheduler for SM2Scheduler {
+    type State = SM2;
+
+    #[inline]
+    fn initial_state(&self) -> SM2 {
+        SM2::new()
+    }
+
+    fn review(&self, state: &SM2, grade: RepQuality, _now: u64) -> Scheduled<SM2> {
+        let state = state.review(grade);
+        let interval = state.new_interval(&grade) as u32;
+        Scheduled { state, interval }
+    }
+
+    fn serialize_state(&self, state: &SM2) -> String {
+        format!("{};{}", state.repetitions(), state.ease_factor())
+    }
+
+    fn deserialize_state(&self, s: &str) -> Option<SM2> {
+        let (repetitions, ease_factor) = s.split_once(';')?;
+        Some(
+            SM2::new()
+                .set_repetitions(repetitions.parse().ok()?)
+                .set_ease_factor(ease_factor.parse().ok()?),
+        )
+    }
+}
+
+/// State of an item scheduled with FSRS
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct FSRSItemState {
+    /// Memory state. `None` if the item has never been reviewed
+    pub memory: Option<FSRSState>,
+    /// Unix timestamp of the last review
+    pub last_review: u64,
+}
+
+impl Scheduler for FSRS {
+    type State = FSRSItemState;
+
+    #[inline]
+    fn initial_state(&self) -> FSRSItemState {
+        FSRSItemState {
+            memory: None,
+            last_review: 0,
+        }
+    }
+
+    fn review(
+        &self,
+        state: &FSRSItemState,
+        grade: RepQuality,
+        now: u64,
+    ) -> Scheduled<FSRSItemState> {
+        let elapsed_days = now.saturating_sub(state.last_review) / utils::DAY_SECS;
+        let memory = FSRS::review(self, state.memory, grade.into(), elapsed_days as u32);
+        let interval = self.interval(memory);
+        Scheduled {
+            state: FSRSItemState {
+                memory: Some(memory),
+                last_review: now,
+            },
+            interval,
+        }
+    }
+
+    fn serialize_state(&self, state: &FSRSItemState) -> String {
+        match state.memory {
+            Some(memory) => format!(
+                "{};{};{}",
+                memory.stability, memory.difficulty, state.last_review
+            ),
+            None => String::new(),
+        }
+    }
+
+    fn deserialize_state(&self, s: &str) -> Option<FSRSItemState> {
+        if s.is_empty() {
+            return Some(self.initial_state());
+        }
+
+        let mut parts = s.split(';');
+        let stability = parts.next()?.parse().ok()?;
+        let difficulty = parts.next()?.parse().ok()?;
+        let last_review = parts.next()?.parse().ok()?;
+        Some(FSRSItemState {
+            memory: Some(FSRSState {
+                stability,
+                difficulty,
+            }),
+            last_review,
+        })
+    }
+}
+
+/// State of an item for any of the available schedulers
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum SchedulerState {
+    SM2(SM2),
+    FSRS(FSRSItemState),
+}
+
+impl SchedulerState {
+    /// Returns the initial state of the scheduler selected in `settings`
+    pub fn new(settings: &Settings) -> Self {
+        match settings.scheduler {
+            SchedulerKind::SM2 => SchedulerState::SM2(SM2Scheduler.initial_state()),
+            SchedulerKind::FSRS => SchedulerState::FSRS(FSRS::default().initial_state()),
+        }
+    }
+
+    /// Returns the scheduler the state belongs to
+    #[inline]
+    pub fn kind(&self) -> SchedulerKind {
+        match self {
+            SchedulerState::SM2(_) => SchedulerKind::SM2,
+            SchedulerState::FSRS(_) => SchedulerKind::FSRS,
+        }
+    }
+
+    /// Reviews an item with the scheduler selected in `settings` and the parameters of `config`.
+    /// The state has to be converted with [`SchedulerState::convert`] first if it belongs to
+    /// another scheduler
+    pub fn review(
+        &self,
+        settings: &Settings,
+        config: &SchedulerConfig,
+        grade: RepQuality,
+        now: u64,
+    ) -> Scheduled<Self> {
+        let scheduled = match self {
+            SchedulerState::SM2(state) => {
+                let scheduled = SM2Scheduler.review(state, grade, now);
+                wrap(scheduled, SchedulerState::SM2)
+            }
+            SchedulerState::FSRS(state) => {
+                // `FSRS` has an inherent `review` operating on the memory state only
+                let scheduled = Scheduler::review(&fsrs(settings, config), state, grade, now);
+                wrap(scheduled, SchedulerState::FSRS)
+            }
+        };
+
+        if scheduled.interval <= config.maximum_interval {
+            return scheduled;
+        }
+
+        Scheduled {
+            interval: config.maximum_interval,
+            ..scheduled
+        }
+    }
+
+    /// Converts the state into one of the scheduler selected in `settings`, keeping the item due
+    /// after `interval` days counted from `last_review`
+    pub fn convert(
+        &self,
+        settings: &Settings,
+        config: &SchedulerConfig,
+        interval: u32,
+        last_review: u64,
+    ) -> Self {
+        let fsrs = fsrs(settings, config);
+        match (self, settings.scheduler) {
+            (SchedulerState::SM2(sm2), SchedulerKind::FSRS) => {
+                let memory = (interval > 0).then(|| fsrs.state_from_sm2(sm2, interval));
+                SchedulerState::FSRS(FSRSItemState {
+                    memory,
+                    last_review,
+                })
+            }
+            (SchedulerState::FSRS(state), SchedulerKind::SM2) => {
+                let sm2 = match state.memory {
+                    // SM-2 only knows the amount of successful reviews in a row
+                    Some(memory) => SM2::new()
+                        .set_repetitions(if interval >= 6 { 2 } else { 1 })
+                        .set_ease_factor(fsrs.ease_factor(memory)),
+                    None => SM2::new(),
+                };
+                SchedulerState::SM2(sm2)
+            }
+            _ => *self,
+        }
+    }
+
+    /// Moves the time of the last review `secs` seconds later, so the time an item has been
+    /// put aside doesn't count as time since the last review
+    pub fn postpone(&self, secs: u64) -> Self {
+        match self {
+            SchedulerState::FSRS(state) if state.last_review > 0 => {
+                SchedulerState::FSRS(FSRSItemState {
+                    last_review: state.last_review + secs,
+                    ..*state
+                })
+            }
+            _ => *self,
+        }
+    }
+
+    /// Returns the SM-2 ease factor of the state. For FSRS it gets derived from the difficulty
+    pub fn ease_factor(&self) -> f64 {
+        match self {
+            SchedulerState::SM2(sm2) => sm2.ease_factor(),
+            SchedulerState::FSRS(state) => state
+                .memory
+                .map(|i| FSRS::default().ease_factor(i))
+                .unwrap_or_else(|| SM2::new().ease_factor()),
+        }
+    }
+
+    /// Returns the state serialized by its scheduler
+    pub fn serialize(&self) -> String {
+        match self {
+            SchedulerState::SM2(state) => SM2Scheduler.serialize_state(state),
+            SchedulerState::FSRS(state) => FSRS::default().serialize_state(state),
+        }
+    }
+
+    /// Parses a state of the scheduler `kind` serialized with [`SchedulerState::serialize`]
+    pub fn deserialize(kind: SchedulerKind, s: &str) -> Option<Self> {
+        Some(match kind {
+            SchedulerKind::SM2 => SchedulerState::SM2(SM2Scheduler.deserialize_state(s)?),
+            SchedulerKind::FSRS => SchedulerState::FSRS(FSRS::default().deserialize_state(s)?),
+        })
+    }
+}
+
+impl Default for SchedulerState {
+    #[inline]
+    fn default() -> Self {
+        SchedulerState::new(&Settings::default())
+    }
+}
+
+/// Returns FSRS using the retention of `settings` and the weights of `config`
+fn fsrs(settings: &Settings, config: &SchedulerConfig) -> FSRS {
+    match config.fsrs_weights() {
+        Some(weights) => FSRS::with_weights(settings.desired_retention, weights),
+        None => FSRS::new(settings.desired_retention),
+    }
+}
+
+#[inline]
+fn wrap<S>(scheduled: Scheduled<S>, f: impl Fn(S) -> SchedulerState) -> Scheduled<SchedulerState> {
+    Scheduled {
+        state: f(scheduled.state),
+        interval: scheduled.interval,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn fsrs_settings() -> Settings {
+        Settings {
+            scheduler: SchedulerKind::FSRS,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn sm2_review() {
+        let settings = Settings::default();
+        let mut config = SchedulerConfig::default();
+        let state = SchedulerState::default();
+        let scheduled = state.review(&settings, &config, RepQuality::Grade4, NOW);
+        assert_eq!(1, scheduled.interval);
+
+        let second = scheduled
+            .state
+            .review(&settings, &config, RepQuality::Grade4, NOW);
+        assert_eq!(6, second.interval);
+
+        config.maximum_interval = 3;
+        let capped = scheduled
+            .state
+            .review(&settings, &config, RepQuality::Grade4, NOW);
+        assert_eq!(3, capped.interval);
+    }
+
+    #[test]
+    fn fuzz_ranges() {
+        assert_eq!((1, 1), fuzz_range(1, 36500));
+        assert_eq!((2, 2), fuzz_range(2, 36500));
+        assert_eq!((4, 8), fuzz_range(6, 36500));
+        assert_eq!((8, 12), fuzz_range(10, 36500));
+        assert_eq!((93, 107), fuzz_range(100, 36500));
+        assert_eq!((93, 103), fuzz_range(100, 103));
+    }
+
+    #[test]
+    fn fsrs_elapsed_days() {
+        let settings = fsrs_settings();
+        let config = SchedulerConfig::default();
+        let state = SchedulerState::new(&settings);
+        let first = state.review(&settings, &config, RepQuality::Grade4, NOW);
+        assert_eq!(4, first.interval);
+
+        // Reviewing late increases the stability more than reviewing right away
+        let early = first
+            .state
+            .review(&settings, &config, RepQuality::Grade4, NOW);
+        let late = first.state.review(
+            &settings,
+            &config,
+            RepQuality::Grade4,
+            NOW + 4 * utils::DAY_SECS,
+        );
+        assert!(late.interval > early.interval);
+    }
+
+    #[test]
+    fn serialization() {
+        let settings = fsrs_settings();
+        let states = [
+            SchedulerState::SM2(SM2::new().set_repetitions(3).set_ease_factor(1.7)),
+            SchedulerState::new(&settings),
+            SchedulerState::new(&settings)
+                .review(
+                    &settings,
+                    &SchedulerConfig::default(),
+                    RepQuality::Grade5,
+                    NOW,
+                )
+                .state,
+        ];
+
+        for state in states {
+            let s = state.serialize();
+            assert_eq!(Some(state), SchedulerState::deserialize(state.kind(), &s));
+        }
+        assert!(SchedulerState::deserialize(SchedulerKind::SM2, "").is_none());
+    }
+
+    #[test]
+    fn convert() {
+        let config = SchedulerConfig::default();
+        let sm2 = SchedulerState::SM2(SM2::new().set_repetitions(3));
+        let fsrs = sm2.convert(&fsrs_settings(), &config, 15, NOW);
+        assert_eq!(SchedulerKind::FSRS, fsrs.kind());
+        assert_eq!(2.5, (fsrs.ease_factor() * 1000.0).round() / 1000.0);
+
+        let back = fsrs.convert(&Settings::default(), &config, 15, NOW);
+        assert_eq!(SchedulerKind::SM2, back.kind());
+
+        // Items which have never been reviewed stay new
+        let new = SchedulerState::default().convert(&fsrs_settings(), &config, 0, 0);
+        assert_eq!(SchedulerState::new(&fsrs_settings()), new);
+    }
+}
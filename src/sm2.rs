@@ -7,52 +7,51 @@
 //! # Inputs
 //!
 //! * Repetitions: The number of times the item has been
-//! successfully recalled in a row since the last time
-//! it was not.
+//!   successfully recalled in a row since the last time
+//!   it was not.
 //!
 //! * Interval: The inter-repetition interval, which is
-//! the number of days to wait before doing a review again
+//!   the number of days to wait before doing a review again
 //!
 //! * Ease factor: The easiness factor, which determines
-//! how quickly the inter-repetition interval grows
+//!   how quickly the inter-repetition interval grows
 //!
-//! * [`Quality`]: The quality of the response
+//! * [`RepQuality`]: The quality of the response
 //!
 //! # Examples
 //!
 //! Doing a review
 //!
 //! ```
-//! use sra::sm_2::{Quality, SM2};
+//! use kanji_srs::sm2::{RepQuality, SM2};
 //!
-//! let item = SM2::new().review(Quality::Grade4);
+//! let item = SM2::new().review(RepQuality::Grade4);
 //!
 //! assert_eq!(1, item.interval());
 //! ```
 //!
-//! Doing a review with a custom repetition, interval, and ease factor
+//! Doing a review with a custom repetition and ease factor
 //!
 //! ```
-//! use sra::sm_2::{Quality, SM2};
+//! use kanji_srs::sm2::{RepQuality, SM2};
 //!
 //! let item = SM2::new()
 //!     .set_repetitions(7)
-//!     .set_interval(12)
 //!     .set_ease_factor(2.0)
-//!     .review(Quality::Grade5);
+//!     .review(RepQuality::Grade5);
 //!
-//! assert_eq!(24, item.interval());
+//! assert_eq!(478, item.interval());
 //! ```
 //!
 //! Doing multiple reviews
 //!
 //! ```
-//! use sra::sm_2::{Quality, SM2};
+//! use kanji_srs::sm2::{RepQuality, SM2};
 //!
 //! let item = SM2::new()
 //!     .set_ease_factor(2.0)
-//!     .review(Quality::Grade5)
-//!     .review(Quality::Grade4);
+//!     .review(RepQuality::Grade5)
+//!     .review(RepQuality::Grade4);
 //!
 //! assert_eq!(6, item.interval());
 //! ```
@@ -60,7 +59,7 @@
 use serde::{Deserialize, Serialize};
 
 /// The quality of the response
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum RepQuality {
     /// perfect response
     Grade5,
@@ -77,8 +76,28 @@ pub enum RepQuality {
 }
 
 impl RepQuality {
+    /// Returns the grade as number from 0 (complete blackout) to 5 (perfect response)
     #[inline]
-    fn is_correct(&self) -> bool {
+    pub fn grade(&self) -> u8 {
+        5 - (*self as u8)
+    }
+
+    /// Returns the quality for a grade from 0 to 5. `None` if `grade` is out of range
+    pub fn from_grade(grade: u8) -> Option<Self> {
+        Some(match grade {
+            5 => RepQuality::Grade5,
+            4 => RepQuality::Grade4,
+            3 => RepQuality::Grade3,
+            2 => RepQuality::Grade2,
+            1 => RepQuality::Grade1,
+            0 => RepQuality::Grade0,
+            _ => return None,
+        })
+    }
+
+    /// Returns `true` if the grade counts as recalled
+    #[inline]
+    pub fn is_correct(&self) -> bool {
         matches!(
             self,
             RepQuality::Grade3 | RepQuality::Grade4 | RepQuality::Grade5
@@ -87,7 +106,7 @@ impl RepQuality {
 }
 
 /// An implementation of the SM-2 algorithm
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SM2 {
     /// The number of times the item has been successfully recalled
     repetitions: usize,
@@ -117,8 +136,8 @@ impl SM2 {
     /// Doing a review with a repetition of 10
     ///
     /// ```
-    /// use sra::sm_2::{Quality, SM2};
-    /// let item = SM2::new().set_repetitions(10).review(Quality::Grade4);
+    /// use kanji_srs::sm2::{RepQuality, SM2};
+    /// let item = SM2::new().set_repetitions(10).review(RepQuality::Grade4);
     /// ```
     pub fn set_repetitions(mut self, reviews: usize) -> Self {
         self.repetitions = reviews;
@@ -138,8 +157,8 @@ impl SM2 {
     /// Doing a review with an ease factor of 2.1
     ///
     /// ```
-    /// use sra::sm_2::{Quality, SM2};
-    /// let item = SM2::new().set_ease_factor(2.1).review(Quality::Grade4);
+    /// use kanji_srs::sm2::{RepQuality, SM2};
+    /// let item = SM2::new().set_ease_factor(2.1).review(RepQuality::Grade4);
     /// ```
     pub fn set_ease_factor(mut self, easiness: f64) -> Self {
         self.ease_factor = easiness.max(1.3);
@@ -151,15 +170,15 @@ impl SM2 {
     /// # Examples
     ///
     /// ```
-    /// use sra::sm_2::{Quality, SM2};
+    /// use kanji_srs::sm2::{RepQuality, SM2};
     ///
     /// let item = SM2::new()
     ///     .set_repetitions(3)
     ///     .set_ease_factor(2.0)
-    ///     .review(Quality::Grade3);
+    ///     .review(RepQuality::Grade3);
     ///
     /// assert_eq!(4, item.repetitions());
-    /// assert_eq!(10, item.interval());
+    /// assert_eq!(20, item.interval());
     /// assert_eq!(1.86, item.ease_factor());
     /// ```
     pub fn review(mut self, quality: RepQuality) -> Self {
@@ -168,6 +187,12 @@ impl SM2 {
         self
     }
 
+    /// Returns the interval in days resulting from the current repetitions and ease factor
+    #[inline]
+    pub fn interval(&self) -> usize {
+        self.calc_new_interval(self.repetitions.max(1))
+    }
+
     /// Calculates the new interval
     pub fn new_interval(&self, quality: &RepQuality) -> usize {
         if quality.is_correct() {
@@ -179,8 +204,8 @@ impl SM2 {
 
     fn calc_new_interval(&self, n: usize) -> usize {
         match n {
-            1 => return 1,
-            2 => return 6,
+            1 => 1,
+            2 => 6,
             _ => ((self.calc_new_interval(n - 1) as f64) * self.ease_factor) as usize,
         }
     }
@@ -255,6 +280,14 @@ mod tests {
         assert_review((1, 1, 1.3), (0, 0, 1.3, RepQuality::Grade3));
     }
 
+    #[test]
+    fn grades() {
+        for grade in 0..=5 {
+            assert_eq!(grade, RepQuality::from_grade(grade).unwrap().grade());
+        }
+        assert!(RepQuality::from_grade(6).is_none());
+    }
+
     #[test]
     fn incorrect_review() {
         assert_review((0, 1, 2.5), (3, 0, 2.5, RepQuality::Grade2));
@@ -0,0 +1,868 @@
+//! Import of kanji and their progress from Anki collections (`collection.anki2`) and deck
+//! packages (`.apkg`). The cards are converted into an [`Export`] so they are imported like an
+//! export of this tool
+
+use super::{
+    error::{Error, Result},
+    export::{Export, ExportedKanji, ExportedReview, EXPORT_VERSION},
+    srs::{self, LearningStep},
+};
+use crate::{japanese::JapaneseExt, scheduler::SchedulerState, sm2::SM2, time::DayBoundary};
+use rand::Rng;
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+/// Files in a deck package holding the collection, in the order they are looked for
+const PACKAGE_COLLECTIONS: [&str; 2] = ["collection.anki21", "collection.anki2"];
+
+/// Zstd compressed collection of packages written by newer versions of Anki
+const COMPRESSED_COLLECTION: &str = "collection.anki21b";
+
+/// Repetitions SM-2 is tried with to match the interval of a card
+const MAX_REPETITIONS: usize = 30;
+
+/// Prefix of the name of collections extracted from deck packages
+const TEMP_FILE_PREFIX: &str = "kanji_srs_anki_";
+
+/// Separator of the fields of a note
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// A deck of an Anki collection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deck {
+    pub id: i64,
+    /// Full name, subdecks separated by `::`
+    pub name: String,
+    /// Notes in the deck and its subdecks
+    pub notes: usize,
+}
+
+/// A field of the notes in a deck
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    /// Notes in which the field contains kanji
+    pub with_kanji: usize,
+}
+
+struct Note {
+    /// Id of the note type
+    mid: i64,
+    fields: Vec<String>,
+    leech: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Card {
+    id: i64,
+    nid: i64,
+    /// Deck the card belongs to. For cards in filtered decks the one they came from
+    did: i64,
+    /// 0 new, 1 learning, 2 review, 3 relearning
+    kind: i64,
+    /// -1 suspended, -2 and -3 buried, 0 new, 1 and 3 learning, 2 review
+    queue: i64,
+    /// Position of new cards, unix timestamp of cards in learning or days since the creation of
+    /// the collection
+    due: i64,
+    /// Interval in days. Negative for seconds of learning steps
+    ivl: i64,
+    /// Ease factor in permille
+    factor: i64,
+    lapses: i64,
+}
+
+impl Card {
+    /// Returns the queue of the card, for suspended and buried cards the one they return to. Like
+    /// Anki, cards in learning return into the intraday queue if they're due at a unix timestamp
+    fn restored_queue(&self) -> i64 {
+        match (self.queue, self.kind) {
+            (0.., _) => self.queue,
+            (_, 1 | 3) if self.due > 1_000_000_000 => 1,
+            (_, 1 | 3) => 3,
+            (_, kind) => kind,
+        }
+    }
+}
+
+/// An opened Anki collection
+pub struct AnkiCollection {
+    conn: Connection,
+    /// Unix timestamp of the day the collection has been created
+    crt: u64,
+    decks: Vec<(i64, String)>,
+    /// Field names of each note type by their order
+    note_types: HashMap<i64, Vec<String>>,
+    notes: HashMap<i64, Note>,
+    cards: Vec<Card>,
+    /// Collection extracted from a deck package, which gets removed on drop
+    extracted: Option<PathBuf>,
+}
+
+impl AnkiCollection {
+    /// Opens a collection file or the collection of a deck package
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let extracted = is_zip(path)?.then(|| extract(path)).transpose()?;
+        let collection = extracted.as_deref().unwrap_or(path);
+
+        let conn = Connection::open_with_flags(collection, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .and_then(Self::load);
+        match conn {
+            Ok(mut collection) => {
+                collection.extracted = extracted;
+                Ok(collection)
+            }
+            Err(err) => {
+                if let Some(extracted) = extracted {
+                    let _ = std::fs::remove_file(extracted);
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    fn load(conn: Connection) -> rusqlite::Result<Self> {
+        let crt: i64 = conn.query_row("SELECT crt FROM col", [], |row| row.get(0))?;
+        let split_schema: bool = conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'notetypes'",
+            [],
+            |row| row.get(0),
+        )?;
+        let (decks, note_types) = if split_schema {
+            load_split_schema(&conn)?
+        } else {
+            load_json_schema(&conn)?
+        };
+
+        let mut notes = HashMap::new();
+        {
+            let mut stmt = conn.prepare("SELECT id, mid, flds, tags FROM notes")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let fields: String = row.get(2)?;
+                let tags: String = row.get(3)?;
+                let note = Note {
+                    mid: row.get(1)?,
+                    fields: fields.split(FIELD_SEPARATOR).map(String::from).collect(),
+                    leech: tags
+                        .split_whitespace()
+                        .any(|i| i.eq_ignore_ascii_case("leech")),
+                };
+                notes.insert(row.get(0)?, note);
+            }
+        }
+
+        let mut cards = vec![];
+        {
+            let mut stmt = conn.prepare(
+                "SELECT id, nid, did, type, queue, due, ivl, factor, lapses, odid, odue
+                FROM cards ORDER BY id",
+            )?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let odid: i64 = row.get(9)?;
+                let odue: i64 = row.get(10)?;
+                let filtered = odid != 0;
+                cards.push(Card {
+                    id: row.get(0)?,
+                    nid: row.get(1)?,
+                    did: if filtered { odid } else { row.get(2)? },
+                    kind: row.get(3)?,
+                    queue: row.get(4)?,
+                    due: if filtered && odue != 0 {
+                        odue
+                    } else {
+                        row.get(5)?
+                    },
+                    ivl: row.get(6)?,
+                    factor: row.get(7)?,
+                    lapses: row.get(8)?,
+                });
+            }
+        }
+
+        Ok(Self {
+            conn,
+            crt: crt.max(0) as u64,
+            decks,
+            note_types,
+            notes,
+            cards,
+            extracted: None,
+        })
+    }
+
+    /// Returns all decks which have notes, ordered by their name
+    pub fn decks(&self) -> Vec<Deck> {
+        let mut decks = self
+            .decks
+            .iter()
+            .map(|(id, name)| {
+                let ids = self.deck_ids(name);
+                let notes = self
+                    .cards
+                    .iter()
+                    .filter(|i| ids.contains(&i.did))
+                    .map(|i| i.nid)
+                    .collect::<HashSet<_>>()
+                    .len();
+                Deck {
+                    id: *id,
+                    name: name.clone(),
+                    notes,
+                }
+            })
+            .filter(|i| i.notes > 0)
+            .collect::<Vec<_>>();
+        decks.sort_by(|a, b| a.name.cmp(&b.name));
+        decks
+    }
+
+    /// Returns the fields of the note types used in `deck`
+    pub fn fields(&self, deck: &Deck) -> Vec<Field> {
+        let mut fields: Vec<Field> = vec![];
+        for note in self.deck_notes(deck) {
+            let names = match self.note_types.get(&note.mid) {
+                Some(names) => names,
+                None => continue,
+            };
+
+            for (name, value) in names.iter().zip(note.fields.iter()) {
+                let pos = match fields.iter().position(|i| i.name == *name) {
+                    Some(pos) => pos,
+                    None => {
+                        fields.push(Field {
+                            name: name.clone(),
+                            with_kanji: 0,
+                        });
+                        fields.len() - 1
+                    }
+                };
+                if value.chars().any(|i| i.is_kanji()) {
+                    fields[pos].with_kanji += 1;
+                }
+            }
+        }
+        fields
+    }
+
+    /// Converts the cards of `deck` into an export. Each kanji in `field` becomes a kanji with
+    /// the progress of its card. If a kanji is on multiple cards the most progressed one is
+    /// taken. Learned kanji come first, new ones in the order Anki would introduce them. Due
+    /// dates are calculated with days starting at `boundary`
+    pub fn to_export(
+        &self,
+        deck: &Deck,
+        field: &str,
+        boundary: &DayBoundary,
+        now: u64,
+    ) -> Result<Export> {
+        let ids = self.deck_ids(&deck.name);
+        let mut best: HashMap<char, Card> = HashMap::new();
+        let mut has_field = false;
+        for card in self.cards.iter().filter(|i| ids.contains(&i.did)) {
+            let value = match self.field_value(card.nid, field) {
+                Some(value) => value,
+                None => continue,
+            };
+            has_field = true;
+
+            for literal in value.chars().filter(|i| i.is_kanji()) {
+                let better = best
+                    .get(&literal)
+                    .is_none_or(|i| progress(card) > progress(i));
+                if better {
+                    best.insert(literal, *card);
+                }
+            }
+        }
+
+        if !has_field {
+            return Err(Error::Invalid(format!(
+                "no notes with the field '{field}' in '{}'",
+                deck.name
+            )));
+        }
+
+        let mut cards = best.into_iter().collect::<Vec<_>>();
+        cards.sort_by_key(|(literal, card)| {
+            let position = if card.kind == 0 { card.due } else { card.nid };
+            (card.kind == 0, position, *literal)
+        });
+
+        let kanji = cards
+            .iter()
+            .map(|(literal, card)| {
+                let leech = self.notes.get(&card.nid).is_some_and(|i| i.leech);
+                let item = self.to_item(card, leech, now, boundary);
+                ExportedKanji::new(*literal, &item, boundary)
+            })
+            .collect();
+
+        let literals = cards
+            .iter()
+            .map(|(literal, card)| (card.id, *literal))
+            .collect::<HashMap<_, _>>();
+        let reviews = self.reviews(&literals)?;
+
+        Ok(Export {
+            version: EXPORT_VERSION,
+            kanji,
+            reviews,
+        })
+    }
+
+    /// Returns the SRS data of `card`. The SM-2 repetitions are picked so the next review
+    /// continues with about the interval Anki would have used
+    fn to_item(&self, card: &Card, leech: bool, now: u64, boundary: &DayBoundary) -> srs::Item {
+        let mut item = srs::Item::new(0);
+        item.leech = leech;
+        if card.queue == -1 {
+            item.suspended = true;
+            item.suspended_on = now;
+        }
+        if card.kind == 0 {
+            return item;
+        }
+
+        let ease = match card.factor {
+            0 => SM2::new().ease_factor(),
+            factor => factor as f64 / 1000.0,
+        };
+        let interval = card.ivl.max(1) as u32;
+        let sm2 = SM2::new().set_ease_factor(ease);
+        let repetitions = (1..MAX_REPETITIONS)
+            .find(|i| sm2.set_repetitions(*i).interval() >= interval as usize)
+            .unwrap_or(MAX_REPETITIONS);
+
+        item.state = SchedulerState::SM2(sm2.set_repetitions(repetitions));
+        item.in_learning = true;
+        item.interval = interval;
+        item.lapses = card.lapses.max(0) as u32;
+        // Cards in the intraday learning queue are due at a unix timestamp and continue with the
+        // steps, others are due on a day
+        if card.restored_queue() == 1 {
+            item.step = Some(LearningStep {
+                index: 0,
+                relearning: card.kind == 3,
+            });
+            item.due_on = card.due.max(0) as u64;
+        } else {
+            item.due_on = boundary.offset(self.crt, card.due.max(0) as u32);
+        }
+        item
+    }
+
+    /// Returns the reviews of the cards in `literals`, which maps card ids to the kanji they have
+    /// been picked for
+    fn reviews(&self, literals: &HashMap<i64, char>) -> rusqlite::Result<Vec<ExportedReview>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, cid, ease, ivl, lastIvl, factor, type FROM revlog ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+
+        let mut reviews = vec![];
+        let mut eases = HashMap::new();
+        while let Some(row) = rows.next()? {
+            let cid: i64 = row.get(1)?;
+            let literal = match literals.get(&cid) {
+                Some(literal) => *literal,
+                None => continue,
+            };
+
+            // Type 4 are manual reschedules, which aren't reviews
+            let (answer, kind): (i64, i64) = (row.get(2)?, row.get(6)?);
+            let grade = match answer {
+                1 => 1,
+                2 => 3,
+                3 => 4,
+                4 => 5,
+                _ => continue,
+            };
+            if kind == 4 {
+                continue;
+            }
+
+            let factor: i64 = row.get(5)?;
+            let ease_before = eases.get(&cid).copied().unwrap_or(SM2::new().ease_factor());
+            let ease_after = if factor > 0 {
+                factor as f64 / 1000.0
+            } else {
+                ease_before
+            };
+            eases.insert(cid, ease_after);
+
+            // Anki ids are the time of the review in milliseconds
+            let id: i64 = row.get(0)?;
+            let timestamp = (id / 1000).max(0) as u64;
+            let (ivl, last_ivl): (i64, i64) = (row.get(3)?, row.get(4)?);
+            reviews.push(ExportedReview {
+                literal,
+                timestamp,
+                grade,
+                prev_interval: last_ivl.max(0) as u32,
+                new_interval: ivl.max(0) as u32,
+                ease_before,
+                ease_after,
+                session: timestamp,
+            });
+        }
+        Ok(reviews)
+    }
+
+    /// Returns the ids of the deck `name` and its subdecks
+    fn deck_ids(&self, name: &str) -> HashSet<i64> {
+        let prefix = format!("{name}::");
+        self.decks
+            .iter()
+            .filter(|(_, i)| i == name || i.starts_with(&prefix))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn deck_notes(&self, deck: &Deck) -> impl Iterator<Item = &Note> {
+        let ids = self.deck_ids(&deck.name);
+        self.cards
+            .iter()
+            .filter(|i| ids.contains(&i.did))
+            .map(|i| i.nid)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|i| self.notes.get(&i))
+    }
+
+    /// Returns the value of the field `name` of a note. `None` if its note type has no such field
+    fn field_value(&self, nid: i64, name: &str) -> Option<&str> {
+        let note = self.notes.get(&nid)?;
+        let pos = self
+            .note_types
+            .get(&note.mid)?
+            .iter()
+            .position(|i| i == name)?;
+        note.fields.get(pos).map(|i| i.as_str())
+    }
+}
+
+impl Drop for AnkiCollection {
+    fn drop(&mut self) {
+        if let Some(extracted) = self.extracted.take() {
+            let _ = std::fs::remove_file(extracted);
+        }
+    }
+}
+
+/// Returns how far a card has been learned. Cards being learned come before new ones, longer
+/// intervals before shorter ones
+#[inline]
+fn progress(card: &Card) -> (bool, i64, i64) {
+    (card.kind != 0, card.ivl, card.due)
+}
+
+/// Decks and note types of collections up to schema 11 which keep them as JSON in `col`
+type Schema = (Vec<(i64, String)>, HashMap<i64, Vec<String>>);
+
+fn load_json_schema(conn: &Connection) -> rusqlite::Result<Schema> {
+    let (decks, models): (String, String) =
+        conn.query_row("SELECT decks, models FROM col", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    let invalid = |err| rusqlite::Error::ToSqlConversionFailure(Box::new(err));
+    let decks: HashMap<String, serde_json::Value> =
+        serde_json::from_str(&decks).map_err(invalid)?;
+    let models: HashMap<String, serde_json::Value> =
+        serde_json::from_str(&models).map_err(invalid)?;
+
+    let decks = decks
+        .into_iter()
+        .filter_map(|(id, deck)| Some((id.parse().ok()?, deck["name"].as_str()?.to_string())))
+        .collect();
+
+    let note_types = models
+        .into_iter()
+        .filter_map(|(id, model)| {
+            let mut fields = model["flds"]
+                .as_array()?
+                .iter()
+                .filter_map(|i| Some((i["ord"].as_i64()?, i["name"].as_str()?.to_string())))
+                .collect::<Vec<_>>();
+            fields.sort();
+            Some((id.parse().ok()?, fields.into_iter().map(|i| i.1).collect()))
+        })
+        .collect();
+
+    Ok((decks, note_types))
+}
+
+/// Decks and note types of collections from schema 18 on which keep them in their own tables
+fn load_split_schema(conn: &Connection) -> rusqlite::Result<Schema> {
+    let mut stmt = conn.prepare("SELECT id, name FROM decks")?;
+    let decks = stmt
+        .query_map([], |row| {
+            let name: String = row.get(1)?;
+            Ok((row.get(0)?, name.replace(FIELD_SEPARATOR, "::")))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut note_types: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT ntid, name FROM fields ORDER BY ntid, ord")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        note_types.entry(row.get(0)?).or_default().push(row.get(1)?);
+    }
+
+    Ok((decks, note_types))
+}
+
+/// Returns `true` if the file at `path` is a zip file
+fn is_zip(path: &Path) -> Result<bool> {
+    let mut magic = [0; 4];
+    let read = File::open(path)?.read(&mut magic)?;
+    Ok(read == 4 && magic == *b"PK\x03\x04")
+}
+
+/// Extracts the collection of a deck package into a temporary file and returns its path
+fn extract(path: &Path) -> Result<PathBuf> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    // Packages of newer versions only have a placeholder in `collection.anki2`
+    if archive.by_name(COMPRESSED_COLLECTION).is_ok()
+        && archive.by_name(PACKAGE_COLLECTIONS[0]).is_err()
+    {
+        return Err(Error::Invalid(
+            "the deck package is compressed, export it in Anki with \"Support older Anki versions\" checked".to_string(),
+        ));
+    }
+    let name = PACKAGE_COLLECTIONS
+        .iter()
+        .copied()
+        .find(|i| archive.by_name(i).is_ok())
+        .ok_or_else(|| Error::Invalid("no collection in the deck package".to_string()))?;
+
+    let (target, mut file) = create_temp_file()?;
+    let copied = archive
+        .by_name(name)
+        .map_err(Error::from)
+        .and_then(|mut collection| Ok(io::copy(&mut collection, &mut file)?));
+    if let Err(err) = copied {
+        let _ = std::fs::remove_file(&target);
+        return Err(err);
+    }
+    Ok(target)
+}
+
+/// Creates a new file with a random name in the temporary directory. Existing files are never
+/// opened, so a file or symlink placed there by someone else can't be overwritten
+fn create_temp_file() -> io::Result<(PathBuf, File)> {
+    let mut rng = rand::thread_rng();
+    loop {
+        let name = format!("{}{:016x}.anki2", TEMP_FILE_PREFIX, rng.gen::<u64>());
+        let path = std::env::temp_dir().join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        storage::{export::ImportPolicy, Storage},
+        time::FixedClock,
+    };
+    use chrono::NaiveDate;
+    use chrono_tz::Europe::Berlin;
+    use rusqlite::params;
+    use std::{io::Write, rc::Rc};
+
+    #[test]
+    fn item_of_review_card() {
+        let card = Card {
+            id: 1,
+            nid: 1,
+            did: 1,
+            kind: 2,
+            queue: 2,
+            due: 10,
+            ivl: 100,
+            factor: 2500,
+            lapses: 2,
+        };
+        let collection = AnkiCollection {
+            conn: Connection::open_in_memory().unwrap(),
+            crt: 1_600_000_000,
+            decks: vec![],
+            note_types: HashMap::new(),
+            notes: HashMap::new(),
+            cards: vec![card],
+            extracted: None,
+        };
+        let boundary = DayBoundary::LEGACY;
+
+        let item = collection.to_item(&card, false, 0, &boundary);
+        assert!(item.in_learning);
+        assert_eq!(100, item.interval);
+        assert_eq!(2, item.lapses);
+        assert_eq!(boundary.offset(1_600_000_000, 10), item.due_on);
+        // The next review continues with a longer interval instead of starting over
+        match item.state {
+            SchedulerState::SM2(sm2) => {
+                assert_eq!(2.5, sm2.ease_factor());
+                assert!(sm2.interval() >= 100);
+                assert!(sm2.set_repetitions(sm2.repetitions() - 1).interval() < 100);
+            }
+            state => panic!("unexpected state {state:?}"),
+        }
+
+        let new = Card {
+            kind: 0,
+            queue: -1,
+            ..card
+        };
+        let item = collection.to_item(&new, true, 5, &boundary);
+        assert!(!item.in_learning && item.suspended && item.leech);
+        assert_eq!(5, item.suspended_on);
+    }
+
+    #[test]
+    fn item_of_learning_card() {
+        // Relearning after a lapse, due at 2020-09-14 13:00 UTC
+        let card = Card {
+            id: 1,
+            nid: 1,
+            did: 1,
+            kind: 3,
+            queue: 1,
+            due: 1_600_088_400,
+            ivl: 1,
+            factor: 2300,
+            lapses: 1,
+        };
+        let collection = AnkiCollection {
+            conn: Connection::open_in_memory().unwrap(),
+            crt: 1_600_000_000,
+            decks: vec![],
+            note_types: HashMap::new(),
+            notes: HashMap::new(),
+            cards: vec![card],
+            extracted: None,
+        };
+        let boundary = DayBoundary::LEGACY;
+
+        let item = collection.to_item(&card, false, 0, &boundary);
+        let relearning = LearningStep {
+            index: 0,
+            relearning: true,
+        };
+        assert_eq!(Some(relearning), item.step);
+        assert_eq!(1_600_088_400, item.due_on);
+
+        // Suspended cards return into the queue they have been in
+        let suspended = Card { queue: -1, ..card };
+        let item = collection.to_item(&suspended, false, 0, &boundary);
+        assert!(item.suspended);
+        assert_eq!(Some(relearning), item.step);
+        assert_eq!(1_600_088_400, item.due_on);
+
+        // Learning steps of a day or longer are due on a day
+        let day_learning = Card {
+            queue: 3,
+            due: 2,
+            ..card
+        };
+        let item = collection.to_item(&day_learning, false, 0, &boundary);
+        assert_eq!(None, item.step);
+        assert_eq!(boundary.offset(1_600_000_000, 2), item.due_on);
+    }
+
+    /// 2022-03-10 12:00 in Berlin
+    const NOW: u64 = 1_646_910_000;
+
+    /// 2022-03-01 04:00 in Berlin, when the collection has been created
+    const CRT: i64 = 1_646_103_600;
+
+    /// Writes a collection in the format of Anki 2.1 up to schema 11. `Japanese` has 日 in review
+    /// in a subdeck, 月 new, a suspended leech 火 and a new note 日本. 水 is in `Default`
+    fn write_collection(path: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE col (id INTEGER PRIMARY KEY, crt INTEGER, models TEXT, decks TEXT);
+            CREATE TABLE notes (id INTEGER PRIMARY KEY, mid INTEGER, flds TEXT, tags TEXT);
+            CREATE TABLE cards (id INTEGER PRIMARY KEY, nid INTEGER, did INTEGER, type INTEGER,
+                queue INTEGER, due INTEGER, ivl INTEGER, factor INTEGER, lapses INTEGER,
+                odue INTEGER, odid INTEGER);
+            CREATE TABLE revlog (id INTEGER PRIMARY KEY, cid INTEGER, ease INTEGER, ivl INTEGER,
+                lastIvl INTEGER, factor INTEGER, type INTEGER);",
+        )
+        .unwrap();
+
+        let models = r#"{"10": {"name": "Kanji", "flds": [
+            {"name": "Meaning", "ord": 1}, {"name": "Kanji", "ord": 0}
+        ]}}"#;
+        let decks = r#"{"1": {"name": "Default"}, "2": {"name": "Japanese"},
+            "3": {"name": "Japanese::Kanji"}}"#;
+        conn.execute(
+            "INSERT INTO col VALUES (1, ?, ?, ?)",
+            params![CRT, models, decks],
+        )
+        .unwrap();
+
+        let notes = [
+            (1, "日\x1fsun", ""),
+            (2, "月\x1fmoon", ""),
+            (3, "火\x1ffire", " leech "),
+            (4, "水\x1fwater", ""),
+            (5, "日本\x1fJapan", ""),
+        ];
+        for (id, fields, tags) in notes {
+            conn.execute(
+                "INSERT INTO notes VALUES (?, 10, ?, ?)",
+                params![id, fields, tags],
+            )
+            .unwrap();
+        }
+
+        // id, nid, did, type, queue, due, ivl, factor, lapses
+        let cards = [
+            (101, 1, 3, 2, 2, 15, 6, 2300, 1),
+            (102, 2, 2, 0, 0, 2, 0, 0, 0),
+            (103, 3, 2, 2, -1, 20, 30, 2500, 8),
+            (104, 4, 1, 0, 0, 1, 0, 0, 0),
+            (105, 5, 2, 0, 0, 5, 0, 0, 0),
+        ];
+        for card in cards {
+            conn.execute(
+                "INSERT INTO cards VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 0, 0)",
+                params![card.0, card.1, card.2, card.3, card.4, card.5, card.6, card.7, card.8],
+            )
+            .unwrap();
+        }
+
+        // Two reviews of 日 and a reschedule which isn't a review
+        let reviews = [
+            (1_646_200_000_000_i64, 3, 1, 0, 2500, 0),
+            (1_646_300_000_000, 3, 6, 1, 2300, 1),
+            (1_646_400_000_000, 0, 6, 6, 2300, 4),
+        ];
+        for review in reviews {
+            conn.execute(
+                "INSERT INTO revlog VALUES (?, 101, ?, ?, ?, ?, ?)",
+                params![review.0, review.1, review.2, review.3, review.4, review.5],
+            )
+            .unwrap();
+        }
+    }
+
+    fn due_of(storage: &Storage, literal: char) -> u64 {
+        let id = storage.get_by_lit(literal).unwrap().get_id();
+        storage.get_srs_storage().find(id).unwrap().due_on
+    }
+
+    #[test]
+    fn import_collection() {
+        let path = std::env::temp_dir().join(format!("kanji_srs_col_{}.anki2", std::process::id()));
+        write_collection(&path);
+        let collection = AnkiCollection::open(&path).unwrap();
+        let boundary = DayBoundary::new(Berlin, 4);
+
+        let decks = collection.decks();
+        let names = decks.iter().map(|i| i.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["Default", "Japanese", "Japanese::Kanji"], names);
+        let deck = &decks[1];
+        assert_eq!(4, deck.notes);
+
+        let fields = collection.fields(deck);
+        let kanji = Field {
+            name: "Kanji".to_string(),
+            with_kanji: 4,
+        };
+        assert_eq!(kanji, fields[0]);
+        assert_eq!(0, fields[1].with_kanji);
+        assert!(collection
+            .to_export(deck, "Reading", &boundary, NOW)
+            .is_err());
+
+        let mut storage = Storage::in_memory();
+        storage.set_clock(Rc::new(FixedClock::new(NOW)));
+        storage.set_day_boundary(boundary);
+        let export = collection.to_export(deck, "Kanji", &boundary, NOW).unwrap();
+        let summary = storage.import(&export, ImportPolicy::Skip).unwrap();
+        assert_eq!((4, 2), (summary.added, summary.reviews));
+
+        // Learned kanji come first, new ones in the order Anki would show them
+        let ids = "日火月本"
+            .chars()
+            .map(|i| storage.get_by_lit(i).unwrap().get_id())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 2, 3, 4], ids);
+
+        let sun = storage.get_by_lit('日').unwrap();
+        let item = storage.get_srs_storage().find(sun.get_id()).unwrap();
+        assert!(item.in_learning);
+        assert_eq!((6, 1), (item.interval, item.lapses));
+        assert_eq!(2.3, item.state.ease_factor());
+        let due_on = NaiveDate::from_ymd(2022, 3, 16);
+        assert_eq!(boundary.start_of(due_on), due_of(&storage, '日'));
+
+        let fire = storage.get_by_lit('火').unwrap();
+        let item = storage.get_srs_storage().find(fire.get_id()).unwrap();
+        assert!(item.suspended && item.leech);
+        assert_eq!(30, item.interval);
+
+        assert_eq!(0, due_of(&storage, '月'));
+        assert!(storage.get_by_lit('水').is_none());
+
+        drop(collection);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn import_package() {
+        let dir = std::env::temp_dir();
+        let collection = dir.join(format!("kanji_srs_pkg_{}.anki2", std::process::id()));
+        let package = dir.join(format!("kanji_srs_pkg_{}.apkg", std::process::id()));
+        write_collection(&collection);
+
+        let mut zip = zip::ZipWriter::new(File::create(&package).unwrap());
+        zip.start_file("collection.anki2", Default::default())
+            .unwrap();
+        zip.write_all(&std::fs::read(&collection).unwrap()).unwrap();
+        zip.start_file("media", Default::default()).unwrap();
+        zip.write_all(b"{}").unwrap();
+        zip.finish().unwrap();
+
+        let anki = AnkiCollection::open(&package).unwrap();
+        let boundary = DayBoundary::new(Berlin, 4);
+        let deck = anki.decks().remove(2);
+        assert_eq!("Japanese::Kanji", deck.name);
+        let export = anki.to_export(&deck, "Kanji", &boundary, NOW).unwrap();
+        let literals = export.kanji.iter().map(|i| i.literal).collect::<String>();
+        assert_eq!("日", literals);
+        assert_eq!(2, export.reviews.len());
+
+        // The extracted collection is removed along with the collection
+        assert_eq!(1, extracted_collections(&dir));
+        drop(anki);
+        assert_eq!(0, extracted_collections(&dir));
+
+        std::fs::remove_file(&collection).unwrap();
+        std::fs::remove_file(&package).unwrap();
+    }
+
+    fn extracted_collections(dir: &Path) -> usize {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|i| {
+                let name = i.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(TEMP_FILE_PREFIX)
+            })
+            .count()
+    }
+}
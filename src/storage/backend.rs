@@ -0,0 +1,167 @@
+use super::{
+    error::Result, info::InfoCache, item, log::ReviewLog, merge::Snapshot, settings::Settings, srs,
+    ItemStorage, SRSStorage,
+};
+use std::fmt::Debug;
+
+/// A place where items and their SRS data are persisted. Backends keep the data in memory
+/// and only touch their underlying store on `load` and `save`
+pub trait StorageBackend: Debug {
+    /// (Re)loads all data from the underlying store, replacing the data in memory
+    fn load(&mut self) -> Result<()>;
+
+    /// Writes all data to the underlying store
+    fn save(&mut self) -> Result<()>;
+
+    /// Returns `true` if the underlying store has been written by someone else since this
+    /// backend has loaded or saved it the last time
+    #[inline]
+    fn changed(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns a reference to the item storage
+    fn items(&self) -> &ItemStorage;
+
+    /// Returns a mutable reference to the item storage
+    fn items_mut(&mut self) -> &mut ItemStorage;
+
+    /// Returns a reference to the srs storage
+    fn srs(&self) -> &SRSStorage;
+
+    /// Returns a mutable reference to the srs storage
+    fn srs_mut(&mut self) -> &mut SRSStorage;
+
+    /// Returns a reference to the review log
+    fn review_log(&self) -> &ReviewLog;
+
+    /// Returns a mutable reference to the review log. Backends may assume that entries only
+    /// get appended or marked as undone
+    fn review_log_mut(&mut self) -> &mut ReviewLog;
+
+    /// Returns a reference to the settings stored along with the data
+    fn settings(&self) -> &Settings;
+
+    /// Returns a mutable reference to the settings stored along with the data
+    fn settings_mut(&mut self) -> &mut Settings;
+
+    /// Returns a reference to the cached information about kanji
+    fn info_cache(&self) -> &InfoCache;
+
+    /// Returns a mutable reference to the cached information about kanji
+    fn info_cache_mut(&mut self) -> &mut InfoCache;
+
+    /// Replaces all data in memory with `data`. It gets written on the next `save`
+    fn set_data(&mut self, data: Snapshot) {
+        *self.items_mut() = data.items;
+        *self.srs_mut() = data.srs;
+        *self.review_log_mut() = data.review_log;
+        *self.settings_mut() = data.settings;
+        *self.info_cache_mut() = data.info;
+    }
+
+    /// Adds a new item along with fresh SRS data. Returns the id of the new item or `None` if
+    /// the literal is already in the storage
+    fn add_item(&mut self, literal: char) -> Option<u32> {
+        let id = self.items_mut().add_item(literal)?.id;
+        self.srs_mut().add(id);
+        Some(id)
+    }
+
+    /// Removes an item and its SRS data. Returns `false` if there was no such item
+    fn remove_item(&mut self, id: u32) -> bool {
+        self.items_mut().remove_item(id) && self.srs_mut().remove(id).is_some()
+    }
+
+    /// Returns an item by its id
+    #[inline]
+    fn get_item(&self, id: u32) -> Option<&item::Item> {
+        self.items().item_by_id(id)
+    }
+
+    /// Returns an iterator over the SRS data of all items
+    #[inline]
+    fn iter_srs(&self) -> Box<dyn Iterator<Item = &srs::Item> + '_> {
+        Box::new(self.srs().iter())
+    }
+}
+
+/// Backend which doesn't persist anything. Useful for tests
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    items: ItemStorage,
+    srs: SRSStorage,
+    review_log: ReviewLog,
+    settings: Settings,
+    info: InfoCache,
+}
+
+impl MemoryBackend {
+    /// Creates a new, empty in-memory backend
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    #[inline]
+    fn load(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn save(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn items(&self) -> &ItemStorage {
+        &self.items
+    }
+
+    #[inline]
+    fn items_mut(&mut self) -> &mut ItemStorage {
+        &mut self.items
+    }
+
+    #[inline]
+    fn srs(&self) -> &SRSStorage {
+        &self.srs
+    }
+
+    #[inline]
+    fn srs_mut(&mut self) -> &mut SRSStorage {
+        &mut self.srs
+    }
+
+    #[inline]
+    fn review_log(&self) -> &ReviewLog {
+        &self.review_log
+    }
+
+    #[inline]
+    fn review_log_mut(&mut self) -> &mut ReviewLog {
+        &mut self.review_log
+    }
+
+    #[inline]
+    fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    #[inline]
+    fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    #[inline]
+    fn info_cache(&self) -> &InfoCache {
+        &self.info
+    }
+
+    #[inline]
+    fn info_cache_mut(&mut self) -> &mut InfoCache {
+        &mut self.info
+    }
+}
@@ -0,0 +1,531 @@
+//! Backend storing items and SRS data in two separate bincode files (`item_storage` and
+//! `srs_storage`). This is the format the storage used before it has been moved into SQLite.
+//! Data which didn't exist back then is kept in additional files (`srs_ext` and `review_log`)
+
+use super::{
+    backend::StorageBackend,
+    error::{Error, Result},
+    info::InfoCache,
+    item,
+    log::ReviewLog,
+    settings::Settings,
+    srs::{self, LearningStep},
+    ItemStorage, SRSStorage,
+};
+use crate::{config::SchedulerConfig, scheduler::SchedulerState, sm2::SM2};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{copy, metadata, rename, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// File name of the item storage
+const ITEM_FILE: &str = "item_storage";
+
+/// File name of the SRS storage
+const SRS_FILE: &str = "srs_storage";
+
+/// File name of SRS data not contained in the SRS storage
+const EXT_FILE: &str = "srs_ext";
+
+/// File name of the review log
+const LOG_FILE: &str = "review_log";
+
+/// Version of the layout of the ext file
+const EXT_VERSION: u32 = 1;
+
+/// All files used by the backend
+const FILES: [&str; 4] = [ITEM_FILE, SRS_FILE, EXT_FILE, LOG_FILE];
+
+/// Extension appended to the files after they have been migrated into another backend
+const MIGRATED_EXT: &str = "migrated";
+
+/// Storage backend using bincode files
+#[derive(Debug)]
+pub struct BincodeBackend {
+    dir: PathBuf,
+    items: ItemStorage,
+    srs: SRSStorage,
+    review_log: ReviewLog,
+    settings: Settings,
+    /// Kept in memory only. Kanji information is cached since the database has been moved into
+    /// SQLite
+    info: InfoCache,
+    /// Modification times of all files when they were loaded or saved the last time
+    modified: Vec<Option<SystemTime>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ItemFile {
+    file: String,
+    items: Vec<FileItem>,
+    last_id: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileItem {
+    id: u32,
+    literal: char,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SRSFile {
+    file: String,
+    data: HashMap<u32, FileSRSItem>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileSRSItem {
+    id: u32,
+    srs_data: FileSM2,
+    due_on: u64,
+    in_learning: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileSM2 {
+    repetitions: usize,
+    ease_factor: f64,
+}
+
+/// SRS data which doesn't fit into the SRS storage. Files written by the original version of
+/// the backend don't have it
+#[derive(Deserialize, Serialize, Default)]
+struct ExtFile {
+    /// Always [`EXT_VERSION`]. Comes first so it can be read even if the rest changed
+    version: u32,
+    settings: Settings,
+    data: HashMap<u32, FileSRSExt>,
+    /// Learning steps of the items which are in one
+    steps: HashMap<u32, LearningStep>,
+    /// Lapses and leech state of the items which have lapsed at least once
+    lapses: HashMap<u32, FileLapses>,
+    /// Items which have been suspended or buried
+    suspensions: HashMap<u32, FileSuspension>,
+}
+
+/// Suspended or buried state of an item
+#[derive(Deserialize, Serialize)]
+struct FileSuspension {
+    suspended_on: u64,
+    buried_until: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileLapses {
+    lapses: u32,
+    leech: bool,
+    suspended: bool,
+    drill: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FileSRSExt {
+    interval: u32,
+    /// `None` if the state is the SM-2 data stored in the SRS storage
+    state: Option<SchedulerState>,
+}
+
+impl BincodeBackend {
+    /// Opens the bincode files within `dir`. Missing files are treated as empty storage
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut backend = Self {
+            dir: dir.as_ref().to_path_buf(),
+            items: ItemStorage::new(),
+            srs: SRSStorage::new(),
+            review_log: ReviewLog::new(),
+            settings: Settings::default(),
+            info: InfoCache::new(),
+            modified: vec![],
+        };
+        backend.load()?;
+        Ok(backend)
+    }
+
+    /// Returns `true` if there are bincode files in `dir`
+    pub fn exists<P: AsRef<Path>>(dir: P) -> bool {
+        dir.as_ref().join(ITEM_FILE).exists()
+    }
+
+    /// Renames the files so they won't be picked up again after their data has been moved
+    /// into another backend
+    pub fn mark_migrated(&self) -> Result<()> {
+        for file in FILES {
+            let path = self.dir.join(file);
+            if path.exists() {
+                rename(&path, path.with_extension(MIGRATED_EXT))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        FILES
+            .iter()
+            .map(|file| {
+                metadata(self.dir.join(file))
+                    .and_then(|i| i.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Copies `file` to `backup` and atomically replaces `file` with the serialized `data`
+    fn write_file<T: Serialize + for<'de> Deserialize<'de>>(
+        file: &Path,
+        backup: &str,
+        data: &T,
+    ) -> Result<()> {
+        if file.exists() {
+            copy(file, file.with_file_name(backup))?;
+        }
+
+        let tmp = file.with_extension("new");
+        bincode::serialize_into(File::create(&tmp)?, data)?;
+
+        // Check that the written file can be read again
+        read_file::<T, _>(&tmp)?;
+
+        rename(tmp, file)?;
+        Ok(())
+    }
+}
+
+impl StorageBackend for BincodeBackend {
+    fn load(&mut self) -> Result<()> {
+        let item_file = self.dir.join(ITEM_FILE);
+        self.items = if item_file.exists() {
+            let file: ItemFile = read_file(item_file)?;
+            let items = file
+                .items
+                .into_iter()
+                .map(|i| item::Item {
+                    id: i.id,
+                    literal: i.literal,
+                })
+                .collect();
+            ItemStorage::from_items(items, file.last_id)
+        } else {
+            ItemStorage::new()
+        };
+
+        let ext_file = self.dir.join(EXT_FILE);
+        let mut ext = if ext_file.exists() {
+            read_ext_file(&ext_file)?
+        } else {
+            ExtFile::default()
+        };
+        self.settings = ext.settings;
+
+        let ext_steps = std::mem::take(&mut ext.steps);
+        let ext_lapses = std::mem::take(&mut ext.lapses);
+        let ext_suspensions = std::mem::take(&mut ext.suspensions);
+
+        // SRS storage only gets created on first save and might be missing
+        let srs_file = self.dir.join(SRS_FILE);
+        self.srs = if srs_file.exists() {
+            let file: SRSFile = read_file(srs_file)?;
+            SRSStorage::from_items(file.data.into_values().map(|i| {
+                let srs_data = SM2::new()
+                    .set_repetitions(i.srs_data.repetitions)
+                    .set_ease_factor(i.srs_data.ease_factor);
+
+                let ext = ext.data.remove(&i.id).unwrap_or_else(|| FileSRSExt {
+                    // Files written by old versions don't contain the interval
+                    interval: if i.in_learning {
+                        srs_data.interval() as u32
+                    } else {
+                        0
+                    },
+                    state: None,
+                });
+
+                let lapses = ext_lapses.get(&i.id);
+                let suspension = ext_suspensions.get(&i.id);
+                srs::Item {
+                    id: i.id,
+                    state: ext.state.unwrap_or(SchedulerState::SM2(srs_data)),
+                    due_on: i.due_on,
+                    in_learning: i.in_learning,
+                    interval: ext.interval,
+                    step: ext_steps.get(&i.id).copied(),
+                    lapses: lapses.map_or(0, |i| i.lapses),
+                    leech: lapses.is_some_and(|i| i.leech),
+                    suspended: lapses.is_some_and(|i| i.suspended),
+                    drill: lapses.is_some_and(|i| i.drill),
+                    suspended_on: suspension.map_or(0, |i| i.suspended_on),
+                    buried_until: suspension.map_or(0, |i| i.buried_until),
+                }
+            }))
+        } else {
+            SRSStorage::new()
+        };
+
+        let log_file = self.dir.join(LOG_FILE);
+        self.review_log = if log_file.exists() {
+            read_file(&log_file)?
+        } else {
+            ReviewLog::new()
+        };
+
+        self.modified = self.modification_times();
+        Ok(())
+    }
+
+    fn save(&mut self) -> Result<()> {
+        let item_file = self.dir.join(ITEM_FILE);
+        let items = ItemFile {
+            file: item_file.display().to_string(),
+            items: self
+                .items
+                .iter()
+                .map(|i| FileItem {
+                    id: i.id,
+                    literal: i.literal,
+                })
+                .collect(),
+            last_id: self.items.last_id(),
+        };
+        Self::write_file(&item_file, "item_backup", &items)?;
+
+        let srs_file = self.dir.join(SRS_FILE);
+        let srs = SRSFile {
+            file: srs_file.display().to_string(),
+            data: self
+                .srs
+                .iter()
+                .map(|i| {
+                    // Items scheduled with another algorithm get approximated SM-2 data
+                    let sm2 = match i.state.convert(
+                        &Settings::default(),
+                        &SchedulerConfig::default(),
+                        i.interval,
+                        0,
+                    ) {
+                        SchedulerState::SM2(sm2) => sm2,
+                        _ => SM2::new(),
+                    };
+                    let srs_data = FileSM2 {
+                        repetitions: sm2.repetitions(),
+                        ease_factor: sm2.ease_factor(),
+                    };
+                    let item = FileSRSItem {
+                        id: i.id,
+                        srs_data,
+                        due_on: i.due_on,
+                        in_learning: i.in_learning,
+                    };
+                    (i.id, item)
+                })
+                .collect(),
+        };
+        Self::write_file(&srs_file, "srs_backup", &srs)?;
+
+        let ext = ExtFile {
+            version: EXT_VERSION,
+            settings: self.settings,
+            data: self
+                .srs
+                .iter()
+                .map(|i| {
+                    let ext = FileSRSExt {
+                        interval: i.interval,
+                        state: (!matches!(i.state, SchedulerState::SM2(_))).then_some(i.state),
+                    };
+                    (i.id, ext)
+                })
+                .collect(),
+            steps: self
+                .srs
+                .iter()
+                .filter_map(|i| Some((i.id, i.step?)))
+                .collect(),
+            lapses: self
+                .srs
+                .iter()
+                .filter(|i| i.lapses > 0 || i.suspended)
+                .map(|i| {
+                    let lapses = FileLapses {
+                        lapses: i.lapses,
+                        leech: i.leech,
+                        suspended: i.suspended,
+                        drill: i.drill,
+                    };
+                    (i.id, lapses)
+                })
+                .collect(),
+            suspensions: self
+                .srs
+                .iter()
+                .filter(|i| i.suspended_on > 0 || i.buried_until > 0)
+                .map(|i| {
+                    let suspension = FileSuspension {
+                        suspended_on: i.suspended_on,
+                        buried_until: i.buried_until,
+                    };
+                    (i.id, suspension)
+                })
+                .collect(),
+        };
+        Self::write_file(&self.dir.join(EXT_FILE), "ext_backup", &ext)?;
+
+        let log_file = self.dir.join(LOG_FILE);
+        Self::write_file(&log_file, "log_backup", &self.review_log)?;
+
+        self.modified = self.modification_times();
+        Ok(())
+    }
+
+    #[inline]
+    fn changed(&self) -> Result<bool> {
+        Ok(self.modification_times() != self.modified)
+    }
+
+    #[inline]
+    fn items(&self) -> &ItemStorage {
+        &self.items
+    }
+
+    #[inline]
+    fn items_mut(&mut self) -> &mut ItemStorage {
+        &mut self.items
+    }
+
+    #[inline]
+    fn srs(&self) -> &SRSStorage {
+        &self.srs
+    }
+
+    #[inline]
+    fn srs_mut(&mut self) -> &mut SRSStorage {
+        &mut self.srs
+    }
+
+    #[inline]
+    fn review_log(&self) -> &ReviewLog {
+        &self.review_log
+    }
+
+    #[inline]
+    fn review_log_mut(&mut self) -> &mut ReviewLog {
+        &mut self.review_log
+    }
+
+    #[inline]
+    fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    #[inline]
+    fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    #[inline]
+    fn info_cache(&self) -> &InfoCache {
+        &self.info
+    }
+
+    #[inline]
+    fn info_cache_mut(&mut self) -> &mut InfoCache {
+        &mut self.info
+    }
+}
+
+fn read_file<T: for<'de> Deserialize<'de>, P: AsRef<Path>>(file: P) -> Result<T> {
+    let r = File::open(file)?;
+    Ok(bincode::deserialize_from(BufReader::new(r))?)
+}
+
+/// Reads the ext file. Files of another version can't be read since bincode doesn't describe
+/// the layout of the data
+fn read_ext_file(file: &Path) -> Result<ExtFile> {
+    let version: u32 = read_file(file)?;
+    if version != EXT_VERSION {
+        return Err(Error::Invalid(format!(
+            "{EXT_FILE} has version {version}, expected {EXT_VERSION}"
+        )));
+    }
+    read_file(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("kanji_srs_bincode_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut backend = BincodeBackend::open(&dir).unwrap();
+        backend.add_item('日');
+        backend.add_item('月');
+        backend.remove_item(1);
+        backend.srs_mut().get_mut(2).unwrap().suspend(100);
+        backend.save().unwrap();
+        assert!(BincodeBackend::exists(&dir));
+
+        let backend = BincodeBackend::open(&dir).unwrap();
+        assert_eq!(1, backend.items().len());
+        assert_eq!(2, backend.items().last_id());
+        let item = backend.srs().find(2).unwrap();
+        assert!(item.suspended);
+        assert_eq!(100, item.suspended_on);
+
+        backend.mark_migrated().unwrap();
+        assert!(!BincodeBackend::exists(&dir));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn baseline_files() {
+        let dir = std::env::temp_dir().join(format!("kanji_srs_baseline_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Only the item and SRS storage existed originally
+        let items = ItemFile {
+            file: String::new(),
+            items: vec![FileItem {
+                id: 1,
+                literal: '日',
+            }],
+            last_id: 1,
+        };
+        bincode::serialize_into(File::create(dir.join(ITEM_FILE)).unwrap(), &items).unwrap();
+        let item = FileSRSItem {
+            id: 1,
+            srs_data: FileSM2 {
+                repetitions: 3,
+                ease_factor: 2.5,
+            },
+            due_on: 100,
+            in_learning: true,
+        };
+        let srs = SRSFile {
+            file: String::new(),
+            data: HashMap::from([(1, item)]),
+        };
+        bincode::serialize_into(File::create(dir.join(SRS_FILE)).unwrap(), &srs).unwrap();
+
+        let mut backend = BincodeBackend::open(&dir).unwrap();
+        let item = backend.srs().find(1).unwrap();
+        assert_eq!(15, item.interval);
+        assert_eq!(100, item.due_on);
+        backend.save().unwrap();
+
+        // Ext files of other versions aren't guessed at
+        let ext = ExtFile {
+            version: EXT_VERSION + 1,
+            ..ExtFile::default()
+        };
+        bincode::serialize_into(File::create(dir.join(EXT_FILE)).unwrap(), &ext).unwrap();
+        assert!(BincodeBackend::open(&dir).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
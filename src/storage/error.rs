@@ -0,0 +1,75 @@
+use std::fmt::Display;
+
+/// Result type for storage operations
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error of a storage backend or an export
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    Bincode(bincode::Error),
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    Zip(zip::result::ZipError),
+    /// Data which can't be imported
+    Invalid(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {err}"),
+            Error::Sqlite(err) => write!(f, "sqlite error: {err}"),
+            Error::Bincode(err) => write!(f, "bincode error: {err}"),
+            Error::Json(err) => write!(f, "json error: {err}"),
+            Error::Csv(err) => write!(f, "csv error: {err}"),
+            Error::Zip(err) => write!(f, "zip error: {err}"),
+            Error::Invalid(err) => write!(f, "invalid data: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    #[inline]
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    #[inline]
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Sqlite(err)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    #[inline]
+    fn from(err: bincode::Error) -> Self {
+        Self::Bincode(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    #[inline]
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    #[inline]
+    fn from(err: csv::Error) -> Self {
+        Self::Csv(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    #[inline]
+    fn from(err: zip::result::ZipError) -> Self {
+        Self::Zip(err)
+    }
+}
@@ -0,0 +1,485 @@
+//! Export of all kanji, their SRS data and reviews into JSON or CSV and the import of such files.
+//! Unlike the database the exported records don't depend on the internal structures, so they
+//! can be kept as backup, put under version control or edited by hand
+
+use super::{
+    error::{Error, Result},
+    log::ReviewEntry,
+    settings::{SchedulerKind, Settings},
+    srs::{self, LearningStep},
+};
+use crate::{japanese::JapaneseExt, scheduler::SchedulerState, sm2::RepQuality, time::DayBoundary};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Version of the export format
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Format of an export file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    /// Kanji along with their reviews
+    Json,
+    /// Kanji only, one per line
+    Csv,
+}
+
+impl FileFormat {
+    /// Returns the format a file is in by its extension. `None` if it's neither JSON nor CSV
+    pub fn of(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl Display for FileFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileFormat::Json => write!(f, "json"),
+            FileFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(FileFormat::Json),
+            "csv" => Ok(FileFormat::Csv),
+            _ => Err(format!("Unknown file format '{s}'. Available: json, csv")),
+        }
+    }
+}
+
+/// What happens to kanji of an import which are in the database already
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImportPolicy {
+    /// Keep the kanji in the database
+    #[default]
+    Skip,
+    /// Replace them with the imported ones
+    Overwrite,
+    /// Keep the one which has been reviewed last, going by the reviews in the import and the
+    /// review log. Kanji not reviewed on either side are kept
+    Newer,
+}
+
+impl Display for ImportPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportPolicy::Skip => write!(f, "skip"),
+            ImportPolicy::Overwrite => write!(f, "overwrite"),
+            ImportPolicy::Newer => write!(f, "newer"),
+        }
+    }
+}
+
+impl FromStr for ImportPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(ImportPolicy::Skip),
+            "overwrite" => Ok(ImportPolicy::Overwrite),
+            "newer" => Ok(ImportPolicy::Newer),
+            _ => Err(format!(
+                "Unknown import policy '{s}'. Available: skip, overwrite, newer"
+            )),
+        }
+    }
+}
+
+/// Contents of an export file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub kanji: Vec<ExportedKanji>,
+    #[serde(default)]
+    pub reviews: Vec<ExportedReview>,
+}
+
+/// A kanji along with its SRS data. All fields but `literal` can be left out, giving a new
+/// kanji
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportedKanji {
+    pub literal: char,
+    /// Id the kanji had in the database it has been exported from. Kanji are imported by their
+    /// literal, so it's informational only
+    pub id: u32,
+    pub in_learning: bool,
+    /// Scheduler the state belongs to, `sm2` or `fsrs`
+    pub scheduler: String,
+    /// State of the scheduler as stored in the database, eg. `repetitions;ease factor` for SM-2
+    pub state: String,
+    /// Local time at which the kanji is due (`YYYY-MM-DD HH:MM`). Empty for new kanji
+    pub due: String,
+    pub interval: u32,
+    /// Index of the learning step the kanji is in
+    pub step: Option<u8>,
+    /// `true` if the learning step is a relearning step
+    pub relearning: bool,
+    pub lapses: u32,
+    pub leech: bool,
+    pub suspended: bool,
+    pub suspended_on: u64,
+    pub buried_until: u64,
+    pub drill: bool,
+}
+
+impl ExportedKanji {
+    /// Creates the exported record of the kanji `literal` with the SRS data `item`
+    pub fn new(literal: char, item: &srs::Item, boundary: &DayBoundary) -> Self {
+        let due = match item.due_on {
+            0 => String::new(),
+            due_on => boundary.format_time(due_on),
+        };
+        Self {
+            literal,
+            id: item.id,
+            in_learning: item.in_learning,
+            scheduler: item.state.kind().to_string(),
+            state: item.state.serialize(),
+            due,
+            interval: item.interval,
+            step: item.step.map(|i| i.index),
+            relearning: item.step.is_some_and(|i| i.relearning),
+            lapses: item.lapses,
+            leech: item.leech,
+            suspended: item.suspended,
+            suspended_on: item.suspended_on,
+            buried_until: item.buried_until,
+            drill: item.drill,
+        }
+    }
+
+    /// Returns the SRS data of the record for an item with the id `id`
+    pub fn to_item(&self, id: u32, boundary: &DayBoundary) -> Result<srs::Item> {
+        let invalid = |what: &str| Error::Invalid(format!("{}: {what}", self.literal));
+        if !self.literal.is_kanji() {
+            return Err(invalid("not a kanji"));
+        }
+
+        let kind = match self.scheduler.as_str() {
+            "" => SchedulerKind::SM2,
+            s => s
+                .parse::<SchedulerKind>()
+                .map_err(|_| invalid(&format!("unknown scheduler '{s}'")))?,
+        };
+        let state = match self.state.as_str() {
+            "" => SchedulerState::new(&Settings {
+                scheduler: kind,
+                ..Settings::default()
+            }),
+            s => SchedulerState::deserialize(kind, s)
+                .ok_or_else(|| invalid(&format!("invalid scheduler state '{s}'")))?,
+        };
+        let due_on = match self.due.as_str() {
+            "" => 0,
+            s => boundary
+                .parse_time(s)
+                .ok_or_else(|| invalid(&format!("invalid due date '{s}'")))?,
+        };
+
+        Ok(srs::Item {
+            id,
+            state,
+            due_on,
+            in_learning: self.in_learning,
+            interval: self.interval,
+            step: self.step.map(|index| LearningStep {
+                index,
+                relearning: self.relearning,
+            }),
+            lapses: self.lapses,
+            leech: self.leech,
+            suspended: self.suspended,
+            suspended_on: self.suspended_on,
+            buried_until: self.buried_until,
+            drill: self.drill,
+        })
+    }
+}
+
+/// A review of a kanji
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedReview {
+    pub literal: char,
+    /// Unix timestamp of the review
+    pub timestamp: u64,
+    /// Grade from 0 to 5
+    pub grade: u8,
+    pub prev_interval: u32,
+    pub new_interval: u32,
+    pub ease_before: f64,
+    pub ease_after: f64,
+    pub session: u64,
+}
+
+impl ExportedReview {
+    /// Creates the exported record of `entry` which is a review of the kanji `literal`
+    pub fn new(literal: char, entry: &ReviewEntry) -> Self {
+        Self {
+            literal,
+            timestamp: entry.timestamp,
+            grade: entry.quality.grade(),
+            prev_interval: entry.prev_interval,
+            new_interval: entry.new_interval,
+            ease_before: entry.ease_before,
+            ease_after: entry.ease_after,
+            session: entry.session,
+        }
+    }
+
+    /// Returns the review log entry of the record for the item with the id `item_id`. Imported
+    /// reviews can't be undone
+    pub fn to_entry(&self, item_id: u32) -> Result<ReviewEntry> {
+        let quality = RepQuality::from_grade(self.grade).ok_or_else(|| {
+            Error::Invalid(format!("{}: invalid grade {}", self.literal, self.grade))
+        })?;
+        Ok(ReviewEntry {
+            item_id,
+            timestamp: self.timestamp,
+            quality,
+            prev_interval: self.prev_interval,
+            new_interval: self.new_interval,
+            ease_before: self.ease_before,
+            ease_after: self.ease_after,
+            session: self.session,
+            before: None,
+            undone: false,
+        })
+    }
+}
+
+/// Amount of kanji and reviews affected by an import
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ImportSummary {
+    /// Kanji which haven't been in the database
+    pub added: usize,
+    /// Kanji in the database which have been replaced
+    pub updated: usize,
+    /// Kanji in the database which have been kept
+    pub skipped: usize,
+    /// Reviews added to the review log
+    pub reviews: usize,
+}
+
+/// Writes `export` in the given format. CSV files only get the kanji
+pub fn write<W: Write>(export: &Export, format: FileFormat, writer: W) -> Result<()> {
+    match format {
+        FileFormat::Json => serde_json::to_writer_pretty(writer, export)?,
+        FileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for kanji in export.kanji.iter() {
+                writer.serialize(kanji)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads an export in the given format
+pub fn read<R: Read>(format: FileFormat, reader: R) -> Result<Export> {
+    let export: Export = match format {
+        FileFormat::Json => serde_json::from_reader(reader)?,
+        FileFormat::Csv => {
+            let kanji = csv::Reader::from_reader(reader)
+                .deserialize()
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Export {
+                version: EXPORT_VERSION,
+                kanji,
+                reviews: vec![],
+            }
+        }
+    };
+
+    if export.version > EXPORT_VERSION {
+        return Err(Error::Invalid(format!(
+            "export version {} is newer than the supported version {EXPORT_VERSION}",
+            export.version
+        )));
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::SchedulerConfig, storage::Storage, time::FixedClock, utils::DAY_SECS};
+    use chrono_tz::{Asia::Tokyo, Europe::Berlin};
+    use std::rc::Rc;
+
+    #[test]
+    fn csv_round_trip() {
+        let boundary = DayBoundary::new(Tokyo, 4);
+        let mut item = srs::Item::new(3);
+        item.in_learning = true;
+        item.interval = 6;
+        // 2022-03-10 04:00 in Tokyo
+        item.due_on = 1_646_852_400;
+        item.step = Some(LearningStep {
+            index: 1,
+            relearning: true,
+        });
+
+        let export = Export {
+            version: EXPORT_VERSION,
+            kanji: vec![ExportedKanji::new('日', &item, &boundary)],
+            reviews: vec![],
+        };
+        assert_eq!("2022-03-10 04:00", export.kanji[0].due);
+
+        let mut csv = vec![];
+        write(&export, FileFormat::Csv, &mut csv).unwrap();
+        let read = read(FileFormat::Csv, csv.as_slice()).unwrap();
+        assert_eq!(export, read);
+        assert_eq!(item, read.kanji[0].to_item(3, &boundary).unwrap());
+    }
+
+    #[test]
+    fn hand_written_csv() {
+        let boundary = DayBoundary::new(Tokyo, 4);
+        let csv = "literal,in_learning,due,interval\n日,true,2022-03-10,3\n";
+        let export = read(FileFormat::Csv, csv.as_bytes()).unwrap();
+        let item = export.kanji[0].to_item(1, &boundary).unwrap();
+        assert!(item.in_learning);
+        assert_eq!(1_646_852_400, item.due_on);
+
+        // Only the literal gives a new kanji
+        let export = read(FileFormat::Csv, "literal\n月\n".as_bytes()).unwrap();
+        assert_eq!(
+            srs::Item::new(2),
+            export.kanji[0].to_item(2, &boundary).unwrap()
+        );
+
+        let csv = "literal,due\nx,\n";
+        let export = read(FileFormat::Csv, csv.as_bytes()).unwrap();
+        assert!(export.kanji[0].to_item(1, &boundary).is_err());
+    }
+
+    /// Storage with 日 and 月 learned on 2022-03-10 and 火 not learned yet. Uses Berlin time with
+    /// days starting at 04:00, no learning steps and exact intervals
+    fn learned_storage(clock: &Rc<FixedClock>) -> Storage {
+        let mut storage = Storage::in_memory();
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::new(Berlin, 4));
+        storage.set_scheduler_config(SchedulerConfig {
+            learning_steps: vec![],
+            relearning_steps: vec![],
+            fuzz: false,
+            ..SchedulerConfig::default()
+        });
+        for lit in "日月火".chars() {
+            storage.add(lit);
+        }
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+        storage.review(2, RepQuality::Grade5, session);
+        storage
+    }
+
+    #[test]
+    fn json_round_trip() {
+        // 2022-03-10 12:00 in Berlin
+        let clock = Rc::new(FixedClock::new(1_646_910_000));
+        let storage = learned_storage(&clock);
+
+        let mut json = vec![];
+        write(&storage.export(), FileFormat::Json, &mut json).unwrap();
+        let read = read(FileFormat::Json, json.as_slice()).unwrap();
+        assert_eq!(storage.export(), read);
+
+        let mut imported = Storage::in_memory();
+        imported.set_clock(clock.clone());
+        imported.set_day_boundary(DayBoundary::new(Berlin, 4));
+        imported.add('水');
+        let summary = imported.import(&read, ImportPolicy::Skip).unwrap();
+        assert_eq!(
+            ImportSummary {
+                added: 3,
+                updated: 0,
+                skipped: 0,
+                reviews: 2,
+            },
+            summary
+        );
+
+        let item = imported.get_by_lit('日').unwrap();
+        assert_eq!(2, item.get_id());
+        assert_eq!(
+            storage.get_srs_storage().find(1).unwrap().due_on,
+            imported.get_srs_storage().find(2).unwrap().due_on
+        );
+        assert_eq!(2, imported.history().len());
+
+        // Reviews aren't imported twice
+        let summary = imported.import(&read, ImportPolicy::Overwrite).unwrap();
+        assert_eq!(3, summary.updated);
+        assert_eq!(0, summary.reviews);
+    }
+
+    /// Storage with 日 reviewed again a day after the backup and 月 reset
+    fn changed_after_backup() -> Storage {
+        let clock = Rc::new(FixedClock::new(1_646_910_000));
+        let mut storage = learned_storage(&clock);
+        clock.advance(DAY_SECS);
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+        storage.reset('月');
+        storage
+    }
+
+    fn due_on(storage: &Storage, literal: char) -> u64 {
+        let id = storage.get_by_lit(literal).unwrap().get_id();
+        storage.get_srs_storage().find(id).unwrap().due_on
+    }
+
+    #[test]
+    fn import_policies() {
+        let clock = Rc::new(FixedClock::new(1_646_910_000));
+        let backup = learned_storage(&clock);
+        let export = backup.export();
+        let reviewed = due_on(&changed_after_backup(), '日');
+
+        let mut storage = changed_after_backup();
+        let summary = storage.import(&export, ImportPolicy::Skip).unwrap();
+        assert_eq!(3, summary.skipped);
+        assert_eq!(reviewed, due_on(&storage, '日'));
+        assert_eq!(0, due_on(&storage, '月'));
+
+        // 日 has been reviewed after the backup. 月 has been reset since but its last review is
+        // the one in the backup and 火 has never been reviewed, so all of them are kept
+        let mut storage = changed_after_backup();
+        let summary = storage.import(&export, ImportPolicy::Newer).unwrap();
+        assert_eq!(3, summary.skipped);
+        assert_eq!(reviewed, due_on(&storage, '日'));
+        assert_eq!(0, due_on(&storage, '月'));
+
+        let mut storage = learned_storage(&clock);
+        let newer = changed_after_backup().export();
+        let summary = storage.import(&newer, ImportPolicy::Newer).unwrap();
+        assert_eq!((1, 2), (summary.updated, summary.skipped));
+        assert_eq!(reviewed, due_on(&storage, '日'));
+        assert_eq!(due_on(&backup, '月'), due_on(&storage, '月'));
+
+        // Kanji which haven't been reviewed in the database take the reviewed imported ones
+        let mut storage = Storage::in_memory();
+        storage.add('日');
+        let summary = storage.import(&export, ImportPolicy::Newer).unwrap();
+        assert_eq!((1, 2), (summary.updated, summary.added));
+
+        let mut storage = changed_after_backup();
+        let summary = storage.import(&export, ImportPolicy::Overwrite).unwrap();
+        assert_eq!(3, summary.updated);
+        assert_eq!(due_on(&backup, '日'), due_on(&storage, '日'));
+    }
+}
@@ -0,0 +1,157 @@
+//! Projection of the reviews due over the next days
+
+use super::{settings::Settings, srs, SRSStorage};
+use crate::{config::SchedulerConfig, sm2::RepQuality, time::DayBoundary};
+use serde::Serialize;
+
+/// Reviews and new kanji expected on a single day
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ForecastDay {
+    /// Unix timestamp at which the day starts
+    pub start: u64,
+    /// Reviews of kanji which are already in learning or have been introduced on an earlier day
+    pub reviews: usize,
+    /// New kanji introduced on the day
+    pub new: usize,
+}
+
+impl ForecastDay {
+    /// Returns the amount of kanji written on the day
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.reviews + self.new
+    }
+}
+
+/// Projects the reviews of the `days` days starting with the one containing `now`, assuming all
+/// kanji get graded good and `new_per_day` new kanji get introduced each day. Overdue kanji count
+/// for the first day. Kanji get counted once per day, no matter how many learning steps they go
+/// through. Reviews are scheduled with `settings` and `config`, days start at `boundary`
+pub fn forecast(
+    srs: &SRSStorage,
+    settings: &Settings,
+    config: &SchedulerConfig,
+    boundary: &DayBoundary,
+    now: u64,
+    days: u32,
+    new_per_day: usize,
+) -> Vec<ForecastDay> {
+    if days == 0 {
+        return vec![];
+    }
+
+    let mut forecast = (0..days)
+        .map(|i| ForecastDay {
+            start: boundary.offset(now, i),
+            ..ForecastDay::default()
+        })
+        .collect::<Vec<_>>();
+    forecast[0].start = boundary.day_start(now);
+    let end = boundary.offset(now, days);
+    let review = |item: &mut srs::Item, time| {
+        item.review(RepQuality::Grade4, settings, config, boundary, time, None)
+    };
+
+    let mut scheduled = srs
+        .iter()
+        .filter(|i| i.in_learning && !i.suspended && !i.drill)
+        .copied()
+        .collect::<Vec<_>>();
+    scheduled.sort_by_key(|i| i.id);
+    for item in scheduled {
+        let time = item.due_on.max(item.buried_until).max(now);
+        simulate(item, time, end, review, &mut forecast, true);
+    }
+
+    let mut new = srs
+        .iter()
+        .filter(|i| !i.in_learning && !i.suspended && !i.drill)
+        .copied()
+        .collect::<Vec<_>>();
+    new.sort_by_key(|i| i.id);
+    let mut new = new.into_iter();
+    for day in 0..forecast.len() {
+        let time = forecast[day].start.max(now);
+        for item in new.by_ref().take(new_per_day) {
+            forecast[day].new += 1;
+            simulate(item, time, end, review, &mut forecast, false);
+        }
+    }
+
+    forecast
+}
+
+/// Reviews `item` with `review` at `time` and whenever it gets due again until `end`, counting
+/// the reviews in `forecast`. The first review isn't counted unless `count_first` is set
+fn simulate(
+    mut item: srs::Item,
+    mut time: u64,
+    end: u64,
+    review: impl Fn(&mut srs::Item, u64),
+    forecast: &mut [ForecastDay],
+    count_first: bool,
+) {
+    let mut counted = (!count_first).then(|| day_of(forecast, time));
+    while time < end {
+        let day = day_of(forecast, time);
+        if counted != Some(day) {
+            forecast[day].reviews += 1;
+            counted = Some(day);
+        }
+
+        review(&mut item, time);
+        if item.due_on <= time {
+            break;
+        }
+        time = item.due_on;
+    }
+}
+
+/// Returns the index of the day `time` falls on. Times before the first day fall on it
+#[inline]
+fn day_of(forecast: &[ForecastDay], time: u64) -> usize {
+    forecast
+        .partition_point(|i| i.start <= time)
+        .saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn project_reviews() {
+        let mut srs = SRSStorage::new();
+        for id in 1..=3 {
+            srs.add(id);
+        }
+        let item = srs.get_mut(1).unwrap();
+        item.in_learning = true;
+        item.interval = 1;
+        let boundary = DayBoundary::LEGACY;
+        item.due_on = boundary.offset(NOW, 2);
+
+        let forecast = forecast(
+            &srs,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &boundary,
+            NOW,
+            5,
+            1,
+        );
+        assert_eq!(5, forecast.len());
+        assert_eq!(
+            vec![1, 1, 0, 0, 0],
+            forecast.iter().map(|i| i.new).collect::<Vec<_>>()
+        );
+
+        // New kanji are due again the next day
+        assert_eq!(
+            vec![0, 1, 2, 1, 0],
+            forecast.iter().map(|i| i.reviews).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Statistics about the review history. Undone reviews don't count
+
+use super::log::{ReviewEntry, ReviewLog};
+use crate::time::DayBoundary;
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Ranges of intervals in days the retention is calculated for
+pub const INTERVAL_BUCKETS: [(u32, u32); 5] = [(1, 1), (2, 6), (7, 20), (21, 90), (91, u32::MAX)];
+
+/// Interval in days from which on kanji count as mature
+pub const MATURE_INTERVAL: u32 = 21;
+
+/// Reviews of kanji which had been scheduled with an interval between `min_interval` and
+/// `max_interval` days
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Retention {
+    pub min_interval: u32,
+    pub max_interval: u32,
+    pub reviews: usize,
+    /// Reviews in which the kanji has been recalled
+    pub passed: usize,
+}
+
+impl Retention {
+    /// Returns the fraction of passed reviews. `None` if there weren't any reviews
+    #[inline]
+    pub fn rate(&self) -> Option<f64> {
+        (self.reviews > 0).then(|| self.passed as f64 / self.reviews as f64)
+    }
+}
+
+/// Reviews of the review log which haven't been undone
+pub struct History<'a> {
+    entries: Vec<&'a ReviewEntry>,
+    boundary: DayBoundary,
+}
+
+impl<'a> History<'a> {
+    /// Creates a history of the reviews in `log`, assigning them to days with `boundary`
+    pub fn new(log: &'a ReviewLog, boundary: DayBoundary) -> Self {
+        let entries = log.iter().filter(|i| !i.undone).collect();
+        Self { entries, boundary }
+    }
+
+    /// Returns the day boundary the reviews are assigned to days with
+    #[inline]
+    pub fn boundary(&self) -> &DayBoundary {
+        &self.boundary
+    }
+
+    /// Returns the amount of reviews
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing has been reviewed yet
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the amount of reviews done on each review day
+    pub fn reviews_per_day(&self) -> BTreeMap<NaiveDate, usize> {
+        let mut per_day = BTreeMap::new();
+        for entry in self.entries.iter() {
+            *per_day
+                .entry(self.boundary.date_of(entry.timestamp))
+                .or_default() += 1;
+        }
+        per_day
+    }
+
+    /// Returns the amount of days in a row with reviews up to the day containing `now`. A streak
+    /// isn't broken before the current day is over
+    pub fn streak(&self, now: u64) -> u32 {
+        let per_day = self.reviews_per_day();
+        let mut day = self.boundary.date_of(now);
+        if !per_day.contains_key(&day) {
+            day -= Duration::days(1);
+        }
+
+        let mut streak = 0;
+        while per_day.contains_key(&day) {
+            streak += 1;
+            day -= Duration::days(1);
+        }
+        streak
+    }
+
+    /// Returns the retention of kanji which had been learned already, bucketed by the interval
+    /// they had been scheduled with. Reviews of new kanji and learning steps don't count
+    pub fn retention(&self) -> Vec<Retention> {
+        let mut buckets = INTERVAL_BUCKETS
+            .iter()
+            .map(|(min, max)| Retention {
+                min_interval: *min,
+                max_interval: *max,
+                ..Retention::default()
+            })
+            .collect::<Vec<_>>();
+
+        let reviews = self
+            .entries
+            .iter()
+            .filter(|i| i.prev_interval > 0 && i.before.is_none_or(|b| b.step.is_none()));
+        for entry in reviews {
+            let bucket = buckets
+                .iter_mut()
+                .find(|i| (i.min_interval..=i.max_interval).contains(&entry.prev_interval));
+            if let Some(bucket) = bucket {
+                bucket.reviews += 1;
+                if entry.quality.is_correct() {
+                    bucket.passed += 1;
+                }
+            }
+        }
+        buckets
+    }
+
+    /// Returns the duration in seconds of each session, from its start until its last review.
+    /// Sessions in which grading happened right away have a duration of 0
+    pub fn session_durations(&self) -> Vec<u64> {
+        let mut last_review = HashMap::new();
+        for entry in self.entries.iter() {
+            let last = last_review.entry(entry.session).or_insert(entry.timestamp);
+            *last = (*last).max(entry.timestamp);
+        }
+
+        let mut durations = last_review.into_iter().collect::<Vec<_>>();
+        durations.sort_unstable();
+        durations
+            .into_iter()
+            .map(|(session, last)| last.saturating_sub(session))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sm2::RepQuality, utils::DAY_SECS};
+    use chrono_tz::Asia::Tokyo;
+
+    /// 2022-03-10 12:00 in Tokyo
+    const NOW: u64 = 1_646_881_200;
+
+    fn entry(timestamp: u64, prev_interval: u32, quality: RepQuality) -> ReviewEntry {
+        ReviewEntry {
+            item_id: 1,
+            timestamp,
+            quality,
+            prev_interval,
+            new_interval: 1,
+            ease_before: 2.5,
+            ease_after: 2.5,
+            session: timestamp - 60,
+            before: None,
+            undone: false,
+        }
+    }
+
+    #[test]
+    fn streak_and_retention() {
+        let mut log = ReviewLog::new();
+        log.push(entry(NOW - 3 * DAY_SECS, 0, RepQuality::Grade4));
+        log.push(entry(NOW - DAY_SECS, 1, RepQuality::Grade4));
+        log.push(entry(NOW - DAY_SECS + 60, 10, RepQuality::Grade1));
+        log.push(entry(NOW - 2 * DAY_SECS, 10, RepQuality::Grade5));
+        log.push(ReviewEntry {
+            undone: true,
+            ..entry(NOW, 1, RepQuality::Grade0)
+        });
+        let history = History::new(&log, DayBoundary::new(Tokyo, 4));
+
+        assert_eq!(4, history.len());
+        assert_eq!(3, history.reviews_per_day().len());
+        // Nothing has been reviewed today yet
+        assert_eq!(3, history.streak(NOW));
+        assert_eq!(0, history.streak(NOW + DAY_SECS));
+
+        let retention = history.retention();
+        assert_eq!(Some(1.0), retention[0].rate());
+        assert_eq!(None, retention[1].rate());
+        assert_eq!(Some(0.5), retention[2].rate());
+        assert_eq!(vec![60, 60, 60, 60], history.session_durations());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::jotoba::KanjiInfo;
+use std::collections::HashMap;
+
+/// Cache of kanji information looked up on jotoba. Kanji jotoba doesn't know are cached as well,
+/// without any information, so they aren't looked up again
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InfoCache {
+    data: HashMap<char, KanjiInfo>,
+}
+
+impl InfoCache {
+    /// Creates a new, empty cache
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache from already stored information
+    pub fn from_infos<I: IntoIterator<Item = KanjiInfo>>(infos: I) -> Self {
+        let data = infos.into_iter().map(|i| (i.literal, i)).collect();
+        Self { data }
+    }
+
+    /// Returns the information about `literal` if it has been looked up already
+    #[inline]
+    pub fn get(&self, literal: char) -> Option<&KanjiInfo> {
+        self.data.get(&literal)
+    }
+
+    /// Returns `true` if `literal` has been looked up already
+    #[inline]
+    pub fn contains(&self, literal: char) -> bool {
+        self.data.contains_key(&literal)
+    }
+
+    /// Adds or replaces the information about a kanji
+    #[inline]
+    pub fn insert(&mut self, info: KanjiInfo) {
+        self.data.insert(info.literal, info);
+    }
+
+    /// Returns an iterator over all cached information
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &KanjiInfo> {
+        self.data.values()
+    }
+
+    /// Returns the amount of cached kanji
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if nothing has been cached yet
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
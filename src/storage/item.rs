@@ -1,40 +1,33 @@
-use std::{
-    fs::{copy, rename, File},
-    io::BufReader,
-    path::Path,
-};
-
 use serde::{Deserialize, Serialize};
 
 /// Storage for single characters to learn
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
 pub struct ItemStorage {
-    file: String,
     items: Vec<Item>,
     last_id: u32,
 }
 
 /// A single item to learn
-#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
 pub struct Item {
     pub id: u32,
     pub literal: char,
 }
 
 impl ItemStorage {
-    /// Creates a new ItemStorage
+    /// Creates a new, empty ItemStorage
     #[inline]
-    pub fn new<P: AsRef<str>>(file: P) -> Self {
-        let file = file.as_ref().to_string();
-
-        if Path::new(&file).exists() {
-            return Self::load(file).expect("Failed to load item sorage");
-        }
+    pub fn new() -> Self {
+        Self::default()
+    }
 
+    /// Creates an ItemStorage from already stored items. `last_id` is the highest id that has
+    /// ever been given out and ensures ids of removed items don't get reused
+    pub fn from_items(items: Vec<Item>, last_id: u32) -> Self {
+        let max_id = items.iter().map(|i| i.id).max().unwrap_or(0);
         Self {
-            file,
-            items: vec![],
-            last_id: 0,
+            items,
+            last_id: last_id.max(max_id),
         }
     }
 
@@ -50,6 +43,12 @@ impl ItemStorage {
         self.len() == 0
     }
 
+    /// Returns the highest id that has been given out so far
+    #[inline]
+    pub fn last_id(&self) -> u32 {
+        self.last_id
+    }
+
     /// Returns an Item by its id. `None` if there is no item with given id in the
     /// storage
     #[inline]
@@ -87,37 +86,10 @@ impl ItemStorage {
         self.items.iter()
     }
 
-    fn backup(&self) {
-        let backup = Path::new(&self.file).with_file_name("item_backup");
-        let _ = copy(&self.file, backup);
-    }
-
-    /// Save ItemStorage
-    pub fn save(&self) {
-        self.backup();
-        let path = Path::new(&self.file).with_file_name("item_data_new");
-        let file = File::create(&path).expect("Couldn't write Item Storage file");
-        bincode::serialize_into(&file, &self).unwrap();
-        if !Self::check_file(&path) {
-            panic!("Failed to save item storage");
-        }
-        rename(path, &self.file).unwrap()
-    }
-
     /// Remove an item by its ID
     pub(super) fn remove_item(&mut self, id: u32) -> bool {
         let len = self.items.len();
         self.items.retain(|i| i.id != id);
         self.items.len() < len
     }
-
-    fn check_file<P: AsRef<Path>>(file: P) -> bool {
-        Self::load(file).is_some()
-    }
-
-    fn load<P: AsRef<Path>>(file: P) -> Option<Self> {
-        let r = File::open(&file).ok()?;
-        let item_storage: Self = bincode::deserialize_from(BufReader::new(r)).ok()?;
-        return Some(item_storage);
-    }
 }
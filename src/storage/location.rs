@@ -0,0 +1,128 @@
+//! Resolving the directory the storage lives in
+
+use super::{error::Result, Storage};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Environment variable overriding the storage directory
+pub const DB_ENV: &str = "KANJI_SRS_DB";
+
+/// Name of the storage directory within the user's data directory
+const DIR_NAME: &str = "kanji_srs";
+
+/// Directory old versions kept the storage in, relative to the working directory
+pub const LEGACY_DIR: &str = "./storage/";
+
+/// Directory of a storage and whether it has been chosen explicitly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub dir: PathBuf,
+    /// `true` if the directory has been set by the user instead of being the default one
+    pub custom: bool,
+}
+
+impl Location {
+    /// Resolves the storage directory. `arg` and `env` override the default directory within
+    /// `data_dir` (`$XDG_DATA_HOME` or `~/.local/share`). Falls back to [`LEGACY_DIR`] if there
+    /// is no data directory
+    pub fn resolve(arg: Option<&str>, env: Option<String>, data_dir: Option<PathBuf>) -> Self {
+        let custom = arg
+            .map(PathBuf::from)
+            .or_else(|| env.filter(|i| !i.is_empty()).map(PathBuf::from));
+
+        match custom {
+            Some(dir) => Self { dir, custom: true },
+            None => Self {
+                dir: data_dir
+                    .map(|i| i.join(DIR_NAME))
+                    .unwrap_or_else(|| PathBuf::from(LEGACY_DIR)),
+                custom: false,
+            },
+        }
+    }
+
+    /// Resolves the storage directory using the environment of the process
+    pub fn from_env(arg: Option<&str>) -> Self {
+        Self::resolve(arg, std::env::var(DB_ENV).ok(), dirs::data_dir())
+    }
+
+    /// Returns the old storage in the working directory if it should be moved to this location.
+    /// That's the case if there is none yet and the location hasn't been chosen explicitly
+    pub fn legacy_storage(&self) -> Option<PathBuf> {
+        let legacy = PathBuf::from(LEGACY_DIR);
+        let is_legacy = fs::canonicalize(&legacy).ok() == fs::canonicalize(&self.dir).ok();
+
+        (!self.custom && !is_legacy && !Storage::exists(&self.dir) && Storage::exists(&legacy))
+            .then_some(legacy)
+    }
+}
+
+/// Moves all files of the storage directory `from` into `to`. `from` gets removed if it's empty
+/// afterwards
+pub fn move_storage<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    let from = from.as_ref();
+    let to = to.as_ref();
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let target = to.join(path.file_name().unwrap_or_default());
+        // Renaming fails if both directories are on different file systems
+        if fs::rename(&path, &target).is_err() {
+            fs::copy(&path, &target)?;
+            fs::remove_file(&path)?;
+        }
+    }
+
+    let _ = fs::remove_dir(from);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::DB_FILE;
+
+    #[test]
+    fn resolve() {
+        let data = Some(PathBuf::from("/data"));
+
+        let location = Location::resolve(None, None, data.clone());
+        assert_eq!(PathBuf::from("/data/kanji_srs"), location.dir);
+        assert!(!location.custom);
+
+        let location = Location::resolve(None, Some("/env".to_string()), data.clone());
+        assert_eq!(PathBuf::from("/env"), location.dir);
+        assert!(location.custom);
+
+        let location = Location::resolve(Some("/arg"), Some("/env".to_string()), data.clone());
+        assert_eq!(PathBuf::from("/arg"), location.dir);
+
+        let location = Location::resolve(None, Some(String::new()), data);
+        assert!(!location.custom);
+
+        let location = Location::resolve(None, None, None);
+        assert_eq!(PathBuf::from(LEGACY_DIR), location.dir);
+    }
+
+    #[test]
+    fn move_files() {
+        let dir = std::env::temp_dir().join(format!("kanji_srs_move_{}", std::process::id()));
+        let from = dir.join("from");
+        let to = dir.join("to/kanji_srs");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join(DB_FILE), b"db").unwrap();
+
+        move_storage(&from, &to).unwrap();
+        assert!(Storage::exists(&to));
+        assert!(!from.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use super::srs;
+use crate::sm2::RepQuality;
+use serde::{Deserialize, Serialize};
+
+/// Append-only log of all reviews. Each entry keeps the SRS data the item had before the review
+/// so it can be undone. Undone reviews stay in the log but are marked as such
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ReviewLog {
+    entries: Vec<ReviewEntry>,
+}
+
+/// A single grading event
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReviewEntry {
+    pub item_id: u32,
+    /// Unix timestamp of the review
+    pub timestamp: u64,
+    pub quality: RepQuality,
+    /// Interval in days the item had been scheduled with before the review
+    pub prev_interval: u32,
+    /// Interval in days the item has been scheduled with by the review
+    pub new_interval: u32,
+    pub ease_before: f64,
+    pub ease_after: f64,
+    /// Id of the session the review happened in
+    pub session: u64,
+    /// SRS data of the item before the review. `None` for reviews which have been logged before
+    /// reviews could be undone
+    pub before: Option<srs::Item>,
+    /// `true` if the review has been undone
+    pub undone: bool,
+}
+
+impl ReviewLog {
+    /// Creates a new, empty review log
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a review log from already stored entries
+    #[inline]
+    pub fn from_entries(entries: Vec<ReviewEntry>) -> Self {
+        Self { entries }
+    }
+
+    /// Appends an entry to the log
+    #[inline]
+    pub fn push(&mut self, entry: ReviewEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Returns the amount of entries in the log
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing has been reviewed yet
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over all entries in the order they have been added
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &ReviewEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the entry at `index`
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&ReviewEntry> {
+        self.entries.get(index)
+    }
+
+    /// Marks the entry at `index` as undone
+    #[inline]
+    pub fn mark_undone(&mut self, index: usize) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.undone = true;
+        }
+    }
+
+    /// Returns the id of the latest session which has reviews that can still be undone
+    pub fn last_undoable_session(&self) -> Option<u64> {
+        self.entries
+            .iter()
+            .filter(|i| !i.undone && i.before.is_some())
+            .map(|i| i.session)
+            .max()
+    }
+
+    /// Returns all entries added after the first `n` ones
+    #[inline]
+    pub fn since(&self, n: usize) -> &[ReviewEntry] {
+        self.entries.get(n..).unwrap_or_default()
+    }
+
+    /// Returns a new session id for reviews done at `now`. Session ids are the unix timestamp of
+    /// the sessions start but are guaranteed to be unique
+    pub fn new_session(&self, now: u64) -> u64 {
+        let last = self.entries.iter().map(|i| i.session).max().unwrap_or(0);
+        now.max(last + 1)
+    }
+}
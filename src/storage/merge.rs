@@ -0,0 +1,365 @@
+//! Merging of changes made by one instance into data which has been written by another instance
+//! in the meantime
+
+use super::{
+    backend::StorageBackend,
+    info::InfoCache,
+    log::{ReviewEntry, ReviewLog},
+    settings::Settings,
+    ItemStorage, SRSStorage,
+};
+use std::collections::HashSet;
+
+/// All data of a storage at a given point in time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub items: ItemStorage,
+    pub srs: SRSStorage,
+    pub review_log: ReviewLog,
+    pub settings: Settings,
+    pub info: InfoCache,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the data currently held by `backend`
+    pub fn of(backend: &dyn StorageBackend) -> Self {
+        Self {
+            items: backend.items().clone(),
+            srs: backend.srs().clone(),
+            review_log: backend.review_log().clone(),
+            settings: *backend.settings(),
+            info: backend.info_cache().clone(),
+        }
+    }
+}
+
+/// Applies all changes made between `base` and `local` onto the data currently held by
+/// `backend`. Items are matched by their literal since ids of items added concurrently might
+/// collide. If the SRS data of an item has been changed on both sides the side which changed it
+/// last wins. A side changed it at its latest review since `base`. Changes which aren't reviews,
+/// like resets, aren't logged with a time and count as the latest, with the local side winning if
+/// both made one. Reviews logged locally get appended to the review log, the ones of items the
+/// other side won marked as undone since they don't apply to the kept data. Reviews undone
+/// locally get marked as undone. Kanji information looked up locally gets added to the cache.
+pub fn merge(base: &Snapshot, local: &Snapshot, backend: &mut dyn StorageBackend) {
+    if local.settings != base.settings {
+        *backend.settings_mut() = local.settings;
+    }
+
+    // Items removed locally
+    for item in base.items.iter() {
+        if local.items.item_by_id(item.id).is_some() {
+            continue;
+        }
+
+        if let Some(id) = backend.items().item_by_literal(item.literal).map(|i| i.id) {
+            backend.remove_item(id);
+        }
+    }
+
+    let base_len = base.review_log.len();
+
+    // Local ids of the items whose data on disk has been kept
+    let mut discarded = HashSet::new();
+
+    for item in local.items.iter() {
+        let local_srs = match local.srs.find(item.id) {
+            Some(srs) => *srs,
+            None => continue,
+        };
+        let base_srs = base.srs.find(item.id);
+
+        // Skip items which haven't been touched locally
+        if base.items.item_by_id(item.id).is_some() && base_srs == Some(&local_srs) {
+            continue;
+        }
+
+        let disk_id = match backend.items().item_by_literal(item.literal) {
+            Some(disk_item) => disk_item.id,
+            // Added locally
+            None if base.items.item_by_id(item.id).is_none() => {
+                match backend.add_item(item.literal) {
+                    Some(id) => id,
+                    None => continue,
+                }
+            }
+            // Removed by another instance
+            None => continue,
+        };
+
+        let disk_srs = backend.srs().find(disk_id).copied();
+        let take_local = match disk_srs {
+            Some(disk_srs) => {
+                let disk_unchanged = base_srs.map(|i| i.id == disk_id && *i == disk_srs);
+                disk_unchanged.unwrap_or(false)
+                    || changed_at(local.review_log.since(base_len), item.id)
+                        >= changed_at(backend.review_log().since(base_len), disk_id)
+            }
+            None => true,
+        };
+
+        if !take_local {
+            discarded.insert(item.id);
+            continue;
+        }
+
+        backend.srs_mut().add(disk_id);
+        if let Some(srs) = backend.srs_mut().get_mut(disk_id) {
+            *srs = local_srs;
+            srs.id = disk_id;
+        }
+    }
+
+    for entry in local.review_log.since(base_len) {
+        let mut entry = *entry;
+        if discarded.contains(&entry.item_id) {
+            entry.undone = true;
+        }
+
+        // Entries of removed items keep their old id
+        let literal = local.items.item_by_id(entry.item_id).map(|i| i.literal);
+        if let Some(disk_item) = literal.and_then(|i| backend.items().item_by_literal(i)) {
+            entry.item_id = disk_item.id;
+        }
+
+        backend.review_log_mut().push(entry);
+    }
+
+    for info in local.info.iter() {
+        if !base.info.contains(info.literal) {
+            backend.info_cache_mut().insert(info.clone());
+        }
+    }
+
+    // Reviews undone locally. Entries logged before `base` are the same on both sides
+    for (index, entry) in local.review_log.iter().enumerate() {
+        let base_entry = match base.review_log.get(index) {
+            Some(base_entry) => base_entry,
+            None => break,
+        };
+        if entry.undone && !base_entry.undone {
+            backend.review_log_mut().mark_undone(index);
+        }
+    }
+}
+
+/// Returns the unix timestamp of the latest review of the item `id` in `entries`. `u64::MAX` if
+/// it hasn't been reviewed, in which case it has been changed otherwise
+fn changed_at(entries: &[ReviewEntry], id: u32) -> u64 {
+    entries
+        .iter()
+        .filter(|i| i.item_id == id && !i.undone)
+        .map(|i| i.timestamp)
+        .max()
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::SchedulerConfig,
+        sm2::RepQuality,
+        storage::{settings::Settings, MemoryBackend},
+        time::DayBoundary,
+    };
+
+    fn backend_with(literals: &[char]) -> MemoryBackend {
+        let mut backend = MemoryBackend::new();
+        for lit in literals {
+            backend.add_item(*lit);
+        }
+        backend
+    }
+
+    /// Reviews the item `id` of `backend` at `now` and logs the review
+    fn review(backend: &mut MemoryBackend, id: u32, quality: RepQuality, now: u64) {
+        let item = backend.srs_mut().get_mut(id).unwrap();
+        let before = *item;
+        item.review(
+            quality,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            now,
+            None,
+        );
+        let after = *item;
+        backend.review_log_mut().push(ReviewEntry {
+            item_id: id,
+            timestamp: now,
+            quality,
+            prev_interval: before.interval,
+            new_interval: after.interval,
+            ease_before: before.state.ease_factor(),
+            ease_after: after.state.ease_factor(),
+            session: now,
+            before: Some(before),
+            undone: false,
+        });
+    }
+
+    /// Backend holding the same data as `base`
+    fn copy_of(base: &Snapshot) -> MemoryBackend {
+        let mut backend = MemoryBackend::new();
+        backend.set_data(base.clone());
+        backend
+    }
+
+    #[test]
+    fn concurrent_add_and_review() {
+        let mut disk = backend_with(&['日', '月']);
+        let base = Snapshot::of(&disk);
+
+        // Another instance adds a kanji while this one reviews
+        let mut local = disk.srs().clone();
+        local.get_mut(1).unwrap().review(
+            RepQuality::Grade4,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            0,
+            None,
+        );
+        let local = Snapshot {
+            srs: local,
+            ..base.clone()
+        };
+        disk.add_item('火');
+
+        merge(&base, &local, &mut disk);
+
+        assert_eq!(3, disk.items().len());
+        assert!(disk.srs().find(1).unwrap().in_learning);
+        assert!(!disk.srs().find(3).unwrap().in_learning);
+    }
+
+    #[test]
+    fn concurrent_adds() {
+        let mut disk = backend_with(&['日']);
+        let base = Snapshot::of(&disk);
+
+        let mut local = backend_with(&['日']);
+        local.add_item('月');
+        local.srs_mut().get_mut(2).unwrap().review(
+            RepQuality::Grade4,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            0,
+            None,
+        );
+        let local = Snapshot::of(&local);
+
+        disk.add_item('火');
+
+        merge(&base, &local, &mut disk);
+
+        assert_eq!(3, disk.items().len());
+        let id = disk.items().item_by_literal('月').unwrap().id;
+        assert_eq!(3, id);
+        assert!(disk.srs().find(id).unwrap().in_learning);
+        assert_eq!(Some('火'), disk.get_item(2).map(|i| i.literal));
+    }
+
+    #[test]
+    fn concurrent_undo() {
+        let mut disk = backend_with(&['日']);
+        let mut storage = crate::storage::Storage::new(backend_with(&['日']));
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+        let base = Snapshot::of(storage.backend.as_ref());
+        merge(&Snapshot::of(&disk), &base, &mut disk);
+
+        storage.undo_last_session();
+        let local = Snapshot::of(storage.backend.as_ref());
+        disk.add_item('月');
+
+        merge(&base, &local, &mut disk);
+
+        assert!(disk.review_log().get(0).unwrap().undone);
+        assert!(!disk.srs().find(1).unwrap().in_learning);
+    }
+
+    #[test]
+    fn concurrent_remove() {
+        let mut disk = backend_with(&['日', '月']);
+        let base = Snapshot::of(&disk);
+
+        let mut local = backend_with(&['日', '月']);
+        local.remove_item(1);
+        let local = Snapshot::of(&local);
+
+        disk.srs_mut().get_mut(2).unwrap().review(
+            RepQuality::Grade4,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            0,
+            None,
+        );
+
+        merge(&base, &local, &mut disk);
+
+        assert_eq!(1, disk.items().len());
+        assert!(disk.items().item_by_literal('日').is_none());
+        assert!(disk.srs().find(2).unwrap().in_learning);
+    }
+
+    #[test]
+    fn concurrent_reset() {
+        let mut disk = backend_with(&['日']);
+        review(&mut disk, 1, RepQuality::Grade4, 0);
+        let base = Snapshot::of(&disk);
+
+        // Passing 日 schedules it later but the reset isn't undone by it
+        let mut local = copy_of(&base);
+        review(&mut local, 1, RepQuality::Grade4, 86_400);
+        disk.srs_mut().get_mut(1).unwrap().reset();
+
+        merge(&base, &Snapshot::of(&local), &mut disk);
+
+        assert!(!disk.srs().find(1).unwrap().in_learning);
+        assert_eq!(2, disk.review_log().len());
+        assert!(disk.review_log().get(1).unwrap().undone);
+    }
+
+    #[test]
+    fn concurrent_reviews() {
+        let mut disk = backend_with(&['日', '月']);
+        for id in 1..=2 {
+            review(&mut disk, id, RepQuality::Grade4, 0);
+        }
+        let base = Snapshot::of(&disk);
+
+        // The later review wins even though it has been failed and is due earlier
+        let mut local = copy_of(&base);
+        review(&mut local, 1, RepQuality::Grade4, 86_400);
+        review(&mut local, 2, RepQuality::Grade1, 90_000);
+        review(&mut disk, 1, RepQuality::Grade1, 90_000);
+        review(&mut disk, 2, RepQuality::Grade4, 86_400);
+        let failed = *disk.srs().find(1).unwrap();
+        let local = Snapshot::of(&local);
+
+        merge(&base, &local, &mut disk);
+
+        assert_eq!(&failed, disk.srs().find(1).unwrap());
+        assert_eq!(local.srs.find(2), disk.srs().find(2));
+        let undone = disk
+            .review_log()
+            .iter()
+            .map(|i| (i.item_id, i.undone))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (1, false),
+                (2, false),
+                (1, false),
+                (2, false),
+                (1, true),
+                (2, false)
+            ],
+            undone
+        );
+    }
+}
@@ -1,16 +1,65 @@
+pub mod anki;
+pub mod backend;
+pub mod bincode_files;
+pub mod error;
+pub mod export;
+pub mod forecast;
+pub mod history;
+pub mod info;
 pub mod item;
+pub mod location;
+pub mod log;
+pub mod merge;
+pub mod order;
+pub mod settings;
+pub mod sqlite;
 pub mod srs;
+pub mod workload;
 
+pub use backend::{MemoryBackend, StorageBackend};
+pub use bincode_files::BincodeBackend;
+pub use error::Error;
 pub use item::ItemStorage;
+pub use sqlite::SqliteBackend;
 pub use srs::SRSStorage;
 
-use crate::sm2::SM2;
+use crate::{
+    config::{NewOrder, ReviewOrder, SchedulerConfig},
+    jotoba::KanjiInfo,
+    scheduler::SchedulerState,
+    sm2::RepQuality,
+    time::{Clock, DayBoundary, SystemClock},
+};
+use export::{Export, ExportedKanji, ExportedReview, ImportPolicy, ImportSummary};
+use forecast::ForecastDay;
+use history::History;
+use log::{ReviewEntry, ReviewLog};
+use merge::Snapshot;
+use proc_lock::{lock, LockGuard, LockPath};
+use settings::Settings;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use workload::Workload;
+
+/// Name of the file used to synchronize access to a storage directory between instances
+const LOCK_FILE: &str = "kanji_srs.lock";
 
 /// ItemStorage and SRSStorage combined for functions which need both
 #[derive(Debug)]
 pub struct Storage {
-    item_storage: ItemStorage,
-    srs_storage: SRSStorage,
+    backend: Box<dyn StorageBackend>,
+    /// Data as it was when it has been loaded or saved the last time
+    base: Snapshot,
+    /// File to lock while reading or writing the backend
+    lock_file: Option<PathBuf>,
+    /// Source of the time used for reviews and due dates
+    clock: Box<dyn Clock>,
+    /// Parameters reviews get scheduled with
+    scheduler: SchedulerConfig,
+    /// `true` if old bincode files have been migrated into the database on open
+    migrated: bool,
 }
 
 /// A full storage item
@@ -18,6 +67,7 @@ pub struct Storage {
 pub struct Item<'a> {
     item_val: &'a item::Item,
     srs: &'a srs::Item,
+    boundary: DayBoundary,
 }
 
 impl<'a> Item<'a> {
@@ -27,10 +77,10 @@ impl<'a> Item<'a> {
         self.item_val.literal
     }
 
-    /// Returns SRS Info
+    /// Returns the state of the scheduler
     #[inline]
-    pub fn get_srs(&self) -> SM2 {
-        self.srs.srs_data
+    pub fn get_state(&self) -> SchedulerState {
+        self.srs.state
     }
 
     /// Returns the ID of the item
@@ -45,89 +95,619 @@ impl<'a> Item<'a> {
         self.srs.in_learning
     }
 
-    /// Returns `true` if item can be reviewed or learned
+    /// Returns `true` if item can be reviewed or learned at the unix timestamp `now`
     #[inline]
-    pub fn can_be_reviewed(&self) -> bool {
-        self.srs.can_be_reviewed()
+    pub fn can_be_reviewed(&self, now: u64) -> bool {
+        self.srs.can_be_reviewed(now, &self.boundary)
     }
 }
 
 impl Storage {
-    /// New Storage
+    /// New Storage using `backend` to persist its data
     #[inline]
-    pub fn new(item: ItemStorage, srs: SRSStorage) -> Self {
+    pub fn new<B: StorageBackend + 'static>(backend: B) -> Self {
+        let base = Snapshot::of(&backend);
         Self {
-            item_storage: item,
-            srs_storage: srs,
+            backend: Box::new(backend),
+            base,
+            lock_file: None,
+            clock: Box::new(SystemClock),
+            scheduler: SchedulerConfig::default(),
+            migrated: false,
+        }
+    }
+
+    /// New Storage which doesn't persist anything
+    #[inline]
+    pub fn in_memory() -> Self {
+        Self::new(MemoryBackend::new())
+    }
+
+    /// Opens the SQLite storage within the directory `dir`. Old bincode files found in `dir` get
+    /// migrated into the database automatically
+    pub fn open<P: AsRef<Path>>(dir: P) -> error::Result<Self> {
+        let dir = dir.as_ref();
+        let lock_file = dir.join(LOCK_FILE);
+        let _guard = lock(&LockPath::FullPath(&lock_file))?;
+
+        let db_file = dir.join(sqlite::DB_FILE);
+        let migrate = !db_file.exists() && BincodeBackend::exists(dir);
+
+        let mut backend = SqliteBackend::open(db_file)?;
+
+        if migrate {
+            let old = BincodeBackend::open(dir)?;
+            backend.set_data(Snapshot::of(&old));
+
+            let mut storage = Self::new(backend);
+
+            // Inconsistent data can't be written into the database
+            if !storage.check() {
+                storage.repair();
+            }
+
+            storage.backend.save()?;
+            storage.base = Snapshot::of(storage.backend.as_ref());
+            old.mark_migrated()?;
+
+            storage.lock_file = Some(lock_file);
+            storage.migrated = true;
+            return Ok(storage);
+        }
+
+        let mut storage = Self::new(backend);
+        storage.lock_file = Some(lock_file);
+        Ok(storage)
+    }
+
+    /// Returns `true` if old bincode files have been migrated into the database when the
+    /// storage has been opened
+    #[inline]
+    pub fn migrated(&self) -> bool {
+        self.migrated
+    }
+
+    /// Replaces the clock used for reviews and due dates
+    #[inline]
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Replaces the parameters reviews get scheduled with
+    #[inline]
+    pub fn set_scheduler_config(&mut self, config: SchedulerConfig) {
+        self.scheduler = config;
+    }
+
+    /// Returns the current unix timestamp according to the storage's clock
+    #[inline]
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    /// Returns the day boundary due dates get calculated with. Storages which never had one
+    /// set use the one of older versions
+    #[inline]
+    pub fn day_boundary(&self) -> DayBoundary {
+        self.backend
+            .settings()
+            .day_boundary
+            .unwrap_or(DayBoundary::LEGACY)
+    }
+
+    /// Returns the unix timestamp at which the current day started
+    #[inline]
+    pub fn today(&self) -> u64 {
+        self.day_boundary().day_start(self.now())
+    }
+
+    /// Returns the unix timestamp at which the day `days` days after the current one starts
+    #[inline]
+    pub fn day_offset(&self, days: u32) -> u64 {
+        self.day_boundary().offset(self.now(), days)
+    }
+
+    /// Returns all items which are due for review
+    #[inline]
+    pub fn get_due(&self) -> impl Iterator<Item = &srs::Item> {
+        self.backend.srs().get_due(self.now(), &self.day_boundary())
+    }
+
+    /// Returns all items which are due for review in the order they should be reviewed in. The
+    /// random order stays the same throughout a day
+    pub fn get_due_ordered(&self, order: ReviewOrder) -> Vec<&srs::Item> {
+        let mut due = self.get_due().collect::<Vec<_>>();
+        let boundary = self.day_boundary();
+        order::sort_due(&mut due, order, &boundary, self.now(), self.today());
+        due
+    }
+
+    /// Projects the reviews of the next `days` days including today, assuming all kanji get graded
+    /// good and `new_per_day` new kanji get introduced each day
+    pub fn forecast(&self, days: u32, new_per_day: usize) -> Vec<ForecastDay> {
+        let settings = *self.backend.settings();
+        forecast::forecast(
+            self.backend.srs(),
+            &settings,
+            &self.scheduler,
+            &self.day_boundary(),
+            self.now(),
+            days,
+            new_per_day,
+        )
+    }
+
+    /// Spreads the items which are still due evenly over the next `days` days, the ones coming
+    /// first in `order` on the earlier days. Items in learning steps aren't moved. Returns the
+    /// amount of moved items
+    pub fn spread_due(&mut self, days: u32, order: ReviewOrder) -> usize {
+        if days == 0 {
+            return 0;
         }
+
+        let due = self
+            .get_due_ordered(order)
+            .into_iter()
+            .filter(|i| i.step.is_none())
+            .map(|i| i.id)
+            .collect::<Vec<_>>();
+        let per_day = due.len().div_ceil(days as usize);
+
+        for (i, id) in due.iter().enumerate() {
+            let due_on = self.day_offset(1 + (i / per_day) as u32);
+            if let Some(item) = self.backend.srs_mut().get_mut(*id) {
+                item.due_on = due_on;
+            }
+        }
+
+        due.len()
+    }
+
+    /// Returns all new items which can be learned now
+    #[inline]
+    pub fn get_new(&self) -> impl Iterator<Item = &srs::Item> {
+        self.backend.srs().get_new(self.now())
+    }
+
+    /// Returns all new items which can be learned now in the order they get introduced in. The
+    /// random order stays the same throughout a day
+    pub fn get_new_ordered(&self, order: NewOrder) -> Vec<&srs::Item> {
+        let mut new = self.get_new().collect::<Vec<_>>();
+        let (items, info) = (self.backend.items(), self.backend.info_cache());
+        order::sort_new(&mut new, order, items, info, self.today());
+        new
+    }
+
+    /// Returns the literals of all new items which haven't been looked up on jotoba yet
+    pub fn missing_info(&self) -> Vec<char> {
+        let info = self.backend.info_cache();
+        self.get_new()
+            .filter_map(|i| self.backend.get_item(i.id))
+            .map(|i| i.literal)
+            .filter(|i| !info.contains(*i))
+            .collect()
+    }
+
+    /// Returns the cached information about a kanji
+    #[inline]
+    pub fn get_info(&self, literal: char) -> Option<&KanjiInfo> {
+        self.backend.info_cache().get(literal)
+    }
+
+    /// Caches information about a kanji
+    #[inline]
+    pub fn add_info(&mut self, info: KanjiInfo) {
+        self.backend.info_cache_mut().insert(info);
+    }
+
+    /// Returns `true` if there is a storage of any format within `dir`
+    pub fn exists<P: AsRef<Path>>(dir: P) -> bool {
+        let dir = dir.as_ref();
+        dir.join(sqlite::DB_FILE).exists() || BincodeBackend::exists(dir)
     }
 
     /// Get srs item mutable
     pub fn get_srs_mut(&mut self, id: u32) -> Option<&mut srs::Item> {
-        self.srs_storage.get_mut(id)
+        self.backend.srs_mut().get_mut(id)
     }
 
     /// Returns a full item from the storage
-    pub fn get_by_lit(&self, literal: char) -> Option<Item> {
-        let item_val = self.item_storage.item_by_literal(literal)?;
-        let srs = self.srs_storage.find(item_val.id)?;
-        Some(Item { item_val, srs })
+    pub fn get_by_lit(&self, literal: char) -> Option<Item<'_>> {
+        let item_val = self.backend.items().item_by_literal(literal)?;
+        let srs = self.backend.srs().find(item_val.id)?;
+        let boundary = self.day_boundary();
+        Some(Item {
+            item_val,
+            srs,
+            boundary,
+        })
     }
 
     /// Returns a full item from the storage
-    pub fn get_by_id(&self, id: u32) -> Option<Item> {
-        let item_val = self.item_storage.item_by_id(id)?;
-        let srs = self.srs_storage.find(id)?;
-        Some(Item { item_val, srs })
+    pub fn get_by_id(&self, id: u32) -> Option<Item<'_>> {
+        let item_val = self.backend.get_item(id)?;
+        let srs = self.backend.srs().find(id)?;
+        let boundary = self.day_boundary();
+        Some(Item {
+            item_val,
+            srs,
+            boundary,
+        })
     }
 
     /// Returns a mutable reference to the srs storage
     #[inline]
     pub fn get_srs_storage_mut(&mut self) -> &mut SRSStorage {
-        &mut self.srs_storage
+        self.backend.srs_mut()
     }
 
     /// Returns a reference to the srs storage
     #[inline]
     pub fn get_srs_storage(&self) -> &SRSStorage {
-        &self.srs_storage
+        self.backend.srs()
     }
 
     /// Returns a reference to the item storage
     #[inline]
     pub fn get_item_storage(&self) -> &ItemStorage {
-        &self.item_storage
+        self.backend.items()
     }
 
     /// Returns an iterator over all items in the storage
     #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = Item> {
-        self.srs_storage.iter().filter_map(|i| self.get_by_id(i.id))
+    pub fn iter(&self) -> impl Iterator<Item = Item<'_>> {
+        self.backend.iter_srs().filter_map(|i| self.get_by_id(i.id))
     }
 
     /// Adds a new item to the storage
     pub fn add(&mut self, literal: char) -> bool {
-        let item = self.item_storage.add_item(literal);
-        if item.is_none() {
-            return false;
-        }
-        let item = item.unwrap();
-
-        self.srs_storage.add(item.id)
+        self.backend.add_item(literal).is_some()
     }
 
     /// Removes an item from the storage
     pub fn remove(&mut self, literal: char) -> bool {
-        let item = self.item_storage.item_by_literal(literal).copied();
-        if item.is_none() {
-            return false;
+        let id = match self.backend.items().item_by_literal(literal) {
+            Some(item) => item.id,
+            None => return false,
+        };
+
+        self.backend.remove_item(id)
+    }
+
+    /// Returns a reference to the review log
+    #[inline]
+    pub fn get_review_log(&self) -> &ReviewLog {
+        self.backend.review_log()
+    }
+
+    /// Returns the reviews which haven't been undone
+    #[inline]
+    pub fn history(&self) -> History<'_> {
+        History::new(self.backend.review_log(), self.day_boundary())
+    }
+
+    /// Starts a new review session and returns its id
+    #[inline]
+    pub fn start_session(&self) -> u64 {
+        self.backend.review_log().new_session(self.now())
+    }
+
+    /// Reviews the item with the given id and adds the review to the review log. Returns `false`
+    /// if there is no such item
+    pub fn review(&mut self, id: u32, quality: RepQuality, session: u64) -> bool {
+        let settings = *self.backend.settings();
+        let boundary = self.day_boundary();
+        let now = self.now();
+        let workload = self
+            .scheduler
+            .load_balance
+            .then(|| Workload::of(self.backend.srs()));
+        let item = match self.backend.srs_mut().get_mut(id) {
+            Some(item) => item,
+            None => return false,
+        };
+
+        let before = *item;
+        item.review(
+            quality,
+            &settings,
+            &self.scheduler,
+            &boundary,
+            now,
+            workload.as_ref(),
+        );
+        let after = *item;
+
+        self.backend.review_log_mut().push(ReviewEntry {
+            item_id: id,
+            timestamp: now,
+            quality,
+            prev_interval: before.interval,
+            new_interval: after.interval,
+            ease_before: before.state.ease_factor(),
+            ease_after: after.state.ease_factor(),
+            session,
+            before: Some(before),
+            undone: false,
+        });
+
+        true
+    }
+
+    /// Undoes all reviews of the latest session which hasn't been undone yet. Returns the id of
+    /// the session and the ids of the items which have been restored
+    pub fn undo_last_session(&mut self) -> Option<(u64, Vec<u32>)> {
+        let log = self.backend.review_log();
+        let session = log.last_undoable_session()?;
+        let entries = log
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.session == session)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        Some((session, self.undo(&entries)))
+    }
+
+    /// Undoes the review at `index` in the review log. Returns the ids of the items which have
+    /// been restored
+    #[inline]
+    pub fn undo_review(&mut self, index: usize) -> Vec<u32> {
+        self.undo(&[index])
+    }
+
+    /// Undoes the reviews at `indices` in the review log by restoring the SRS data the items had
+    /// before. Later reviews of the same items get undone as well since they built upon the
+    /// undone ones. Items with reviews which can't be undone are left untouched
+    fn undo(&mut self, indices: &[usize]) -> Vec<u32> {
+        let log = self.backend.review_log();
+
+        // First review to undo of each item
+        let mut first: Vec<(u32, usize)> = vec![];
+        for index in indices {
+            let entry = match log.get(*index) {
+                Some(entry) if !entry.undone => entry,
+                _ => continue,
+            };
+            match first.iter_mut().find(|i| i.0 == entry.item_id) {
+                Some(i) => i.1 = i.1.min(*index),
+                None => first.push((entry.item_id, *index)),
+            }
+        }
+
+        // Reviews to undo and the data to restore of each item
+        let undo = first
+            .into_iter()
+            .filter_map(|(item_id, first)| {
+                let reviews = log
+                    .iter()
+                    .enumerate()
+                    .skip(first)
+                    .filter(|(_, i)| i.item_id == item_id && !i.undone)
+                    .map(|(index, i)| i.before.map(|_| index))
+                    .collect::<Option<Vec<_>>>()?;
+                let before = log.get(first)?.before?;
+                Some((item_id, before, reviews))
+            })
+            .collect::<Vec<_>>();
+
+        let mut restored = vec![];
+        for (item_id, before, reviews) in undo {
+            // Ids of items might have changed while merging or the item got removed. Its
+            // reviews stay as they are then
+            let item = match self.backend.srs_mut().get_mut(item_id) {
+                Some(item) => item,
+                None => continue,
+            };
+            *item = srs::Item {
+                id: item_id,
+                ..before
+            };
+            for index in reviews {
+                self.backend.review_log_mut().mark_undone(index);
+            }
+            restored.push(item_id);
+        }
+
+        restored
+    }
+
+    /// Returns the settings of the storage
+    #[inline]
+    pub fn get_settings(&self) -> Settings {
+        *self.backend.settings()
+    }
+
+    /// Changes the settings of the storage. The scheduler state of all items gets converted if
+    /// the scheduler changes
+    pub fn set_settings(&mut self, settings: Settings) {
+        let boundary = self.day_boundary();
+        for item in self.backend.srs_mut().iter_mut() {
+            item.convert(&settings, &self.scheduler, &boundary);
+        }
+
+        *self.backend.settings_mut() = settings;
+    }
+
+    /// Sets the day boundary due dates get calculated with. Due dates calculated with another
+    /// boundary are moved to the start of the same day under the new one. Items in a learning step
+    /// are due at an exact time and keep it
+    pub fn set_day_boundary(&mut self, boundary: DayBoundary) {
+        let settings = *self.backend.settings();
+        let old = settings.day_boundary.unwrap_or(DayBoundary::LEGACY);
+        if settings.day_boundary == Some(boundary) {
+            return;
+        }
+
+        for item in self.backend.srs_mut().iter_mut() {
+            if item.in_learning && item.due_on > 0 && item.step.is_none() {
+                item.due_on = boundary.convert(&old, item.due_on);
+            }
+        }
+
+        self.backend.settings_mut().day_boundary = Some(boundary);
+    }
+
+    /// Suspends an item by its literal. Returns `false` if there is no such item
+    pub fn suspend(&mut self, literal: char) -> bool {
+        let now = self.now();
+        self.get_srs_by_lit_mut(literal)
+            .map(|i| i.suspend(now))
+            .is_some()
+    }
+
+    /// Unsuspends an item by its literal. Returns `false` if there is no such item or it wasn't
+    /// suspended
+    pub fn unsuspend(&mut self, literal: char) -> bool {
+        let now = self.now();
+        let boundary = self.day_boundary();
+        self.get_srs_by_lit_mut(literal)
+            .map(|i| i.unsuspend(now, &boundary))
+            .unwrap_or(false)
+    }
+
+    /// Skips an item by its literal until the next day starts. Returns `false` if there is no
+    /// such item
+    pub fn bury(&mut self, literal: char) -> bool {
+        let tomorrow = self.day_offset(1);
+        self.get_srs_by_lit_mut(literal)
+            .map(|i| i.bury(tomorrow))
+            .is_some()
+    }
+
+    /// Returns the SRS data of an item by its literal, mutable
+    fn get_srs_by_lit_mut(&mut self, literal: char) -> Option<&mut srs::Item> {
+        let id = self.backend.items().item_by_literal(literal)?.id;
+        self.backend.srs_mut().get_mut(id)
+    }
+
+    /// Returns all items with their SRS data and the reviews which haven't been undone in a
+    /// format independent of the database
+    pub fn export(&self) -> Export {
+        let boundary = self.day_boundary();
+        let items = self.backend.items();
+        let mut kanji = self
+            .backend
+            .iter_srs()
+            .filter_map(|i| {
+                Some(ExportedKanji::new(
+                    self.get_by_id(i.id)?.get_literal(),
+                    i,
+                    &boundary,
+                ))
+            })
+            .collect::<Vec<_>>();
+        kanji.sort_by_key(|i| i.id);
+
+        let reviews = self
+            .backend
+            .review_log()
+            .iter()
+            .filter(|i| !i.undone)
+            .filter_map(|i| Some(ExportedReview::new(items.item_by_id(i.item_id)?.literal, i)))
+            .collect();
+
+        Export {
+            version: export::EXPORT_VERSION,
+            kanji,
+            reviews,
         }
-        let item = item.unwrap();
+    }
 
-        let success =
-            self.item_storage.remove_item(item.id) && self.srs_storage.remove(item.id).is_some();
+    /// Imports the kanji of `export`. Kanji already in the storage are matched by their literal
+    /// and handled according to `policy`. Reviews of added or updated kanji are added to the
+    /// review log unless they're in it already. Nothing gets imported if any record is invalid
+    pub fn import(
+        &mut self,
+        export: &Export,
+        policy: ImportPolicy,
+    ) -> error::Result<ImportSummary> {
+        let boundary = self.day_boundary();
+        let kanji = export
+            .kanji
+            .iter()
+            .map(|i| Ok((i.literal, i.to_item(0, &boundary)?)))
+            .collect::<error::Result<Vec<_>>>()?;
+        let reviews = export
+            .reviews
+            .iter()
+            .map(|i| Ok((i.literal, i.to_entry(0)?)))
+            .collect::<error::Result<Vec<_>>>()?;
 
-        success
+        // Latest review of each kanji in the import
+        let mut last_reviews = HashMap::new();
+        for (literal, entry) in &reviews {
+            let last = last_reviews.entry(*literal).or_insert(entry.timestamp);
+            *last = entry.timestamp.max(*last);
+        }
+
+        let mut summary = ImportSummary::default();
+        let mut imported = HashSet::new();
+        for (literal, item) in kanji {
+            let existing = self.backend.items().item_by_literal(literal).map(|i| i.id);
+            let id = match existing {
+                Some(id) => {
+                    let replace = match (policy, self.backend.srs().find(id)) {
+                        (ImportPolicy::Overwrite, _) | (_, None) => true,
+                        (ImportPolicy::Newer, Some(_)) => {
+                            let logged = self
+                                .backend
+                                .review_log()
+                                .iter()
+                                .filter(|i| i.item_id == id && !i.undone)
+                                .map(|i| i.timestamp)
+                                .max();
+                            // Reviewed in the import but not in the database counts as newer
+                            last_reviews.get(&literal).copied() > logged
+                        }
+                        (ImportPolicy::Skip, Some(_)) => false,
+                    };
+                    if !replace {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    summary.updated += 1;
+                    id
+                }
+                None => match self.backend.add_item(literal) {
+                    Some(id) => {
+                        summary.added += 1;
+                        id
+                    }
+                    None => continue,
+                },
+            };
+
+            self.backend.srs_mut().add(id);
+            if let Some(srs) = self.backend.srs_mut().get_mut(id) {
+                *srs = srs::Item { id, ..item };
+            }
+            imported.insert(literal);
+        }
+
+        for (literal, entry) in reviews {
+            if !imported.contains(&literal) {
+                continue;
+            }
+            let item_id = match self.backend.items().item_by_literal(literal) {
+                Some(item) => item.id,
+                None => continue,
+            };
+            let logged = self
+                .backend
+                .review_log()
+                .iter()
+                .any(|i| i.item_id == item_id && i.timestamp == entry.timestamp);
+            if logged {
+                continue;
+            }
+
+            self.backend
+                .review_log_mut()
+                .push(ReviewEntry { item_id, ..entry });
+            summary.reviews += 1;
+        }
+
+        Ok(summary)
     }
 
     /// Resets an item by its literal
@@ -148,7 +728,7 @@ impl Storage {
     /// Returns the amount of items in the storage
     #[inline]
     pub fn len(&self) -> usize {
-        self.item_storage.len()
+        self.backend.items().len()
     }
 
     /// Returns `true` if the storage is empty
@@ -160,7 +740,12 @@ impl Storage {
     /// Returns the amount of kanji currently in learning
     #[inline]
     pub fn learning_kanji(&self) -> usize {
-        self.srs_storage.iter().filter(|i| i.in_learning).count()
+        self.backend.iter_srs().filter(|i| i.in_learning).count()
+    }
+
+    /// Returns the amount of suspended kanji
+    pub fn suspended_kanji(&self) -> usize {
+        self.backend.iter_srs().filter(|i| i.suspended).count()
     }
 
     /// Returns `true` if the storage is properly built or empty and `false` if there is data corruption
@@ -169,25 +754,26 @@ impl Storage {
             return true;
         }
 
-        if self.item_storage.is_empty() || self.srs_storage.is_empty() {
+        let item_storage = self.backend.items();
+        let srs_storage = self.backend.srs();
+
+        if item_storage.is_empty() || srs_storage.is_empty() {
             return false;
         }
 
         // check item_storage is subset of srs_storage
-        let is_subset_of_srs = !self
-            .item_storage
+        let is_subset_of_srs = !item_storage
             .iter()
-            .any(|item| !self.srs_storage.find(item.id).is_some());
+            .any(|item| srs_storage.find(item.id).is_none());
 
         if !is_subset_of_srs {
             return false;
         }
 
         // check srs_storage is subset of item_storage
-        let srs_subset_of_is = !self
-            .srs_storage
+        let srs_subset_of_is = !srs_storage
             .iter()
-            .any(|i| !self.item_storage.item_by_id(i.id).is_some());
+            .any(|i| item_storage.item_by_id(i.id).is_none());
 
         if !srs_subset_of_is {
             return false;
@@ -203,27 +789,40 @@ impl Storage {
             return false;
         }
 
-        if self.srs_storage.is_empty() {
+        if self.backend.srs().is_empty() {
             // Fill SRS with new empty values
             return self.repair_srs();
         }
 
-        if self.item_storage.is_empty() {
-            // Pray to god this won't happen
-            return true;
+        // Drop SRS data of items which don't exist anymore
+        let orphaned = self
+            .backend
+            .iter_srs()
+            .filter(|i| self.backend.get_item(i.id).is_none())
+            .map(|i| i.id)
+            .collect::<Vec<_>>();
+        for id in orphaned {
+            self.backend.srs_mut().remove(id);
         }
 
-        // TODO: add functionality to merge both and drop broken data in order to continue
-        // with items that aren't broken
+        // Add SRS data for items which don't have one
+        self.repair_srs();
 
-        false
+        self.check()
     }
 
     /// Fills SRS storage with empty values for kanji in order to have both structures being balanced
     fn repair_srs(&mut self) -> bool {
+        let ids = self
+            .backend
+            .items()
+            .iter()
+            .map(|i| i.id)
+            .collect::<Vec<_>>();
+
         let mut updated = 0;
-        for i in self.item_storage.iter() {
-            let added = self.srs_storage.add(i.id);
+        for id in ids {
+            let added = self.backend.srs_mut().add(id);
             if added {
                 updated += 1;
             }
@@ -231,15 +830,517 @@ impl Storage {
         updated > 0
     }
 
-    fn save(&self) {
-        self.item_storage.save();
-        self.srs_storage.save();
+    /// Writes the storage using its backend. If another instance has written the storage in the
+    /// meantime, the local changes get merged into its data instead of overwriting them
+    fn save(&mut self) -> error::Result<()> {
+        let local = Snapshot::of(self.backend.as_ref());
+        if local == self.base {
+            return Ok(());
+        }
+
+        let _guard = self.lock()?;
+
+        if self.backend.changed()? {
+            self.backend.load()?;
+            merge::merge(&self.base, &local, self.backend.as_mut());
+        }
+
+        self.backend.save()?;
+        self.base = Snapshot::of(self.backend.as_ref());
+        Ok(())
+    }
+
+    /// Locks the storage for other instances until the returned guard gets dropped
+    fn lock(&self) -> error::Result<Option<LockGuard>> {
+        match &self.lock_file {
+            Some(file) => Ok(Some(lock(&LockPath::FullPath(file))?)),
+            None => Ok(None),
+        }
     }
 }
 
 impl Drop for Storage {
     #[inline]
     fn drop(&mut self) {
-        self.save()
+        self.save().expect("Failed to save storage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::SchedulerConfig, time::FixedClock, utils::DAY_SECS};
+    use chrono::TimeZone;
+    use chrono_tz::Europe::Berlin;
+    use settings::SchedulerKind;
+    use std::rc::Rc;
+
+    const HOUR_SECS: u64 = 60 * 60;
+
+    #[test]
+    fn add_remove() {
+        let mut storage = Storage::in_memory();
+        assert!(storage.add('日'));
+        assert!(storage.add('月'));
+        assert!(!storage.add('日'));
+        assert_eq!(2, storage.len());
+
+        assert!(storage.remove('日'));
+        assert!(!storage.remove('日'));
+        assert!(storage.get_by_lit('日').is_none());
+        assert_eq!(1, storage.len());
+        assert!(storage.check());
+    }
+
+    #[test]
+    fn migrate_bincode() {
+        let dir = std::env::temp_dir().join(format!("kanji_srs_migrate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut old = BincodeBackend::open(&dir).unwrap();
+        old.add_item('日');
+        old.save().unwrap();
+
+        let storage = Storage::open(&dir).unwrap();
+        assert!(storage.migrated());
+        assert!(storage.get_by_lit('日').is_some());
+        drop(storage);
+        assert!(!Storage::open(&dir).unwrap().migrated());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_save() {
+        let dir = std::env::temp_dir().join(format!("kanji_srs_concurrent_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut storage = Storage::open(&dir).unwrap();
+        for lit in "日月火".chars() {
+            storage.add(lit);
+        }
+        drop(storage);
+
+        // This instance still holds its changes when the other one saves
+        let mut a = Storage::open(&dir).unwrap();
+        a.add('水');
+        let session = a.start_session();
+        a.review(1, RepQuality::Grade4, session);
+
+        let mut b = Storage::open(&dir).unwrap();
+        b.add('木');
+        b.remove('月');
+        let session = b.start_session();
+        b.review(3, RepQuality::Grade4, session);
+        drop(b);
+        drop(a);
+
+        let storage = Storage::open(&dir).unwrap();
+        assert!(storage.check());
+        let literals = storage
+            .get_srs_storage()
+            .iter()
+            .map(|i| storage.get_by_id(i.id).unwrap().get_literal())
+            .collect::<HashSet<_>>();
+        assert_eq!("日火木水".chars().collect::<HashSet<_>>(), literals);
+        for lit in ['日', '火'] {
+            let id = storage.get_by_lit(lit).unwrap().get_id();
+            assert!(storage.get_srs_storage().find(id).unwrap().in_learning);
+        }
+        assert_eq!(2, storage.get_review_log().len());
+        drop(storage);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reset() {
+        let mut storage = Storage::in_memory();
+        storage.add('日');
+        let id = storage.get_by_lit('日').unwrap().get_id();
+        storage.get_srs_mut(id).unwrap().review(
+            RepQuality::Grade5,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            0,
+            None,
+        );
+        assert_eq!(1, storage.learning_kanji());
+
+        assert!(storage.reset('日'));
+        assert_eq!(0, storage.learning_kanji());
+        assert!(!storage.reset('月'));
+    }
+
+    #[test]
+    fn review_log() {
+        let mut storage = Storage::in_memory();
+        storage.set_clock(FixedClock::new(1_650_000_000));
+        storage.add('日');
+        storage.add('月');
+
+        // Easy items skip the learning steps
+        let session = storage.start_session();
+        assert!(storage.review(1, RepQuality::Grade5, session));
+        assert!(storage.review(2, RepQuality::Grade2, session));
+        assert!(!storage.review(3, RepQuality::Grade4, session));
+        assert!(storage.start_session() > session);
+
+        let session = storage.start_session();
+        assert!(storage.review(1, RepQuality::Grade5, session));
+
+        let log = storage.get_review_log().iter().collect::<Vec<_>>();
+        assert_eq!(3, log.len());
+        assert_eq!((0, 1), (log[0].prev_interval, log[0].new_interval));
+        // SM-2 schedules 6 days, fuzzed into 4 to 8 days
+        assert_eq!((1, 7), (log[2].prev_interval, log[2].new_interval));
+        assert_eq!(2.6, log[2].ease_before);
+        assert_eq!(2.7, log[2].ease_after);
+        assert_eq!(session, log[2].session);
+    }
+
+    #[test]
+    fn undo() {
+        let mut storage = Storage::in_memory();
+        storage.add('日');
+        storage.add('月');
+        storage.add('火');
+        let new = *storage.get_srs_storage().find(1).unwrap();
+
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade5, session);
+        storage.review(2, RepQuality::Grade5, session);
+        let reviewed = *storage.get_srs_storage().find(2).unwrap();
+        let session = storage.start_session();
+        storage.review(2, RepQuality::Grade1, session);
+        storage.review(3, RepQuality::Grade5, session);
+
+        assert_eq!(Some((session, vec![2, 3])), storage.undo_last_session());
+        assert_eq!(reviewed, *storage.get_srs_storage().find(2).unwrap());
+        assert!(!storage.get_srs_storage().find(3).unwrap().in_learning);
+
+        // Undoing a review undoes the later ones of the same item as well
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+        assert_eq!(vec![1], storage.undo_review(0));
+        assert_eq!(new, *storage.get_srs_storage().find(1).unwrap());
+        assert!(storage.undo_review(0).is_empty());
+
+        let log = storage.get_review_log();
+        let undone = log.iter().map(|i| i.undone).collect::<Vec<_>>();
+        assert_eq!(vec![true, false, true, true, true], undone);
+        assert_eq!(
+            Some(log.get(1).unwrap().session),
+            log.last_undoable_session()
+        );
+
+        // Reviews of removed items can't be undone
+        let session = storage.start_session();
+        storage.review(3, RepQuality::Grade5, session);
+        storage.remove('火');
+        assert_eq!(Some((session, vec![])), storage.undo_last_session());
+        assert!(!storage.get_review_log().get(5).unwrap().undone);
+    }
+
+    #[test]
+    fn switch_to_fsrs() {
+        let mut storage = Storage::in_memory();
+        storage.set_clock(FixedClock::new(1_650_000_000));
+        storage.add('日');
+        storage.add('月');
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+
+        let fsrs = Settings {
+            scheduler: SchedulerKind::FSRS,
+            ..Settings::default()
+        };
+        storage.set_settings(fsrs);
+        let state = storage.get_srs_storage().find(1).unwrap().state;
+        assert!(matches!(state, SchedulerState::FSRS(i) if i.memory.is_some()));
+        let state = storage.get_srs_storage().find(2).unwrap().state;
+        assert_eq!(SchedulerState::new(&fsrs), state);
+
+        // New item rated 'Good' has a stability of ~3.7 days. 4 days get fuzzed into 3 to 5
+        storage.review(2, RepQuality::Grade4, session);
+        assert_eq!(4, storage.get_srs_storage().find(2).unwrap().interval);
+
+        storage.set_settings(Settings::default());
+        let state = storage.get_srs_storage().find(2).unwrap().state;
+        assert!(matches!(state, SchedulerState::SM2(_)));
+    }
+
+    #[test]
+    fn renormalize_due_dates() {
+        use chrono::{TimeZone, Timelike};
+        use chrono_tz::{Asia::Tokyo, Europe::Berlin, UTC};
+
+        let mut storage = Storage::in_memory();
+        storage.add('日');
+        storage.add('月');
+        let legacy_due = UTC.ymd(2022, 3, 27).and_hms(4, 0, 0).timestamp() as u64;
+        let item = storage.get_srs_mut(1).unwrap();
+        item.in_learning = true;
+        item.due_on = legacy_due;
+
+        let berlin = DayBoundary::new(Berlin, 4);
+        storage.set_day_boundary(berlin);
+        let due = storage.get_srs_storage().find(1).unwrap().due_on;
+        assert_eq!(
+            Berlin.ymd(2022, 3, 27).and_hms(4, 0, 0).timestamp() as u64,
+            due
+        );
+        assert_eq!(0, storage.get_srs_storage().find(2).unwrap().due_on);
+
+        // Setting the same boundary again doesn't change anything
+        storage.set_day_boundary(berlin);
+        assert_eq!(due, storage.get_srs_storage().find(1).unwrap().due_on);
+
+        storage.set_day_boundary(DayBoundary::new(Tokyo, 6));
+        let due = storage.get_srs_storage().find(1).unwrap().due_on;
+        assert_eq!(
+            Tokyo.ymd(2022, 3, 27).and_hms(6, 0, 0).timestamp() as u64,
+            due
+        );
+        assert_eq!(
+            Some(DayBoundary::new(Tokyo, 6)),
+            storage.get_settings().day_boundary
+        );
+
+        // Due dates are calculated with the boundary of the storage
+        storage.set_clock(FixedClock::new(due - 60));
+        assert_eq!(0, storage.get_due().count());
+        storage.set_clock(FixedClock::new(due));
+        assert_eq!(1, storage.get_due().count());
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+        let due = storage.get_srs_storage().find(1).unwrap().due_on;
+        assert_eq!(6, Tokyo.timestamp(due as i64, 0).hour());
+    }
+
+    #[test]
+    fn repair() {
+        let mut storage = Storage::in_memory();
+        storage.add('日');
+        storage.add('月');
+        storage.get_srs_storage_mut().remove(1);
+        storage.get_srs_storage_mut().add(5);
+        assert!(!storage.check());
+
+        assert!(storage.repair());
+        assert!(storage.check());
+        assert!(storage.get_by_lit('日').is_some());
+        assert!(storage.get_srs_storage().find(5).is_none());
+    }
+
+    fn berlin_time(y: i32, m: u32, d: u32, h: u32, min: u32) -> u64 {
+        Berlin.ymd(y, m, d).and_hms(h, min, 0).timestamp() as u64
+    }
+
+    /// Storage with the kanji `literals` and a clock set to `now`. Uses Berlin time with days
+    /// starting at 04:00, no learning steps and exact intervals
+    fn scenario_storage(literals: &str, now: u64) -> (Storage, Rc<FixedClock>) {
+        let mut storage = Storage::in_memory();
+        let clock = Rc::new(FixedClock::new(now));
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::new(Berlin, 4));
+        storage.set_scheduler_config(SchedulerConfig {
+            learning_steps: vec![],
+            relearning_steps: vec![],
+            fuzz: false,
+            ..SchedulerConfig::default()
+        });
+        for literal in literals.chars() {
+            storage.add(literal);
+        }
+        (storage, clock)
+    }
+
+    /// Reviews all due and `new` new items with `grade`. Returns the literals of the reviewed items
+    fn review_all(storage: &mut Storage, new: usize, grade: RepQuality) -> String {
+        let ids = storage
+            .get_due()
+            .map(|i| i.id)
+            .chain(storage.get_new().map(|i| i.id).take(new))
+            .collect::<Vec<_>>();
+
+        let session = storage.start_session();
+        let mut literals = String::new();
+        for id in ids {
+            literals.push(storage.get_by_id(id).unwrap().get_literal());
+            storage.review(id, grade, session);
+        }
+        literals
+    }
+
+    fn due_literals(storage: &Storage) -> String {
+        let mut due = storage.get_due().map(|i| i.id).collect::<Vec<_>>();
+        due.sort_unstable();
+        due.into_iter()
+            .map(|i| storage.get_by_id(i).unwrap().get_literal())
+            .collect()
+    }
+
+    #[test]
+    fn due_after_rollover() {
+        let (mut storage, clock) = scenario_storage("日月", berlin_time(2022, 3, 20, 10, 0));
+        assert_eq!("日月", review_all(&mut storage, 2, RepQuality::Grade4));
+        assert_eq!("", due_literals(&storage));
+
+        // Late at night still belongs to the day of the session
+        clock.set(berlin_time(2022, 3, 21, 3, 59));
+        assert_eq!("", due_literals(&storage));
+
+        clock.set(berlin_time(2022, 3, 21, 4, 0));
+        assert_eq!("日月", due_literals(&storage));
+    }
+
+    #[test]
+    fn interval_across_dst() {
+        let (mut storage, clock) = scenario_storage("日", berlin_time(2022, 3, 20, 10, 0));
+        review_all(&mut storage, 1, RepQuality::Grade4);
+        clock.set(berlin_time(2022, 3, 21, 22, 0));
+        review_all(&mut storage, 0, RepQuality::Grade4);
+
+        // Clocks jump forward on 2022-03-27 but the day still starts at 04:00 local time
+        let item = storage.get_srs_storage().find(1).unwrap();
+        assert_eq!(6, item.interval);
+        assert_eq!(berlin_time(2022, 3, 27, 4, 0), item.due_on);
+
+        clock.set(berlin_time(2022, 3, 27, 3, 30));
+        assert_eq!("", due_literals(&storage));
+        clock.advance(HOUR_SECS);
+        assert_eq!("日", due_literals(&storage));
+    }
+
+    #[test]
+    fn month_of_daily_sessions() {
+        let (mut storage, clock) =
+            scenario_storage("一二三四五六七八九十", berlin_time(2022, 10, 1, 20, 0));
+
+        let mut reviewed = vec![];
+        for _ in 0..30 {
+            reviewed.push(review_all(&mut storage, 2, RepQuality::Grade4));
+            clock.advance(DAY_SECS);
+        }
+
+        // Two new kanji each day until all have been learned
+        assert_eq!("一二", reviewed[0]);
+        assert_eq!("一二三四", reviewed[1]);
+        assert_eq!("七八九十", reviewed[4]);
+        assert_eq!("九十", reviewed[5]);
+        assert_eq!("", reviewed[6]);
+
+        // Every kanji is reviewed after 1, 6 and 15 days, even across the end of DST on 10-30
+        let log = storage.get_review_log();
+        let boundary = storage.day_boundary();
+        for id in 1..=10 {
+            let days = log
+                .iter()
+                .filter(|i| i.item_id == id)
+                .map(|i| boundary.date_of(i.timestamp))
+                .collect::<Vec<_>>();
+            let gaps = days
+                .windows(2)
+                .map(|i| (i[1] - i[0]).num_days())
+                .collect::<Vec<_>>();
+            assert_eq!(&[1, 6, 15][..gaps.len()], &gaps[..], "item {id}");
+            assert!(gaps.len() >= 2, "item {id}");
+        }
+    }
+
+    #[test]
+    fn failed_items_come_back_next_day() {
+        let (mut storage, clock) = scenario_storage("日月", berlin_time(2022, 6, 1, 12, 0));
+        review_all(&mut storage, 2, RepQuality::Grade4);
+        clock.advance(DAY_SECS);
+
+        let id = storage.get_by_lit('月').unwrap().get_id();
+        let session_id = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session_id);
+        storage.review(id, RepQuality::Grade1, session_id);
+
+        clock.advance(DAY_SECS);
+        assert_eq!("月", due_literals(&storage));
+    }
+
+    /// Storage with the kanji 日, 月 and 火 and a clock set to `now`. Uses Berlin time with days
+    /// starting at 04:00 and no learning steps
+    fn storage_at(now: u64) -> (Storage, Rc<FixedClock>) {
+        let mut storage = Storage::in_memory();
+        let clock = Rc::new(FixedClock::new(now));
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::new(Berlin, 4));
+        storage.set_scheduler_config(SchedulerConfig {
+            learning_steps: vec![],
+            relearning_steps: vec![],
+            ..SchedulerConfig::default()
+        });
+        for lit in ['日', '月', '火'] {
+            storage.add(lit);
+        }
+        (storage, clock)
+    }
+
+    fn due_ids(storage: &Storage) -> Vec<u32> {
+        storage.get_due().map(|i| i.id).collect()
+    }
+
+    fn new_ids(storage: &Storage) -> Vec<u32> {
+        storage.get_new().map(|i| i.id).collect()
+    }
+
+    #[test]
+    fn suspending_freezes_schedule() {
+        // 2022-03-10 12:00 in Berlin
+        let (mut storage, clock) = storage_at(1_646_910_000);
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade5, session);
+        let due_on = storage.get_srs_storage().find(1).unwrap().due_on;
+        assert_eq!(storage.day_offset(1), due_on);
+
+        assert!(storage.suspend('日'));
+        assert!(storage.suspend('月'));
+        assert_eq!(vec![3], new_ids(&storage));
+
+        // Suspended kanji don't show up even though they are overdue
+        clock.advance(10 * DAY_SECS);
+        assert!(due_ids(&storage).is_empty());
+        assert!(!storage.get_by_id(1).unwrap().can_be_reviewed(storage.now()));
+
+        // The schedule continues where it has been stopped
+        assert!(storage.unsuspend('日'));
+        assert!(!storage.unsuspend('日'));
+        let item = storage.get_srs_storage().find(1).unwrap();
+        assert!(!item.suspended);
+        assert_eq!(storage.day_offset(1), item.due_on);
+        assert!(due_ids(&storage).is_empty());
+        clock.advance(DAY_SECS);
+        assert_eq!(vec![1], due_ids(&storage));
+
+        assert!(storage.unsuspend('月'));
+        assert_eq!(vec![2, 3], new_ids(&storage));
+    }
+
+    #[test]
+    fn burying_skips_until_tomorrow() {
+        // 2022-03-10 23:00 in Berlin, the day ends at 4:00
+        let (mut storage, clock) = storage_at(1_646_949_600);
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade4, session);
+        clock.advance(DAY_SECS);
+        assert_eq!(vec![1], due_ids(&storage));
+
+        assert!(storage.bury('日'));
+        assert!(storage.bury('月'));
+        assert!(!storage.bury('木'));
+        assert!(due_ids(&storage).is_empty());
+        assert_eq!(vec![3], new_ids(&storage));
+
+        // Buried kanji are back once the next day has started
+        clock.advance(4 * 3600);
+        assert!(due_ids(&storage).is_empty());
+        clock.advance(3600);
+        assert_eq!(vec![1], due_ids(&storage));
+        assert_eq!(vec![2, 3], new_ids(&storage));
     }
 }
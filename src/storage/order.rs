@@ -0,0 +1,286 @@
+//! Orders in which new items get introduced and due items get reviewed
+
+use super::{info::InfoCache, srs, ItemStorage};
+use crate::{
+    config::{NewOrder, ReviewOrder},
+    jotoba::KanjiInfo,
+    time::DayBoundary,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::collections::{HashMap, HashSet};
+
+/// Sorts the new items `new` by `order`. Items are expected to be in the order they have been
+/// added and keep it among each other if `order` doesn't tell them apart. Items without
+/// information in `info` come last. `seed` determines the random order
+pub fn sort_new(
+    new: &mut Vec<&srs::Item>,
+    order: NewOrder,
+    items: &ItemStorage,
+    info: &InfoCache,
+    seed: u64,
+) {
+    let literals = items
+        .iter()
+        .map(|i| (i.id, i.literal))
+        .collect::<HashMap<_, _>>();
+    let info_of = |item: &srs::Item| -> Option<&KanjiInfo> {
+        literals.get(&item.id).and_then(|i| info.get(*i))
+    };
+
+    let key: fn(&KanjiInfo) -> Option<u32> = match order {
+        NewOrder::Added => return,
+        NewOrder::Random => {
+            new.shuffle(&mut StdRng::seed_from_u64(seed));
+            return;
+        }
+        NewOrder::Components => {
+            *new = components_first(std::mem::take(new), &literals, info);
+            return;
+        }
+        NewOrder::Strokes => |i| i.stroke_count.map(u32::from),
+        NewOrder::Frequency => |i| i.frequency.map(u32::from),
+        NewOrder::Grade => |i| i.grade.map(u32::from),
+        // N5 is the lowest level
+        NewOrder::Jlpt => |i| i.jlpt.map(|i| 5u32.saturating_sub(i as u32)),
+    };
+
+    new.sort_by_cached_key(|i| info_of(i).and_then(key).unwrap_or(u32::MAX));
+}
+
+/// Sorts the due items `due` by `order`. Items are expected in the order returned by
+/// [`srs::SRSStorage::get_due`]. Items in learning steps stay in front of the others. Days
+/// start at `boundary`
+pub fn sort_due(
+    due: &mut [&srs::Item],
+    order: ReviewOrder,
+    boundary: &DayBoundary,
+    now: u64,
+    seed: u64,
+) {
+    let split = due.iter().take_while(|i| i.step.is_some()).count();
+    let due = &mut due[split..];
+
+    match order {
+        ReviewOrder::Added => (),
+        ReviewOrder::Overdue => due.sort_by_key(|i| i.due_on),
+        ReviewOrder::Retrievability => due.sort_by(|a, b| {
+            a.retrievability(now, boundary)
+                .total_cmp(&b.retrievability(now, boundary))
+        }),
+        ReviewOrder::Ease => {
+            due.sort_by(|a, b| a.state.ease_factor().total_cmp(&b.state.ease_factor()))
+        }
+        ReviewOrder::Random => due.shuffle(&mut StdRng::seed_from_u64(seed)),
+    }
+}
+
+/// Orders `new` so that kanji come after the kanji they are built from which are new as well.
+/// Otherwise the order is kept
+fn components_first<'a>(
+    mut new: Vec<&'a srs::Item>,
+    literals: &HashMap<u32, char>,
+    info: &InfoCache,
+) -> Vec<&'a srs::Item> {
+    let literal_of = |item: &srs::Item| literals.get(&item.id).copied().unwrap_or_default();
+    let mut pending = new.iter().map(|i| literal_of(i)).collect::<HashSet<_>>();
+
+    let mut ordered = Vec::with_capacity(new.len());
+    while !new.is_empty() {
+        let ready = new
+            .iter()
+            .position(|item| {
+                let literal = literal_of(item);
+                info.get(literal)
+                    .is_none_or(|i| i.components().all(|c| !pending.contains(&c)))
+            })
+            // Kanji which are built from each other
+            .unwrap_or(0);
+
+        let item = new.remove(ready);
+        pending.remove(&literal_of(item));
+        ordered.push(item);
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::SchedulerConfig, sm2::RepQuality, storage::Storage, time::FixedClock,
+        utils::DAY_SECS,
+    };
+    use chrono_tz::Europe::Berlin;
+    use std::rc::Rc;
+
+    fn info(literal: char, strokes: u8, parts: &str) -> KanjiInfo {
+        KanjiInfo {
+            literal,
+            stroke_count: Some(strokes),
+            parts: parts.chars().map(|i| i.to_string()).collect(),
+            ..KanjiInfo::default()
+        }
+    }
+
+    fn sorted(order: NewOrder) -> String {
+        let mut items = ItemStorage::new();
+        for literal in "明日月木林森休".chars() {
+            items.add_item(literal);
+        }
+        let info = InfoCache::from_infos([
+            info('明', 8, "日月"),
+            info('日', 4, ""),
+            info('月', 4, ""),
+            info('木', 4, ""),
+            info('林', 8, "木"),
+            info('森', 12, "木林"),
+        ]);
+
+        let srs = items
+            .iter()
+            .map(|i| srs::Item::new(i.id))
+            .collect::<Vec<_>>();
+        let mut new = srs.iter().collect::<Vec<_>>();
+        sort_new(&mut new, order, &items, &info, 0);
+        new.into_iter()
+            .filter_map(|i| items.item_by_id(i.id))
+            .map(|i| i.literal)
+            .collect()
+    }
+
+    #[test]
+    fn by_info() {
+        assert_eq!("明日月木林森休", sorted(NewOrder::Added));
+        assert_eq!("日月木明林森休", sorted(NewOrder::Strokes));
+    }
+
+    #[test]
+    fn components_first() {
+        assert_eq!("日月明木林森休", sorted(NewOrder::Components));
+    }
+
+    #[test]
+    fn steps_stay_first() {
+        let mut items = (1..=3).map(srs::Item::new).collect::<Vec<_>>();
+        items[0].step = Some(srs::LearningStep {
+            index: 0,
+            relearning: true,
+        });
+        items[0].due_on = 300;
+        items[1].due_on = 200;
+        items[2].due_on = 100;
+
+        let mut due = items.iter().collect::<Vec<_>>();
+        sort_due(&mut due, ReviewOrder::Overdue, &DayBoundary::LEGACY, 400, 0);
+        assert_eq!(vec![1, 3, 2], due.iter().map(|i| i.id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn random_per_seed() {
+        let random = sorted(NewOrder::Random);
+        assert_eq!(random, sorted(NewOrder::Random));
+        let mut chars = random.chars().collect::<Vec<_>>();
+        chars.sort_unstable();
+        let mut added = sorted(NewOrder::Added).chars().collect::<Vec<_>>();
+        added.sort_unstable();
+        assert_eq!(added, chars);
+    }
+
+    /// Storage with the kanji 一 to 十 where 一 to 五 have been learned on the first day and
+    /// 六 to 十 a week later
+    fn backlog() -> (Storage, Rc<FixedClock>) {
+        let mut storage = Storage::in_memory();
+        // 2022-03-10 12:00 in Berlin
+        let clock = Rc::new(FixedClock::new(1_646_910_000));
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::new(Berlin, 4));
+        storage.set_scheduler_config(SchedulerConfig {
+            learning_steps: vec![],
+            relearning_steps: vec![],
+            ..SchedulerConfig::default()
+        });
+        for lit in "一二三四五六七八九十".chars() {
+            storage.add(lit);
+        }
+
+        let session = storage.start_session();
+        for id in 1..=5 {
+            storage.review(id, RepQuality::Grade4, session);
+        }
+        clock.advance(7 * DAY_SECS);
+        let session = storage.start_session();
+        for id in 6..=10 {
+            let grade = if id == 8 {
+                RepQuality::Grade3
+            } else {
+                RepQuality::Grade5
+            };
+            storage.review(id, grade, session);
+        }
+
+        // Long break
+        clock.advance(30 * DAY_SECS);
+        (storage, clock)
+    }
+
+    fn due_ids(storage: &Storage, order: ReviewOrder) -> Vec<u32> {
+        storage
+            .get_due_ordered(order)
+            .into_iter()
+            .map(|i| i.id)
+            .collect()
+    }
+
+    #[test]
+    fn prioritize_backlog() {
+        let (storage, _) = backlog();
+        assert_eq!(10, storage.get_due().count());
+
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+            due_ids(&storage, ReviewOrder::Added)
+        );
+
+        let overdue = due_ids(&storage, ReviewOrder::Overdue);
+        assert_eq!(vec![1, 2, 3, 4, 5], overdue[..5].to_vec());
+
+        // Kanji due after one day have been forgotten more likely than the ones due after a week
+        let retrievability = due_ids(&storage, ReviewOrder::Retrievability);
+        assert_eq!(vec![1, 2, 3, 4, 5], retrievability[..5].to_vec());
+
+        assert_eq!(8, due_ids(&storage, ReviewOrder::Ease)[0]);
+
+        let random = due_ids(&storage, ReviewOrder::Random);
+        assert_eq!(random, due_ids(&storage, ReviewOrder::Random));
+        let mut sorted = random.clone();
+        sorted.sort_unstable();
+        assert_eq!(due_ids(&storage, ReviewOrder::Added), sorted);
+    }
+
+    #[test]
+    fn spread_backlog() {
+        let (mut storage, clock) = backlog();
+
+        // Three kanji fit into today's session, the rest is spread over three days
+        let session = storage.start_session();
+        for id in due_ids(&storage, ReviewOrder::Overdue).into_iter().take(3) {
+            storage.review(id, RepQuality::Grade4, session);
+        }
+        assert_eq!(7, storage.spread_due(3, ReviewOrder::Overdue));
+        assert_eq!(0, storage.get_due().count());
+
+        let mut per_day = vec![];
+        for _ in 0..3 {
+            clock.advance(DAY_SECS);
+            let due = due_ids(&storage, ReviewOrder::Overdue);
+            per_day.push(due.len());
+            let session = storage.start_session();
+            for id in due {
+                storage.review(id, RepQuality::Grade4, session);
+            }
+        }
+
+        assert_eq!(vec![3, 3, 1], per_day);
+    }
+}
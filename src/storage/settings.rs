@@ -0,0 +1,54 @@
+use crate::{fsrs::DEFAULT_RETENTION, time::DayBoundary};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// Settings stored along with the data of a database
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// Algorithm used to schedule reviews
+    pub scheduler: SchedulerKind,
+    /// Probability of recalling an item when it is due. Only used by FSRS
+    pub desired_retention: f64,
+    /// Day boundary the due dates have been calculated with. `None` for databases of versions
+    /// which always used 04:00 UTC
+    pub day_boundary: Option<DayBoundary>,
+}
+
+/// Available scheduling algorithms
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerKind {
+    SM2,
+    FSRS,
+}
+
+impl Default for Settings {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            scheduler: SchedulerKind::SM2,
+            desired_retention: DEFAULT_RETENTION,
+            day_boundary: None,
+        }
+    }
+}
+
+impl Display for SchedulerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerKind::SM2 => write!(f, "sm2"),
+            SchedulerKind::FSRS => write!(f, "fsrs"),
+        }
+    }
+}
+
+impl FromStr for SchedulerKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sm2" | "sm-2" => Ok(SchedulerKind::SM2),
+            "fsrs" => Ok(SchedulerKind::FSRS),
+            _ => Err(()),
+        }
+    }
+}
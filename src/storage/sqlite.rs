@@ -0,0 +1,556 @@
+//! SQLite storage backend. Items and their SRS data live in two related tables which are always
+//! written within a single transaction so they can't get out of sync.
+
+use super::{
+    backend::StorageBackend,
+    error::{Error, Result},
+    info::InfoCache,
+    item,
+    log::{ReviewEntry, ReviewLog},
+    settings::Settings,
+    srs::{self, LearningStep},
+    ItemStorage, SRSStorage,
+};
+use crate::{jotoba::KanjiInfo, scheduler::SchedulerState, sm2::RepQuality};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{path::Path, str::FromStr};
+
+/// Name of the database file inside the storage directory
+pub const DB_FILE: &str = "kanji_srs.db";
+
+/// Version of the database layout, kept in `PRAGMA user_version`
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS items (
+    id INTEGER PRIMARY KEY,
+    literal TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS srs (
+    item_id INTEGER PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+    scheduler TEXT NOT NULL,
+    state TEXT NOT NULL,
+    due_on INTEGER NOT NULL,
+    in_learning INTEGER NOT NULL,
+    interval INTEGER NOT NULL,
+    step INTEGER,
+    relearning INTEGER NOT NULL,
+    lapses INTEGER NOT NULL,
+    leech INTEGER NOT NULL,
+    suspended INTEGER NOT NULL,
+    suspended_on INTEGER NOT NULL,
+    buried_until INTEGER NOT NULL,
+    drill INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS review_log (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    quality INTEGER NOT NULL,
+    prev_interval INTEGER NOT NULL,
+    new_interval INTEGER NOT NULL,
+    ease_before REAL NOT NULL,
+    ease_after REAL NOT NULL,
+    session INTEGER NOT NULL,
+    before TEXT,
+    undone INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS review_log_item ON review_log (item_id);
+CREATE TABLE IF NOT EXISTS kanji_info (
+    literal TEXT PRIMARY KEY,
+    info TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// Key in the `meta` table holding the highest item id given out so far
+const LAST_ID_KEY: &str = "last_id";
+
+/// Key in the `meta` table holding a counter which gets incremented on every save
+const GENERATION_KEY: &str = "generation";
+
+/// Key in the `meta` table holding the scheduler used for reviews
+const SCHEDULER_KEY: &str = "scheduler";
+
+/// Key in the `meta` table holding the desired retention for FSRS
+const RETENTION_KEY: &str = "desired_retention";
+
+/// Key in the `meta` table holding the day boundary due dates have been calculated with
+const DAY_BOUNDARY_KEY: &str = "day_boundary";
+
+/// Storage backend using an SQLite database
+#[derive(Debug)]
+pub struct SqliteBackend {
+    conn: Connection,
+    items: ItemStorage,
+    srs: SRSStorage,
+    review_log: ReviewLog,
+    settings: Settings,
+    info: InfoCache,
+    /// Amount of review log entries which are already in the database
+    logged: usize,
+    /// Row id and undone state in the database of the review log entries already in it
+    log_rows: Vec<(i64, bool)>,
+    generation: u64,
+}
+
+impl SqliteBackend {
+    /// Opens the database at `file` and creates the schema if it doesn't exist yet
+    pub fn open<P: AsRef<Path>>(file: P) -> Result<Self> {
+        Self::from_connection(Connection::open(file)?)
+    }
+
+    /// Opens a new in-memory database
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        Self::create_schema(&conn)?;
+        let mut backend = Self {
+            conn,
+            items: ItemStorage::new(),
+            srs: SRSStorage::new(),
+            review_log: ReviewLog::new(),
+            settings: Settings::default(),
+            info: InfoCache::new(),
+            logged: 0,
+            log_rows: vec![],
+            generation: 0,
+        };
+        backend.load()?;
+        Ok(backend)
+    }
+
+    /// Creates the tables unless the database has them already
+    fn create_schema(conn: &Connection) -> Result<()> {
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            // Written by a newer version which might store data differently
+            return Err(Error::Invalid(format!(
+                "database version {version} is newer than the supported version {SCHEMA_VERSION}"
+            )));
+        }
+
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(())
+    }
+
+    fn get_meta<T: FromStr>(conn: &Connection, key: &str) -> Result<Option<T>> {
+        let value: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = ?", [key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(value.and_then(|i| i.parse().ok()))
+    }
+
+    fn set_meta<T: ToString>(conn: &Connection, key: &str, value: T) -> Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+            params![key, value.to_string()],
+        )?;
+        Ok(())
+    }
+}
+
+impl StorageBackend for SqliteBackend {
+    fn load(&mut self) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, literal FROM items ORDER BY id")?;
+        let items = stmt
+            .query_map([], |row| {
+                let literal: String = row.get(1)?;
+                Ok(item::Item {
+                    id: row.get(0)?,
+                    literal: literal.chars().next().unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let last_id = Self::get_meta(&self.conn, LAST_ID_KEY)?.unwrap_or(0);
+        let generation = Self::get_meta(&self.conn, GENERATION_KEY)?.unwrap_or(0);
+
+        let defaults = Settings::default();
+        let settings = Settings {
+            scheduler: Self::get_meta(&self.conn, SCHEDULER_KEY)?.unwrap_or(defaults.scheduler),
+            desired_retention: Self::get_meta(&self.conn, RETENTION_KEY)?
+                .unwrap_or(defaults.desired_retention),
+            day_boundary: Self::get_meta(&self.conn, DAY_BOUNDARY_KEY)?,
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT item_id, scheduler, state, due_on, in_learning, interval, step, relearning,
+            lapses, leech, suspended, drill, suspended_on, buried_until FROM srs",
+        )?;
+        let srs_items = stmt
+            .query_map([], |row| {
+                let scheduler: String = row.get(1)?;
+                let state: String = row.get(2)?;
+                let due_on: i64 = row.get(3)?;
+                let in_learning = row.get(4)?;
+                let state = scheduler
+                    .parse()
+                    .ok()
+                    .and_then(|kind| SchedulerState::deserialize(kind, &state))
+                    .unwrap_or_default();
+                let mut interval = row.get(5)?;
+                // Items reviewed before the interval has been stored
+                if let (true, 0, SchedulerState::SM2(sm2)) = (in_learning, interval, state) {
+                    interval = sm2.interval() as u32;
+                }
+                let step: Option<u8> = row.get(6)?;
+                let relearning = row.get(7)?;
+                Ok(srs::Item {
+                    id: row.get(0)?,
+                    state,
+                    due_on: due_on as u64,
+                    in_learning,
+                    interval,
+                    step: step.map(|index| LearningStep { index, relearning }),
+                    lapses: row.get(8)?,
+                    leech: row.get(9)?,
+                    suspended: row.get(10)?,
+                    drill: row.get(11)?,
+                    suspended_on: row.get::<_, i64>(12)? as u64,
+                    buried_until: row.get::<_, i64>(13)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT item_id, timestamp, quality, prev_interval, new_interval, ease_before,
+            ease_after, session, before, undone, id FROM review_log ORDER BY id",
+        )?;
+        let mut log_rows = vec![];
+        let entries = stmt
+            .query_map([], |row| {
+                let timestamp: i64 = row.get(1)?;
+                let quality: u8 = row.get(2)?;
+                let session: i64 = row.get(7)?;
+                let before: Option<String> = row.get(8)?;
+                let undone = row.get(9)?;
+                log_rows.push((row.get(10)?, undone));
+                Ok(ReviewEntry {
+                    item_id: row.get(0)?,
+                    timestamp: timestamp as u64,
+                    quality: RepQuality::from_grade(quality).unwrap_or(RepQuality::Grade0),
+                    prev_interval: row.get(3)?,
+                    new_interval: row.get(4)?,
+                    ease_before: row.get(5)?,
+                    ease_after: row.get(6)?,
+                    session: session as u64,
+                    before: before.and_then(|i| serde_json::from_str(&i).ok()),
+                    undone,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = self.conn.prepare("SELECT info FROM kanji_info")?;
+        let infos = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let infos = infos
+            .iter()
+            .filter_map(|i| serde_json::from_str::<KanjiInfo>(i).ok());
+
+        self.info = InfoCache::from_infos(infos);
+        self.items = ItemStorage::from_items(items, last_id);
+        self.srs = SRSStorage::from_items(srs_items);
+        self.logged = entries.len();
+        self.log_rows = log_rows;
+        self.review_log = ReviewLog::from_entries(entries);
+        self.settings = settings;
+        self.generation = generation;
+        Ok(())
+    }
+
+    /// Replaces the content of the database with the data in memory. Either everything or
+    /// nothing gets written
+    fn save(&mut self) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let generation = Self::get_meta::<u64>(&tx, GENERATION_KEY)?.unwrap_or(0) + 1;
+
+        tx.execute("DELETE FROM srs", [])?;
+        tx.execute("DELETE FROM items", [])?;
+        let mut log_rows = self.log_rows.clone();
+
+        {
+            let mut stmt = tx.prepare("INSERT INTO items (id, literal) VALUES (?, ?)")?;
+            for item in self.items.iter() {
+                stmt.execute(params![item.id, item.literal.to_string()])?;
+            }
+
+            let mut stmt = tx.prepare(
+                "INSERT INTO srs (item_id, scheduler, state, due_on, in_learning, interval, step,
+                relearning, lapses, leech, suspended, drill, suspended_on, buried_until)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for i in self.srs.iter() {
+                stmt.execute(params![
+                    i.id,
+                    i.state.kind().to_string(),
+                    i.state.serialize(),
+                    i.due_on as i64,
+                    i.in_learning,
+                    i.interval,
+                    i.step.map(|i| i.index),
+                    i.step.is_some_and(|i| i.relearning),
+                    i.lapses,
+                    i.leech,
+                    i.suspended,
+                    i.drill,
+                    i.suspended_on as i64,
+                    i.buried_until as i64
+                ])?;
+            }
+
+            // The review log is append-only. Entries only get marked as undone
+            let mut stmt = tx.prepare("UPDATE review_log SET undone = 1 WHERE id = ?")?;
+            for (entry, row) in self.review_log.iter().zip(log_rows.iter_mut()) {
+                if entry.undone && !row.1 {
+                    stmt.execute([row.0])?;
+                    row.1 = true;
+                }
+            }
+
+            let mut stmt = tx.prepare(
+                "INSERT INTO review_log (item_id, timestamp, quality, prev_interval, new_interval,
+                ease_before, ease_after, session, before, undone)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for i in self.review_log.since(self.logged) {
+                let before = i.before.and_then(|i| serde_json::to_string(&i).ok());
+                stmt.execute(params![
+                    i.item_id,
+                    i.timestamp as i64,
+                    i.quality.grade(),
+                    i.prev_interval,
+                    i.new_interval,
+                    i.ease_before,
+                    i.ease_after,
+                    i.session as i64,
+                    before,
+                    i.undone
+                ])?;
+                log_rows.push((tx.last_insert_rowid(), i.undone));
+            }
+
+            let mut stmt =
+                tx.prepare("INSERT OR REPLACE INTO kanji_info (literal, info) VALUES (?, ?)")?;
+            for info in self.info.iter() {
+                let json = serde_json::to_string(info).unwrap_or_default();
+                stmt.execute(params![info.literal.to_string(), json])?;
+            }
+        }
+
+        Self::set_meta(&tx, LAST_ID_KEY, self.items.last_id())?;
+        Self::set_meta(&tx, GENERATION_KEY, generation)?;
+        Self::set_meta(&tx, SCHEDULER_KEY, self.settings.scheduler)?;
+        Self::set_meta(&tx, RETENTION_KEY, self.settings.desired_retention)?;
+        match self.settings.day_boundary {
+            Some(boundary) => Self::set_meta(&tx, DAY_BOUNDARY_KEY, boundary)?,
+            None => {
+                tx.execute("DELETE FROM meta WHERE key = ?", [DAY_BOUNDARY_KEY])?;
+            }
+        }
+
+        tx.commit()?;
+        self.logged = self.review_log.len();
+        self.log_rows = log_rows;
+        self.generation = generation;
+        Ok(())
+    }
+
+    fn changed(&self) -> Result<bool> {
+        let generation = Self::get_meta(&self.conn, GENERATION_KEY)?.unwrap_or(0);
+        Ok(generation != self.generation)
+    }
+
+    #[inline]
+    fn items(&self) -> &ItemStorage {
+        &self.items
+    }
+
+    #[inline]
+    fn items_mut(&mut self) -> &mut ItemStorage {
+        &mut self.items
+    }
+
+    #[inline]
+    fn srs(&self) -> &SRSStorage {
+        &self.srs
+    }
+
+    #[inline]
+    fn srs_mut(&mut self) -> &mut SRSStorage {
+        &mut self.srs
+    }
+
+    #[inline]
+    fn review_log(&self) -> &ReviewLog {
+        &self.review_log
+    }
+
+    #[inline]
+    fn review_log_mut(&mut self) -> &mut ReviewLog {
+        &mut self.review_log
+    }
+
+    #[inline]
+    fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    #[inline]
+    fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    #[inline]
+    fn info_cache(&self) -> &InfoCache {
+        &self.info
+    }
+
+    #[inline]
+    fn info_cache_mut(&mut self) -> &mut InfoCache {
+        &mut self.info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::SchedulerConfig,
+        sm2::SM2,
+        storage::{merge::Snapshot, settings::Settings},
+        time::DayBoundary,
+    };
+
+    #[test]
+    fn save_and_load() {
+        let mut db = SqliteBackend::open_in_memory().unwrap();
+        for lit in ['日', '月', '火'] {
+            db.add_item(lit).unwrap();
+        }
+        db.remove_item(3);
+        db.srs_mut().get_mut(1).unwrap().review(
+            RepQuality::Grade5,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            0,
+            None,
+        );
+        // Failed new items go into the first learning step
+        db.srs_mut().get_mut(2).unwrap().review(
+            RepQuality::Grade1,
+            &Settings::default(),
+            &SchedulerConfig::default(),
+            &DayBoundary::LEGACY,
+            0,
+            None,
+        );
+        db.srs_mut().get_mut(2).unwrap().bury(86400);
+        let entry = ReviewEntry {
+            item_id: 1,
+            timestamp: 0,
+            quality: RepQuality::Grade5,
+            prev_interval: 0,
+            new_interval: 1,
+            ease_before: 2.5,
+            ease_after: 2.6,
+            session: 0,
+            before: Some(srs::Item::new(1)),
+            undone: false,
+        };
+        db.review_log_mut().push(entry);
+        db.review_log_mut().push(entry);
+        let info = KanjiInfo {
+            literal: '日',
+            stroke_count: Some(4),
+            ..KanjiInfo::default()
+        };
+        db.info_cache_mut().insert(info.clone());
+        db.save().unwrap();
+        db.review_log_mut().mark_undone(1);
+
+        db.save().unwrap();
+        db.set_data(Snapshot::default());
+        db.load().unwrap();
+
+        assert_eq!(2, db.items().len());
+        assert_eq!(3, db.items().last_id());
+        assert_eq!(Some('月'), db.get_item(2).map(|i| i.literal));
+
+        let reviewed = db.srs().find(1).unwrap();
+        assert!(reviewed.in_learning);
+        let sm2 = SM2::new().set_repetitions(1).set_ease_factor(2.6);
+        assert_eq!(SchedulerState::SM2(sm2), reviewed.state);
+        assert_eq!(None, reviewed.step);
+
+        let stepping = db.srs().find(2).unwrap();
+        let step = LearningStep {
+            index: 0,
+            relearning: false,
+        };
+        assert_eq!(Some(step), stepping.step);
+        assert_eq!(600, stepping.due_on);
+        assert_eq!(86400, stepping.buried_until);
+
+        let log = db.review_log().iter().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                &entry,
+                &ReviewEntry {
+                    undone: true,
+                    ..entry
+                }
+            ],
+            log
+        );
+        assert_eq!(Some(&info), db.info_cache().get('日'));
+    }
+
+    #[test]
+    fn newer_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        let newer = SCHEMA_VERSION + 1;
+        conn.pragma_update(None, "user_version", newer).unwrap();
+        assert!(SqliteBackend::from_connection(conn).is_err());
+    }
+
+    #[test]
+    fn detect_changes() {
+        let file = std::env::temp_dir().join(format!("kanji_srs_gen_{}.db", std::process::id()));
+        let mut first = SqliteBackend::open(&file).unwrap();
+        let mut second = SqliteBackend::open(&file).unwrap();
+        assert!(!first.changed().unwrap());
+
+        second.add_item('日');
+        second.save().unwrap();
+        assert!(first.changed().unwrap());
+        assert!(!second.changed().unwrap());
+
+        first.load().unwrap();
+        assert!(!first.changed().unwrap());
+        assert_eq!(1, first.items().len());
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn srs_requires_item() {
+        let mut db = SqliteBackend::open_in_memory().unwrap();
+        db.srs_mut().add(1);
+        assert!(db.save().is_err());
+    }
+}
@@ -1,46 +1,83 @@
+use super::{
+    settings::Settings,
+    workload::{self, Workload},
+};
 use crate::{
-    sm2::{RepQuality, SM2},
-    utils,
+    config::{LeechAction, SchedulerConfig},
+    fsrs,
+    scheduler::{FSRSItemState, SchedulerState},
+    sm2::RepQuality,
+    time::DayBoundary,
+    utils::DAY_SECS,
 };
-use chrono::{TimeZone, Timelike, Utc};
+use chrono::Duration;
 use serde::{Deserialize, Serialize};
-use std::{
-    collections::HashMap,
-    fs::{copy, rename, File},
-    io::BufReader,
-    path::Path,
-};
+use std::collections::HashMap;
 
-/// Storage for SRS data. The
-#[derive(Deserialize, Serialize, Clone, Debug)]
+/// Storage for SRS data
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
 pub struct SRSStorage {
-    file: String,
     data: HashMap<u32, Item>,
 }
 
 /// SRS info holding item
-#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
 pub struct Item {
     pub id: u32,
-    pub srs_data: SM2,
+    /// State of the scheduler the item has been reviewed with last
+    pub state: SchedulerState,
     pub due_on: u64,
     pub in_learning: bool,
+    /// Interval in days the item has been scheduled with on its last review
+    pub interval: u32,
+    /// Learning step the item is in. `due_on` is the exact time the step ends while an item is
+    /// in a step. `None` if the item isn't in learning steps
+    pub step: Option<LearningStep>,
+    /// Times the item has been failed after it had been learned
+    pub lapses: u32,
+    /// `true` if the item has been failed so often that it's considered a leech
+    pub leech: bool,
+    /// `true` if the item doesn't show up in sessions
+    pub suspended: bool,
+    /// Unix timestamp at which the item has been suspended. Its schedule gets moved by the
+    /// time it has been suspended once it's unsuspended
+    pub suspended_on: u64,
+    /// Unix timestamp until which the item is skipped. 0 if it isn't buried
+    pub buried_until: u64,
+    /// `true` if the item waits in the leech drill queue instead of showing up in sessions
+    pub drill: bool,
 }
 
-impl SRSStorage {
-    /// Create a new srs-storage
-    #[inline]
-    pub fn new<P: AsRef<str>>(file: P) -> Self {
-        let file = file.as_ref().to_string();
+/// Position of an item within the learning or relearning steps
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LearningStep {
+    pub index: u8,
+    /// `true` for the relearning steps of failed items, `false` for the ones of new items
+    pub relearning: bool,
+}
 
-        if Path::new(&file).exists() {
-            return Self::load(file).expect("Failed to load srs storage");
+impl LearningStep {
+    /// Returns the delays of the steps set in `config`, in seconds
+    pub fn delays(&self, config: &SchedulerConfig) -> Vec<u64> {
+        if self.relearning {
+            config.relearning_steps()
+        } else {
+            config.learning_steps()
         }
+    }
+}
 
-        Self {
-            file,
-            data: HashMap::new(),
-        }
+impl SRSStorage {
+    /// Create a new, empty srs-storage
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an srs-storage from already stored SRS items
+    pub fn from_items<I: IntoIterator<Item = Item>>(items: I) -> Self {
+        let data = items.into_iter().map(|i| (i.id, i)).collect();
+        Self { data }
     }
 
     /// Adds a new SRS item to the srs-storage
@@ -90,58 +127,47 @@ impl SRSStorage {
         self.data.remove(&id)
     }
 
-    /// Returns all items that need review
-    pub fn get_due(&self) -> impl Iterator<Item = &Item> {
-        let unix = utils::get_today_unix();
+    /// Returns all items that need review at the unix timestamp `now` with days starting at
+    /// `boundary`. Items whose learning step has ended come first
+    pub fn get_due(&self, now: u64, boundary: &DayBoundary) -> impl Iterator<Item = &Item> {
         let mut due = self
             .data
-            .iter()
-            .filter(move |i| {
-                let due = i.1.due_on as u64;
-                due > 0 && due <= unix && i.1.in_learning
-            })
-            .map(|i| i.1)
+            .values()
+            .filter(|i| i.is_due(now, boundary))
             .collect::<Vec<_>>();
-        due.sort_by(|a, b| a.id.cmp(&b.id));
+        due.sort_by_key(|a| (a.step.is_none(), a.id));
         due.into_iter()
     }
 
-    /// Returns an iterator over new items
-    pub fn get_new(&self) -> impl Iterator<Item = &Item> {
-        let mut unlearned = self
+    /// Returns all items in learning steps which aren't due yet at `now`. Suspended items and the
+    /// ones in the drill queue aren't waiting for a step
+    pub fn get_stepping(&self, now: u64) -> impl Iterator<Item = &Item> {
+        let mut stepping = self
             .data
-            .iter()
-            .filter_map(|i| (!i.1.in_learning).then(|| *i.0))
+            .values()
+            .filter(|i| i.step.is_some() && i.due_on > now && !i.suspended && !i.drill)
             .collect::<Vec<_>>();
-        unlearned.sort_unstable();
-        unlearned.into_iter().filter_map(|i| self.data.get(&i))
+        stepping.sort_by_key(|a| (a.due_on, a.id));
+        stepping.into_iter()
     }
 
-    fn backup(&self) {
-        let backup = Path::new(&self.file).with_file_name("srs_backup");
-        let _ = copy(&self.file, backup);
+    /// Returns all leeches, the ones with the most lapses first
+    pub fn get_leeches(&self) -> impl Iterator<Item = &Item> {
+        let mut leeches = self.data.values().filter(|i| i.leech).collect::<Vec<_>>();
+        leeches.sort_by_key(|a| (std::cmp::Reverse(a.lapses), a.id));
+        leeches.into_iter()
     }
 
-    /// Saves the SRS storage
-    pub fn save(&self) {
-        self.backup();
-        let path = Path::new(&self.file).with_file_name("srs_data_new");
-        let file = File::create(&path).expect("Couldn't write SrsStorage file");
-        bincode::serialize_into(&file, &self).unwrap();
-        if !Self::check_file(&path) {
-            panic!("Failed to save srs storage");
-        }
-        rename(path, &self.file).unwrap()
-    }
-
-    fn check_file<F: AsRef<Path>>(file: F) -> bool {
-        Self::load(file).is_some()
-    }
-
-    fn load<P: AsRef<Path>>(file: P) -> Option<Self> {
-        let r = File::open(&file).ok()?;
-        let item_storage: Self = bincode::deserialize_from(BufReader::new(r)).ok()?;
-        return Some(item_storage);
+    /// Returns an iterator over new items which aren't suspended or buried at `now`
+    pub fn get_new(&self, now: u64) -> impl Iterator<Item = &Item> {
+        let mut unlearned = self
+            .data
+            .iter()
+            .filter(|i| !i.1.in_learning && !i.1.suspended && !i.1.is_buried(now))
+            .map(|i| *i.0)
+            .collect::<Vec<_>>();
+        unlearned.sort_unstable();
+        unlearned.into_iter().filter_map(|i| self.data.get(&i))
     }
 }
 
@@ -149,21 +175,191 @@ impl Item {
     /// Create a new item
     #[inline]
     pub fn new(id: u32) -> Self {
-        let srs_data = SM2::new();
         Self {
             id,
-            srs_data,
+            state: SchedulerState::default(),
             in_learning: false,
             due_on: 0,
+            interval: 0,
+            step: None,
+            lapses: 0,
+            leech: false,
+            suspended: false,
+            suspended_on: 0,
+            buried_until: 0,
+            drill: false,
         }
     }
 
-    /// Review an item
-    pub fn review(&mut self, r_quality: RepQuality) {
+    /// Review an item at the unix timestamp `now` with the scheduler selected in `settings` and
+    /// the parameters of `config`. New and failed items go through the learning steps before
+    /// they get scheduled in days, which start at `boundary`. The scheduler only sees the first
+    /// review of the steps. The interval gets fuzzed and, if `workload` is given, balanced
+    pub fn review(
+        &mut self,
+        r_quality: RepQuality,
+        settings: &Settings,
+        config: &SchedulerConfig,
+        boundary: &DayBoundary,
+        now: u64,
+        workload: Option<&Workload>,
+    ) {
+        if let Some(step) = self.step {
+            self.review_step(step, r_quality, config, boundary, now);
+            return;
+        }
+
+        self.convert(settings, config, boundary);
+        let was_learning = self.in_learning;
+        let scheduled = self.state.review(settings, config, r_quality, now);
+
+        self.state = scheduled.state;
         self.in_learning = true;
-        self.srs_data = self.srs_data.review(r_quality);
-        let next_review = self.srs_data.new_interval(&r_quality) as u32;
-        self.due_on = utils::unix_n_days_offset(next_review)
+        self.interval =
+            workload::pick_interval(self, scheduled.interval, config, boundary, now, workload);
+        self.due_on = boundary.offset(now, self.interval);
+
+        if was_learning && !r_quality.is_correct() {
+            self.lapse(config, now);
+        }
+
+        // Passing a new item counts as the first learning step
+        let step = LearningStep {
+            index: 0,
+            relearning: was_learning,
+        };
+        if !r_quality.is_correct() {
+            self.enter_step(step, config, boundary, now);
+        } else if !was_learning && r_quality != RepQuality::Grade5 {
+            self.advance_step(step, config, boundary, now);
+        }
+    }
+
+    /// Counts a lapse and applies the leech action to the item once it becomes a leech. Like in
+    /// Anki, the action gets applied again every half threshold after that
+    fn lapse(&mut self, config: &SchedulerConfig, now: u64) {
+        self.lapses += 1;
+
+        let threshold = config.leech_threshold;
+        if threshold == 0
+            || self.lapses < threshold
+            || !(self.lapses - threshold).is_multiple_of((threshold / 2).max(1))
+        {
+            return;
+        }
+
+        self.leech = true;
+        match config.leech_action {
+            LeechAction::Tag => (),
+            LeechAction::Suspend => self.suspend(now),
+            LeechAction::Drill => self.drill = true,
+        }
+    }
+
+    /// Reviews an item which is in learning steps. Failing restarts the steps, passing moves
+    /// to the next one and an easy grade graduates the item right away
+    fn review_step(
+        &mut self,
+        step: LearningStep,
+        r_quality: RepQuality,
+        config: &SchedulerConfig,
+        boundary: &DayBoundary,
+        now: u64,
+    ) {
+        if !r_quality.is_correct() {
+            let first = LearningStep { index: 0, ..step };
+            self.enter_step(first, config, boundary, now);
+        } else if r_quality == RepQuality::Grade5 {
+            self.graduate(boundary, now);
+        } else {
+            self.advance_step(step, config, boundary, now);
+        }
+    }
+
+    /// Puts the item into `step`. Graduates it if there is no such step
+    fn enter_step(
+        &mut self,
+        step: LearningStep,
+        config: &SchedulerConfig,
+        boundary: &DayBoundary,
+        now: u64,
+    ) {
+        match step.delays(config).get(step.index as usize) {
+            Some(delay) => {
+                self.step = Some(step);
+                self.due_on = now + delay;
+            }
+            None => self.graduate(boundary, now),
+        }
+    }
+
+    /// Moves the item to the step after `step`
+    fn advance_step(
+        &mut self,
+        step: LearningStep,
+        config: &SchedulerConfig,
+        boundary: &DayBoundary,
+        now: u64,
+    ) {
+        let next = LearningStep {
+            index: step.index.saturating_add(1),
+            ..step
+        };
+        self.enter_step(next, config, boundary, now);
+    }
+
+    /// Takes the item out of the learning steps and schedules it with the interval the
+    /// scheduler has given it
+    fn graduate(&mut self, boundary: &DayBoundary, now: u64) {
+        if self.step.take().is_some() {
+            self.due_on = boundary.offset(now, self.interval.max(1));
+        }
+    }
+
+    /// Converts the scheduler state into one of the scheduler selected in `settings`
+    pub fn convert(
+        &mut self,
+        settings: &Settings,
+        config: &SchedulerConfig,
+        boundary: &DayBoundary,
+    ) {
+        let last_review = self.last_review(boundary);
+        self.state = self
+            .state
+            .convert(settings, config, self.interval, last_review);
+    }
+
+    /// Returns the unix timestamp of the start of the day of the last review. 0 if the item is
+    /// not in learning
+    pub fn last_review(&self, boundary: &DayBoundary) -> u64 {
+        if !self.in_learning {
+            return 0;
+        }
+
+        if self.step.is_some() {
+            return boundary.day_start(self.due_on);
+        }
+
+        let due = boundary.date_of(self.due_on);
+        boundary.start_of(due - Duration::days(self.interval as i64))
+    }
+
+    /// Returns the estimated probability of recalling the item at the unix timestamp `now`. Items
+    /// scheduled with SM-2 are assumed to drop to 90% once their interval has passed
+    pub fn retrievability(&self, now: u64, boundary: &DayBoundary) -> f64 {
+        if !self.in_learning {
+            return 0.0;
+        }
+
+        let (last_review, stability) = match self.state {
+            SchedulerState::FSRS(FSRSItemState {
+                memory: Some(memory),
+                last_review,
+            }) => (last_review, memory.stability),
+            _ => (self.last_review(boundary), self.interval.max(1) as f64),
+        };
+        let elapsed_days = now.saturating_sub(last_review) / DAY_SECS;
+        fsrs::retrievability(elapsed_days as u32, stability)
     }
 
     /// Resets an item completely
@@ -173,26 +369,311 @@ impl Item {
         self.due_on = 0;
     }
 
-    /// Returns `true` if Item can be learned or reviewed
-    pub fn can_be_reviewed(&self) -> bool {
-        if !self.in_learning || self.due_on == 0 {
-            return true;
+    /// Returns `true` if Item can be learned or reviewed at the unix timestamp `now`
+    pub fn can_be_reviewed(&self, now: u64, boundary: &DayBoundary) -> bool {
+        if self.suspended || self.drill || self.is_buried(now) {
+            return false;
         }
 
-        let unix = utils::get_today_unix();
+        !self.in_learning || self.due_on == 0 || self.is_due(now, boundary)
+    }
 
-        self.due_on <= unix
+    /// Stops showing the item in sessions until it gets unsuspended
+    pub fn suspend(&mut self, now: u64) {
+        if !self.suspended {
+            self.suspended = true;
+            self.suspended_on = now;
+        }
     }
 
-    pub fn 直す(&mut self) {
-        let time = Utc.timestamp(self.due_on as i64, 0);
-        let time = time
-            .with_hour(4)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .with_minute(0)
-            .unwrap();
-        self.due_on = time.timestamp() as u64;
+    /// Shows a suspended item in sessions again. The schedule is frozen while an item is
+    /// suspended, so its due date is moved by the days it has been suspended. Returns `false` if
+    /// the item wasn't suspended
+    pub fn unsuspend(&mut self, now: u64, boundary: &DayBoundary) -> bool {
+        if !self.suspended {
+            return false;
+        }
+
+        let paused = now.saturating_sub(self.suspended_on);
+        if self.in_learning && self.due_on > 0 && self.suspended_on > 0 {
+            match self.step {
+                Some(_) => self.due_on += paused,
+                None => {
+                    let days = boundary.date_of(now) - boundary.date_of(self.suspended_on);
+                    self.due_on = boundary.start_of(boundary.date_of(self.due_on) + days);
+                }
+            }
+            self.state = self.state.postpone(paused);
+        }
+
+        self.suspended = false;
+        self.suspended_on = 0;
+        true
+    }
+
+    /// Skips the item until the unix timestamp `until`
+    #[inline]
+    pub fn bury(&mut self, until: u64) {
+        self.buried_until = until;
+    }
+
+    /// Returns `true` if the item is skipped at the unix timestamp `now`
+    #[inline]
+    pub fn is_buried(&self, now: u64) -> bool {
+        now < self.buried_until
+    }
+
+    /// Returns `true` if the item is in learning and due at the unix timestamp `now`. Items in
+    /// learning steps are due once their step has ended, others on the day they are due on
+    pub fn is_due(&self, now: u64, boundary: &DayBoundary) -> bool {
+        if !self.in_learning || self.due_on == 0 || self.suspended || self.drill {
+            return false;
+        }
+
+        if self.is_buried(now) {
+            return false;
+        }
+
+        match self.step {
+            Some(_) => self.due_on <= now,
+            None => self.due_on <= boundary.day_start(now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::Storage, time::FixedClock};
+    use chrono::TimeZone;
+    use chrono_tz::Europe::Berlin;
+    use std::rc::Rc;
+
+    const MINUTE_SECS: u64 = 60;
+
+    fn berlin(m: u32, d: u32, h: u32, min: u32) -> u64 {
+        Berlin.ymd(2022, m, d).and_hms(h, min, 0).timestamp() as u64
+    }
+
+    /// Storage with the kanji 日 and a clock set to `now`. Uses Berlin time with days starting at
+    /// 04:00 and the default steps of 10m and 1h for new kanji and 10m for failed ones
+    fn storage(now: u64) -> (Storage, Rc<FixedClock>) {
+        let mut storage = Storage::in_memory();
+        let clock = Rc::new(FixedClock::new(now));
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::new(Berlin, 4));
+        storage.set_scheduler_config(SchedulerConfig::default());
+        storage.add('日');
+        (storage, clock)
+    }
+
+    fn review(storage: &mut Storage, grade: RepQuality) {
+        let session = storage.start_session();
+        assert!(storage.review(1, grade, session));
+    }
+
+    fn is_due(storage: &Storage) -> bool {
+        storage.get_due().any(|i| i.id == 1)
+    }
+
+    fn step(storage: &Storage) -> Option<LearningStep> {
+        storage.get_srs_storage().find(1).unwrap().step
+    }
+
+    #[test]
+    fn new_kanji_graduate_after_steps() {
+        let (mut storage, clock) = storage(berlin(5, 1, 10, 0));
+
+        // Passing a new kanji completes the first step
+        review(&mut storage, RepQuality::Grade4);
+        let learning = LearningStep {
+            index: 1,
+            relearning: false,
+        };
+        assert_eq!(Some(learning), step(&storage));
+        assert_eq!(
+            berlin(5, 1, 11, 0),
+            storage.get_srs_storage().find(1).unwrap().due_on
+        );
+
+        clock.set(berlin(5, 1, 10, 59));
+        assert!(!is_due(&storage));
+        clock.set(berlin(5, 1, 11, 0));
+        assert!(is_due(&storage));
+
+        review(&mut storage, RepQuality::Grade3);
+        assert_eq!(None, step(&storage));
+        let item = *storage.get_srs_storage().find(1).unwrap();
+        assert_eq!(1, item.interval);
+        assert_eq!(berlin(5, 2, 4, 0), item.due_on);
+        assert!(!is_due(&storage));
+    }
+
+    #[test]
+    fn failing_restarts_steps() {
+        let (mut storage, clock) = storage(berlin(5, 1, 22, 0));
+
+        review(&mut storage, RepQuality::Grade2);
+        assert_eq!(Some(0), step(&storage).map(|i| i.index));
+
+        clock.advance(10 * MINUTE_SECS);
+        review(&mut storage, RepQuality::Grade4);
+        assert_eq!(Some(1), step(&storage).map(|i| i.index));
+
+        // Steps continue past the end of the day
+        clock.advance(60 * MINUTE_SECS);
+        assert!(is_due(&storage));
+        review(&mut storage, RepQuality::Grade0);
+        assert_eq!(Some(0), step(&storage).map(|i| i.index));
+        assert_eq!(
+            berlin(5, 1, 23, 20),
+            storage.get_srs_storage().find(1).unwrap().due_on
+        );
+    }
+
+    #[test]
+    fn easy_skips_steps() {
+        let (mut storage, clock) = storage(berlin(5, 1, 10, 0));
+        review(&mut storage, RepQuality::Grade5);
+        assert_eq!(None, step(&storage));
+
+        clock.set(berlin(5, 2, 4, 0));
+        assert!(is_due(&storage));
+    }
+
+    #[test]
+    fn lapses_relearn_same_day() {
+        let (mut storage, clock) = storage(berlin(5, 1, 10, 0));
+        review(&mut storage, RepQuality::Grade5);
+
+        clock.set(berlin(5, 2, 9, 0));
+        review(&mut storage, RepQuality::Grade1);
+        let relearning = LearningStep {
+            index: 0,
+            relearning: true,
+        };
+        assert_eq!(Some(relearning), step(&storage));
+
+        clock.advance(10 * MINUTE_SECS);
+        assert_eq!(vec![1], storage.get_due().map(|i| i.id).collect::<Vec<_>>());
+
+        // The only relearning step has been passed
+        review(&mut storage, RepQuality::Grade4);
+        assert_eq!(None, step(&storage));
+        assert_eq!(
+            berlin(5, 3, 4, 0),
+            storage.get_srs_storage().find(1).unwrap().due_on
+        );
+    }
+
+    #[test]
+    fn stepping_leaves_out_suspended() {
+        let mut srs = SRSStorage::new();
+        for id in 1..=3 {
+            srs.add(id);
+            let item = srs.get_mut(id).unwrap();
+            item.in_learning = true;
+            item.step = Some(LearningStep {
+                index: 0,
+                relearning: false,
+            });
+            item.due_on = 100;
+        }
+        srs.get_mut(2).unwrap().suspended = true;
+        srs.get_mut(3).unwrap().drill = true;
+        assert_eq!(
+            vec![1],
+            srs.get_stepping(0).map(|i| i.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn day_boundary_changed_mid_step() {
+        let (mut storage, clock) = storage(berlin(5, 1, 10, 0));
+        review(&mut storage, RepQuality::Grade4);
+
+        // The step stays due an hour after the review instead of at the start of the day
+        storage.set_day_boundary(DayBoundary::new(Berlin, 6));
+        assert_eq!(Some(1), step(&storage).map(|i| i.index));
+        assert_eq!(
+            berlin(5, 1, 11, 0),
+            storage.get_srs_storage().find(1).unwrap().due_on
+        );
+        assert!(!is_due(&storage));
+        clock.set(berlin(5, 1, 11, 0));
+        assert!(is_due(&storage));
+
+        review(&mut storage, RepQuality::Grade3);
+        assert_eq!(
+            berlin(5, 2, 6, 0),
+            storage.get_srs_storage().find(1).unwrap().due_on
+        );
+    }
+
+    /// Reviews 日 each day with `grade` until it's due again
+    fn review_days(storage: &mut Storage, clock: &FixedClock, grades: &[RepQuality]) {
+        for grade in grades {
+            while !storage.get_due().any(|i| i.id == 1) {
+                clock.advance(DAY_SECS);
+            }
+            let session = storage.start_session();
+            storage.review(1, *grade, session);
+        }
+    }
+
+    #[test]
+    fn leeches_move_into_drill_queue() {
+        // Leeches are detected after 4 lapses and moved into the drill queue
+        let mut storage = Storage::in_memory();
+        let clock = Rc::new(FixedClock::new(1_650_000_000));
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::LEGACY);
+        storage.set_scheduler_config(SchedulerConfig {
+            learning_steps: vec![],
+            relearning_steps: vec![],
+            leech_threshold: 4,
+            leech_action: LeechAction::Drill,
+            ..SchedulerConfig::default()
+        });
+        storage.add('日');
+        storage.add('月');
+
+        // Failing a new kanji doesn't count as lapse
+        let session = storage.start_session();
+        storage.review(1, RepQuality::Grade1, session);
+        let fail = [RepQuality::Grade4, RepQuality::Grade1];
+        review_days(&mut storage, &clock, &fail);
+        review_days(&mut storage, &clock, &fail);
+        review_days(&mut storage, &clock, &fail);
+        let item = *storage.get_srs_storage().find(1).unwrap();
+        assert_eq!(3, item.lapses);
+        assert!(!item.leech);
+
+        review_days(&mut storage, &clock, &fail);
+        let item = *storage.get_srs_storage().find(1).unwrap();
+        assert_eq!(4, item.lapses);
+        assert!(item.leech && item.drill);
+        assert_eq!(
+            vec![1],
+            storage
+                .get_srs_storage()
+                .get_leeches()
+                .map(|i| i.id)
+                .collect::<Vec<_>>()
+        );
+
+        // Kanji in the drill queue don't show up in sessions anymore
+        clock.advance(30 * DAY_SECS);
+        assert!(storage.get_due().next().is_none());
+        assert!(!storage.get_by_id(1).unwrap().can_be_reviewed(storage.now()));
+
+        // Released from the queue they lapse again. The action is repeated every half threshold
+        storage.get_srs_mut(1).unwrap().drill = false;
+        review_days(&mut storage, &clock, &[RepQuality::Grade1]);
+        assert!(!storage.get_srs_storage().find(1).unwrap().drill);
+        review_days(&mut storage, &clock, &fail);
+        let item = *storage.get_srs_storage().find(1).unwrap();
+        assert_eq!(6, item.lapses);
+        assert!(item.drill);
     }
 }
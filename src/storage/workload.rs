@@ -0,0 +1,139 @@
+//! Fuzzing of intervals and balancing the reviews over the days
+
+use super::{srs, SRSStorage};
+use crate::{config::SchedulerConfig, scheduler, time::DayBoundary};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Amount of reviews due on each day
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Workload {
+    /// Reviews by the unix timestamp of the start of the day they are due on
+    per_day: HashMap<u64, usize>,
+}
+
+impl Workload {
+    /// Counts the reviews of all items in `srs` which show up in sessions
+    pub fn of(srs: &SRSStorage) -> Self {
+        let mut per_day = HashMap::new();
+        let scheduled = srs.iter().filter(|i| {
+            i.in_learning && i.due_on > 0 && i.step.is_none() && !i.suspended && !i.drill
+        });
+        for item in scheduled {
+            *per_day.entry(item.due_on).or_default() += 1;
+        }
+        Self { per_day }
+    }
+
+    /// Returns the amount of reviews due on the day starting at `day_start`
+    #[inline]
+    pub fn on(&self, day_start: u64) -> usize {
+        self.per_day.get(&day_start).copied().unwrap_or(0)
+    }
+}
+
+/// Picks the interval `item` gets due after from the fuzz range around `interval`. The pick only
+/// depends on the item and the time of the review. With `workload` the day with the fewest
+/// reviews is preferred, using the random pick to break ties. Fuzzing and balancing are set in
+/// `config`, days start at `boundary`
+pub fn pick_interval(
+    item: &srs::Item,
+    interval: u32,
+    config: &SchedulerConfig,
+    boundary: &DayBoundary,
+    now: u64,
+    workload: Option<&Workload>,
+) -> u32 {
+    if !config.fuzz {
+        return interval;
+    }
+
+    let (min, max) = scheduler::fuzz_range(interval, config.maximum_interval);
+    if min == max {
+        return interval;
+    }
+
+    let mut rng = StdRng::seed_from_u64(((item.id as u64) << 32) ^ now);
+    let fuzzed = rng.gen_range(min..=max);
+
+    let workload = match workload {
+        Some(workload) if config.load_balance => workload,
+        _ => return fuzzed,
+    };
+    (min..=max)
+        .min_by_key(|i| (workload.on(boundary.offset(now, *i)), i.abs_diff(fuzzed)))
+        .unwrap_or(fuzzed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sm2::RepQuality, storage::Storage, time::FixedClock, utils::DAY_SECS};
+    use chrono_tz::Europe::Berlin;
+    use std::{collections::BTreeMap, rc::Rc};
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn fuzzed_interval() {
+        let config = SchedulerConfig::default();
+        let picks = (1..=20)
+            .map(|id| {
+                pick_interval(
+                    &srs::Item::new(id),
+                    6,
+                    &config,
+                    &DayBoundary::LEGACY,
+                    NOW,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(picks.iter().all(|i| (4..=8).contains(i)));
+        assert!(picks.iter().any(|i| *i != picks[0]));
+
+        // The same review always gets the same interval
+        let item = srs::Item::new(1);
+        assert_eq!(
+            picks[0],
+            pick_interval(&item, 6, &config, &DayBoundary::LEGACY, NOW, None)
+        );
+        assert_eq!(
+            2,
+            pick_interval(&item, 2, &config, &DayBoundary::LEGACY, NOW, None)
+        );
+    }
+
+    #[test]
+    fn balance_reviews() {
+        let mut storage = Storage::in_memory();
+        // 2022-03-10 12:00 in Berlin
+        let clock = Rc::new(FixedClock::new(1_646_910_000));
+        storage.set_clock(clock.clone());
+        storage.set_day_boundary(DayBoundary::new(Berlin, 4));
+        storage.set_scheduler_config(SchedulerConfig {
+            learning_steps: vec![],
+            relearning_steps: vec![],
+            load_balance: true,
+            ..SchedulerConfig::default()
+        });
+        for lit in "一二三四五六七八九十".chars() {
+            storage.add(lit);
+        }
+
+        // The second review schedules 6 days, fuzzed into 4 to 8 days
+        for _ in 0..2 {
+            let session = storage.start_session();
+            for id in 1..=10 {
+                storage.review(id, RepQuality::Grade4, session);
+            }
+            clock.advance(DAY_SECS);
+        }
+
+        let mut per_day = BTreeMap::new();
+        for item in storage.get_srs_storage().iter() {
+            *per_day.entry(item.due_on).or_insert(0) += 1;
+        }
+        assert_eq!(vec![2; 5], per_day.into_values().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,329 @@
+//! Clocks and the boundaries of review days
+//!
+//! A review day doesn't start at midnight but at a configurable hour in the user's timezone, so
+//! reviews done late at night still count for the previous day. All due dates are stored as the
+//! unix timestamp at which the day they are due on starts.
+
+use crate::config::Config;
+use chrono::{Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    cell::Cell,
+    fmt::{Debug, Display},
+    rc::Rc,
+    str::FromStr,
+};
+
+/// Source of the current time
+pub trait Clock: Debug {
+    /// Returns the current unix timestamp
+    fn now(&self) -> u64;
+}
+
+/// Clock returning the time of the system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        chrono::Utc::now().timestamp() as u64
+    }
+}
+
+/// Allows keeping a handle to a clock, eg. a [`FixedClock`], after passing it on
+impl<C: Clock + ?Sized> Clock for Rc<C> {
+    #[inline]
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+/// Clock which only moves when told to
+#[derive(Debug, Clone, Default)]
+pub struct FixedClock(Cell<u64>);
+
+impl FixedClock {
+    #[inline]
+    pub fn new(now: u64) -> Self {
+        Self(Cell::new(now))
+    }
+
+    /// Sets the time to `now`
+    #[inline]
+    pub fn set(&self, now: u64) {
+        self.0.set(now);
+    }
+
+    /// Moves the time forward by `secs` seconds
+    #[inline]
+    pub fn advance(&self, secs: u64) {
+        self.0.set(self.0.get() + secs);
+    }
+}
+
+impl Clock for FixedClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+/// Timezone and hour at which review days start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayBoundary {
+    pub tz: Tz,
+    pub hour: u32,
+}
+
+impl DayBoundary {
+    /// Day boundary used by versions which didn't take the timezone into account
+    pub const LEGACY: DayBoundary = DayBoundary {
+        tz: Tz::UTC,
+        hour: 4,
+    };
+
+    #[inline]
+    pub fn new(tz: Tz, hour: u32) -> Self {
+        Self {
+            tz,
+            hour: hour.min(23),
+        }
+    }
+
+    /// Returns the day boundary set in `config`. Uses the timezone of the system if none is set
+    pub fn from_config(config: &Config) -> Self {
+        let tz = config.tz().or_else(system_tz).unwrap_or(Tz::UTC);
+        Self::new(tz, config.day_start_hour)
+    }
+
+    /// Returns the review day `timestamp` belongs to
+    pub fn date_of(&self, timestamp: u64) -> NaiveDate {
+        let local = self.tz.timestamp(timestamp as i64, 0).naive_local();
+        (local - Duration::hours(self.hour as i64)).date()
+    }
+
+    /// Returns the unix timestamp at which the review day `date` starts. If that time doesn't
+    /// exist because of a DST change, the day starts at the first valid time after it
+    pub fn start_of(&self, date: NaiveDate) -> u64 {
+        let mut start = date.and_hms(self.hour, 0, 0);
+        loop {
+            match self.tz.from_local_datetime(&start) {
+                LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+                    return time.timestamp() as u64
+                }
+                LocalResult::None => start += Duration::minutes(30),
+            }
+        }
+    }
+
+    /// Returns the unix timestamp at which the review day containing `timestamp` started
+    #[inline]
+    pub fn day_start(&self, timestamp: u64) -> u64 {
+        self.start_of(self.date_of(timestamp))
+    }
+
+    /// Returns the unix timestamp at which the review day `days` days after the one containing
+    /// `timestamp` starts
+    #[inline]
+    pub fn offset(&self, timestamp: u64, days: u32) -> u64 {
+        self.start_of(self.date_of(timestamp) + Duration::days(days as i64))
+    }
+
+    /// Parses a point in time given as `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` or unix timestamp. Dates
+    /// and times are local to the boundary's timezone and dates without a time refer to the
+    /// start of that day
+    pub fn parse_time(&self, s: &str) -> Option<u64> {
+        let s = s.trim();
+        if let Ok(timestamp) = s.parse::<u64>() {
+            return Some(timestamp);
+        }
+
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Some(self.start_of(date));
+        }
+
+        let time = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+            .ok()?;
+        match self.tz.from_local_datetime(&time) {
+            LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+                Some(time.timestamp() as u64)
+            }
+            LocalResult::None => None,
+        }
+    }
+
+    /// Formats `timestamp` as local `YYYY-MM-DD HH:MM`
+    pub fn format_time(&self, timestamp: u64) -> String {
+        self.format(timestamp, "%Y-%m-%d %H:%M")
+    }
+
+    /// Formats the review day containing `timestamp` as weekday and date, eg. `Thu 03-10`
+    pub fn format_day(&self, timestamp: u64) -> String {
+        self.format(self.day_start(timestamp), "%a %m-%d")
+    }
+
+    fn format(&self, timestamp: u64, fmt: &str) -> String {
+        self.tz
+            .timestamp(timestamp as i64, 0)
+            .format(fmt)
+            .to_string()
+    }
+
+    /// Converts a due date calculated with `from` so it's due on the same day with this boundary
+    #[inline]
+    pub fn convert(&self, from: &DayBoundary, due_on: u64) -> u64 {
+        self.start_of(from.date_of(due_on))
+    }
+}
+
+impl Display for DayBoundary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.tz.name(), self.hour)
+    }
+}
+
+impl FromStr for DayBoundary {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tz, hour) = s.split_once(' ').ok_or(())?;
+        let hour = hour.parse().map_err(|_| ())?;
+        Ok(Self::new(tz.parse().map_err(|_| ())?, hour))
+    }
+}
+
+impl Serialize for DayBoundary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DayBoundary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| de::Error::custom(format!("invalid day boundary '{s}'")))
+    }
+}
+
+/// Returns the timezone of the system
+fn system_tz() -> Option<Tz> {
+    iana_time_zone::get_timezone().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America::New_York, Asia::Tokyo, Europe::Berlin};
+
+    fn ts(tz: Tz, y: i32, m: u32, d: u32, h: u32, min: u32) -> u64 {
+        tz.ymd(y, m, d).and_hms(h, min, 0).timestamp() as u64
+    }
+
+    #[test]
+    fn day_starts_at_local_hour() {
+        let boundary = DayBoundary::new(Tokyo, 4);
+
+        // 03:59 still belongs to the previous day
+        let night = ts(Tokyo, 2022, 3, 10, 3, 59);
+        assert_eq!(ts(Tokyo, 2022, 3, 9, 4, 0), boundary.day_start(night));
+
+        let morning = ts(Tokyo, 2022, 3, 10, 4, 0);
+        assert_eq!(morning, boundary.day_start(morning));
+        assert_eq!(ts(Tokyo, 2022, 3, 12, 4, 0), boundary.offset(morning, 2));
+    }
+
+    #[test]
+    fn dst_forward() {
+        // Clocks in Berlin jump from 02:00 to 03:00 on 2022-03-27
+        let boundary = DayBoundary::new(Berlin, 4);
+        let before = ts(Berlin, 2022, 3, 26, 12, 0);
+        let next = boundary.offset(before, 1);
+        assert_eq!(ts(Berlin, 2022, 3, 27, 4, 0), next);
+        assert_eq!(23 * 3600, next - boundary.day_start(before));
+
+        // Rollover hour within the skipped hour
+        let boundary = DayBoundary::new(Berlin, 2);
+        assert_eq!(ts(Berlin, 2022, 3, 27, 3, 0), boundary.offset(before, 1));
+    }
+
+    #[test]
+    fn dst_backward() {
+        // Clocks in New York go back from 02:00 to 01:00 on 2022-11-06
+        let boundary = DayBoundary::new(New_York, 1);
+        let before = ts(New_York, 2022, 11, 5, 12, 0);
+        let next = boundary.offset(before, 1);
+        assert_eq!(ts(New_York, 2022, 11, 5, 1, 0) + 24 * 3600, next);
+
+        let after = boundary.offset(before, 2);
+        assert_eq!(25 * 3600, after - next);
+        assert_eq!(
+            boundary.date_of(next) + Duration::days(1),
+            boundary.date_of(after)
+        );
+    }
+
+    #[test]
+    fn convert_legacy() {
+        let legacy = DayBoundary::LEGACY;
+        let due = ts(Tz::UTC, 2022, 3, 10, 4, 0);
+
+        // Due dates keep their day in every timezone
+        for tz in [Tokyo, Berlin, New_York] {
+            let boundary = DayBoundary::new(tz, 4);
+            let converted = boundary.convert(&legacy, due);
+            assert_eq!(ts(tz, 2022, 3, 10, 4, 0), converted);
+            assert_eq!(converted, boundary.convert(&boundary, converted));
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let boundary = DayBoundary::new(Tokyo, 5);
+        assert_eq!(Ok(boundary), boundary.to_string().parse());
+        assert!("Asia/Tokyo".parse::<DayBoundary>().is_err());
+    }
+
+    #[test]
+    fn parse_time() {
+        let boundary = DayBoundary::new(Tokyo, 4);
+        assert_eq!(
+            Some(ts(Tokyo, 2022, 3, 10, 4, 0)),
+            boundary.parse_time("2022-03-10")
+        );
+        assert_eq!(
+            Some(ts(Tokyo, 2022, 3, 10, 23, 30)),
+            boundary.parse_time("2022-03-10 23:30")
+        );
+        assert_eq!(
+            Some(ts(Tokyo, 2022, 3, 10, 23, 30)),
+            boundary.parse_time("2022-03-10T23:30:00")
+        );
+        assert_eq!(Some(1646870400), boundary.parse_time("1646870400"));
+        assert_eq!(None, boundary.parse_time("tomorrow"));
+        assert_eq!(
+            "2022-03-10 23:30",
+            boundary.format_time(ts(Tokyo, 2022, 3, 10, 23, 30))
+        );
+        // Times before the day start belong to the previous day
+        assert_eq!(
+            "Wed 03-09",
+            boundary.format_day(ts(Tokyo, 2022, 3, 10, 3, 30))
+        );
+
+        // Times skipped by DST don't exist
+        let boundary = DayBoundary::new(Berlin, 4);
+        assert_eq!(None, boundary.parse_time("2022-03-27 02:30"));
+    }
+
+    #[test]
+    fn fixed_clock() {
+        let clock = FixedClock::new(100);
+        clock.advance(50);
+        assert_eq!(150, clock.now());
+    }
+}
@@ -1,4 +1,3 @@
-use chrono::{Duration, Timelike};
 use std::io::{Read, Write};
 
 pub fn read_stdin() -> String {
@@ -7,25 +6,8 @@ pub fn read_stdin() -> String {
     String::from_utf8(buf).unwrap()
 }
 
-/// Returns the unix timestamp of 04:00 of the current day
-pub fn get_today_unix() -> u64 {
-    let 今 = chrono::Utc::now().with_hour(4).unwrap();
-    let 今 = 今.with_second(0).unwrap();
-    let 今 = 今.with_minute(0).unwrap();
-    今.timestamp() as u64
-}
-
-pub fn unix_n_days_offset(days: u32) -> u64 {
-    let mut 今 = chrono::Utc::now();
-    今 = 今 + Duration::days(days as i64);
-    今.with_second(0)
-        .unwrap()
-        .with_minute(0)
-        .unwrap()
-        .with_hour(4)
-        .unwrap()
-        .timestamp() as u64
-}
+/// Amount of seconds in a day
+pub const DAY_SECS: u64 = 24 * 60 * 60;
 
 pub fn print_stdout(text: &str) {
     print!("{text}");
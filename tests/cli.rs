@@ -0,0 +1,75 @@
+//! Runs of the binary with its own config and database
+
+use chrono::TimeZone;
+use chrono_tz::Tz;
+use std::{path::Path, process::Command};
+
+/// Runs the binary with its own config and database in `dir`
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_kanji_srs"))
+        .args(args)
+        .arg("--db")
+        .arg(dir.join("db"))
+        .env("KANJI_SRS_CONFIG", dir.join("config.toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn now_flag() {
+    let dir = std::env::temp_dir().join(format!("kanji_srs_now_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("config.toml"),
+        "timezone = 'Asia/Tokyo'\nday_start_hour = 4\n[scheduler]\nlearning_steps = []\nfuzz = false",
+    )
+    .unwrap();
+
+    run(&dir, &["add", "日月"]);
+    let out = run(&dir, &["--now", "2022-03-20 12:00", "review", "日"]);
+    assert!(out.contains("Reviewed 日"), "{out}");
+
+    let out = run(&dir, &["--now", "2022-03-21 03:00", "info"]);
+    assert!(!out.contains("Today"), "{out}");
+    assert!(out.contains("Tomorrow: \n日"), "{out}");
+
+    let out = run(&dir, &["info", "--now", "2022-03-21 04:00"]);
+    assert!(out.contains("Today: 日"), "{out}");
+
+    let out = run(&dir, &["--now", "someday", "info"]);
+    assert!(out.contains("Invalid time"), "{out}");
+
+    let tokyo: Tz = "Asia/Tokyo".parse().unwrap();
+    let reviewed = tokyo.ymd(2022, 3, 20).and_hms(12, 0, 0).timestamp();
+    let db = rusqlite::Connection::open(dir.join("db/kanji_srs.db")).unwrap();
+    let timestamp: i64 = db
+        .query_row("SELECT timestamp FROM review_log", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(reviewed, timestamp);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn info_json() {
+    let dir = std::env::temp_dir().join(format!("kanji_srs_json_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Ordering by strokes needs information about the kanji which isn't cached yet
+    std::fs::write(dir.join("config.toml"), "new_order = 'strokes'").unwrap();
+
+    run(&dir, &["add", "日月"]);
+    let out = run(&dir, &["info", "--format", "json"]);
+    let info: serde_json::Value = serde_json::from_str(&out).expect(&out);
+    assert_eq!(2, info["next"].as_array().unwrap().len());
+
+    // Showing info doesn't look up kanji
+    let db = rusqlite::Connection::open(dir.join("db/kanji_srs.db")).unwrap();
+    let cached: i64 = db
+        .query_row("SELECT count(*) FROM kanji_info", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(0, cached);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}